@@ -1,5 +1,6 @@
-
 import asyncio
+import os
+import random
 import time
 
 
@@ -9,8 +10,101 @@ async def benchmark_rust_vs_python():
     print("Benchmarking Rust vs. Python...")
 
 
+def synthetic_pools(num_pools, num_tokens, seed=0):
+    """Random balanced constant-product pools over `num_tokens` tokens.
+
+    Every pool prices its pair at 1:1, so the graph has no arbitrage loops.
+    """
+    rng = random.Random(seed)
+    pools = []
+    for i in range(num_pools):
+        a, b = rng.sample(range(num_tokens), 2)
+        reserve0 = reserve1 = rng.randint(10**6, 10**9)
+        pools.append(
+            {
+                "address": f"pool{i}",
+                "token0": {"address": f"t{a}", "symbol": f"T{a}"},
+                "token1": {"address": f"t{b}", "symbol": f"T{b}"},
+                "fee": 3000,
+                "liquidity": reserve0 + reserve1,
+                "reserve0": reserve0,
+                "reserve1": reserve1,
+            }
+        )
+    return pools
+
+
+async def benchmark_route_search(num_pools=10_000, num_tokens=2_000, iterations=5):
+    """Wall-clock time of optimize_route on a synthetic graph.
+
+    Run once with RAYON_NUM_THREADS=1 and once without it to compare the
+    sequential and parallel edge relaxation.
+    """
+    import platform_rust
+
+    engine = platform_rust.ExecutionEngine()
+    engine.update_pools(synthetic_pools(num_pools, num_tokens))
+    params = {"token_in": "t0", "token_out": "t1", "amount_in": 10**6}
+    timings = []
+    for i in range(iterations):
+        started = time.perf_counter()
+        # Vary the amount so no call is served from the route cache
+        engine.optimize_route({**params, "amount_in": params["amount_in"] + i})
+        timings.append(time.perf_counter() - started)
+    threads = os.environ.get("RAYON_NUM_THREADS", "default")
+    print(
+        f"optimize_route over {num_pools} pools (RAYON_NUM_THREADS={threads}): "
+        f"best {min(timings) * 1000:.1f} ms, mean {sum(timings) / len(timings) * 1000:.1f} ms"
+    )
+
+
+def hub_pools(degree):
+    """`degree` balanced pools pairing token `hub` with a distinct leaf each."""
+    return [
+        {
+            "address": f"pool{i}",
+            "token0": {"address": "hub", "symbol": "HUB"},
+            "token1": {"address": f"leaf{i}", "symbol": f"LEAF{i}"},
+            "fee": 3000,
+            "liquidity": 2 * 10**9,
+            "reserve0": 10**9,
+            "reserve1": 10**9,
+        }
+        for i in range(degree)
+    ]
+
+
+async def benchmark_hub_expansion(degrees=(1024, 2047, 2048, 4096, 16384), iterations=20):
+    """Time per pool of expanding one token held by `degree` pools.
+
+    Routing out of the hub relaxes every one of its pools once, so the time per
+    pool is the cost of one relaxation. Degrees at and above
+    PARALLEL_RELAX_MIN_POOLS (2048) go through rayon; comparing 2047 with 2048
+    under RAYON_NUM_THREADS=1 isolates rayon's fixed overhead, and running
+    without it shows the speedup on the available cores.
+    """
+    import platform_rust
+
+    threads = os.environ.get("RAYON_NUM_THREADS", "default")
+    for degree in degrees:
+        engine = platform_rust.ExecutionEngine()
+        engine.update_pools(hub_pools(degree))
+        timings = []
+        for i in range(iterations):
+            started = time.perf_counter()
+            engine.optimize_route({"token_in": "hub", "token_out": "leaf0", "amount_in": 10**6 + i})
+            timings.append(time.perf_counter() - started)
+        best = min(timings)
+        print(
+            f"hub of {degree} pools (RAYON_NUM_THREADS={threads}): "
+            f"best {best * 1000:.2f} ms, {best / degree * 1e9:.0f} ns per pool"
+        )
+
+
 async def main():
     await benchmark_rust_vs_python()
+    await benchmark_route_search()
+    await benchmark_hub_expansion()
 
 
 if __name__ == "__main__":
@@ -8,7 +8,8 @@ mock implementations and logs a clear warning.
 """
 
 import structlog
-from typing import Dict, Any, List
+from decimal import Decimal
+from typing import Dict, Any, List, Optional, Tuple
 from .types.rust_bridge import (
     OptimizeRouteParams,
     OptimizeRouteResult,
@@ -68,20 +69,83 @@ def _fallback_simulate_transaction(step: Dict[str, Any]) -> Dict[str, Any]:
     return {"success": True, "gas_used": 150000, "output": "0x..."}
 
 
-def _fallback_aggregate_order_books(books: List[Any]) -> Any:
+def _fallback_aggregate_order_books(books: List[Any], depth: Optional[int] = None) -> Any:
     logger.info("FALLBACK rust_bindings.aggregate_order_books called", num_books=len(books))
-    # Expect normalized entries: {side: 'bid'|'ask', price: int, size: int}
-    bids: List[Any] = []
-    asks: List[Any] = []
-    for e in books:
+    # Mirrors the native aggregation: exact per-price sums, ascending levels and
+    # outputs in the widest input form (int < float < decimal string)
+    levels: Dict[str, Dict[Decimal, Decimal]] = {"bid": {}, "ask": {}}
+    sources: Dict[str, Dict[Decimal, Dict[Optional[str], Decimal]]] = {"bid": {}, "ask": {}}
+    price_kind = size_kind = 0
+    tagged = False
+
+    def exact(value: Any) -> Tuple[Decimal, int]:
+        if isinstance(value, int):
+            return Decimal(value), 0
+        # Float repr is the shortest string that round-trips, e.g. "0.015"
+        return Decimal(str(value)), 1 if isinstance(value, float) else 2
+
+    for index, e in enumerate(books):
         side = e.get("side")
-        price = e.get("price")
-        size = e.get("size")
-        if side == "bid":
-            bids.append((price, size))
-        elif side == "ask":
-            asks.append((price, size))
-    return {"bids": bids, "asks": asks}
+        if side not in levels:
+            raise ValueError(f"unknown side {side!r}")
+        price, kind = exact(e.get("price"))
+        price_kind = max(price_kind, kind)
+        size, kind = exact(e.get("size"))
+        size_kind = max(size_kind, kind)
+        if size < 0:
+            raise ValueError(f"entry {index} has negative size {size}")
+        if size == 0:
+            continue
+        source = e.get("source")
+        tagged = tagged or source is not None
+        levels[side][price] = levels[side].get(price, Decimal(0)) + size
+        by_source = sources[side].setdefault(price, {})
+        by_source[source] = by_source.get(source, Decimal(0)) + size
+
+    def to_py(value: Decimal, kind: int) -> Any:
+        if kind == 0:
+            return int(value)
+        if kind == 1:
+            return float(value)
+        return format(value.normalize(), "f")
+
+    def side_levels(side: str) -> List[Any]:
+        out: List[Any] = []
+        for price, size in sorted(levels[side].items()):
+            level: Tuple[Any, ...] = (to_py(price, price_kind), to_py(size, size_kind))
+            if tagged:
+                by_source = sources[side][price]
+                ordered = sorted(by_source, key=lambda s: (s is not None, s or ""))
+                level += ({s: to_py(by_source[s], size_kind) for s in ordered},)
+            out.append(level)
+        return out
+
+    def vwap(side: str) -> Optional[float]:
+        size = sum(levels[side].values())
+        if not size:
+            return None
+        return float(sum(p * s for p, s in levels[side].items()) / size)
+
+    bids, asks = side_levels("bid"), side_levels("ask")
+    if depth is not None:
+        bids = bids[len(bids) - depth:] if depth < len(bids) else bids
+        asks = asks[:depth]
+    best_bid = max(levels["bid"], default=None)
+    best_ask = min(levels["ask"], default=None)
+    result = {
+        "bids": bids,
+        "asks": asks,
+        "bid_vwap": vwap("bid"),
+        "ask_vwap": vwap("ask"),
+        "best_bid": None if best_bid is None else to_py(best_bid, price_kind),
+        "best_ask": None if best_ask is None else to_py(best_ask, price_kind),
+        "mid_price": None,
+        "spread": None,
+    }
+    if best_bid is not None and best_ask is not None:
+        result["mid_price"] = to_py((best_bid + best_ask) / 2, max(price_kind, 1))
+        result["spread"] = to_py(best_ask - best_bid, price_kind)
+    return result
 
 
 # Public wrappers that delegate to native extension if present
@@ -128,14 +192,14 @@ def simulate_transaction(step: Dict[str, Any]) -> Dict[str, Any]:
     return _fallback_simulate_transaction(step)
 
 
-def aggregate_order_books(books: List[Any]) -> Any:
+def aggregate_order_books(books: List[Any], depth: Optional[int] = None) -> Any:
     # Validate/normalize entries
     entries = [AggregateOrderBookEntry.model_validate(b).model_dump() for b in books]
     if _platform_rust is not None and hasattr(_platform_rust, "aggregate_order_books"):
         try:
-            result = _platform_rust.aggregate_order_books(entries)
+            result = _platform_rust.aggregate_order_books(entries, depth=depth)
             return AggregateOrderBooksOutput.model_validate(result).model_dump()
         except Exception as e:
             logger.error("Native aggregate_order_books failed, falling back", error=str(e))
-            return _fallback_aggregate_order_books(entries)
-    return _fallback_aggregate_order_books(entries)
+            return _fallback_aggregate_order_books(entries, depth)
+    return _fallback_aggregate_order_books(entries, depth)
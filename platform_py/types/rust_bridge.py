@@ -2,7 +2,8 @@
 Typed models for Rust bridge IO validation.
 """
 
-from typing import List, Optional, Literal, Tuple
+from decimal import Decimal
+from typing import Dict, List, Optional, Literal, Tuple, Union
 from pydantic import BaseModel, Field, field_validator
 
 
@@ -17,15 +18,33 @@ class OptimizeRouteResult(BaseModel):
     output_amount: int = Field(..., ge=0, description="Estimated output amount (raw units)")
 
 
+# Book numbers may be fractional; the native side aggregates them exactly
+BookNumber = Union[int, float, Decimal, str]
+
+
 class AggregateOrderBookEntry(BaseModel):
     side: Literal["bid", "ask"]
-    price: int = Field(..., ge=0)
-    size: int = Field(..., ge=0)
+    price: Union[int, float, Decimal] = Field(..., ge=0)
+    size: Union[int, float, Decimal] = Field(..., ge=0)
+    source: Optional[str] = Field(None, description="Venue that quoted this entry")
+
+
+# (price, size), or (price, size, {source: size}) when entries name a source
+BookLevel = Union[
+    Tuple[BookNumber, BookNumber],
+    Tuple[BookNumber, BookNumber, Dict[Optional[str], BookNumber]],
+]
 
 
 class AggregateOrderBooksOutput(BaseModel):
-    bids: List[Tuple[int, int]]
-    asks: List[Tuple[int, int]]
+    bids: List[BookLevel]
+    asks: List[BookLevel]
+    bid_vwap: Optional[float] = None
+    ask_vwap: Optional[float] = None
+    best_bid: Optional[BookNumber] = None
+    best_ask: Optional[BookNumber] = None
+    mid_price: Optional[Union[float, str]] = None
+    spread: Optional[BookNumber] = None
 
 
 class DecodedTransaction(BaseModel):
@@ -34,9 +53,12 @@ class DecodedTransaction(BaseModel):
     nonce: Optional[int] = None
     gas: Optional[int] = None
     gas_price: Optional[int] = Field(None, alias="gas_price")
+    max_fee_per_gas: Optional[int] = None
+    max_priority_fee_per_gas: Optional[int] = None
     value: Optional[int] = None
     input: Optional[str] = None
     hash: Optional[str] = None
+    created_contract: Optional[str] = None
 
     @field_validator("input", mode='before')
     def ensure_hex_prefix(cls, v: Optional[str]) -> Optional[str]:
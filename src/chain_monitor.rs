@@ -1,58 +1,1357 @@
-use pyo3::exceptions::PyValueError;
+use pyo3::exceptions::{PyStopAsyncIteration, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
+use pyo3::types::{PyDict, PyInt, PyList};
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
 
 // Alloy replaces ethers for transaction decoding and signer recovery.
-use alloy_consensus::transaction::{EthereumTxEnvelope, SignerRecoverable, Transaction, TxEip4844};
+use alloy_consensus::transaction::{
+    to_eip155_value, EthereumTxEnvelope, SignerRecoverable, Transaction, TxEip4844,
+};
+use alloy_primitives::{keccak256, Address, I256, U256, U512};
 use alloy_rlp::Decodable;
+use rayon::prelude::*;
 
-#[pyfunction]
-fn decode_transaction(py: Python<'_>, tx_hex: &str) -> PyResult<PyObject> {
+use crate::metrics::METRICS;
+use crate::ws::WsStream;
+use crate::{runtime_handle, LoopFuture};
+
+pub(crate) type TxEnvelope = EthereumTxEnvelope<TxEip4844>;
+
+/// Hex-decode a raw transaction and parse its envelope, keeping the raw bytes.
+pub(crate) fn decode_envelope(tx_hex: &str) -> PyResult<(Vec<u8>, TxEnvelope)> {
+    decode_envelope_raw(tx_hex).map_err(PyValueError::new_err)
+}
+
+/// `decode_envelope` without Python errors, for use outside the GIL or where
+/// a bad transaction should not abort the caller.
+pub(crate) fn decode_envelope_raw(tx_hex: &str) -> Result<(Vec<u8>, TxEnvelope), String> {
     // Strip optional 0x and decode hex
     let raw = tx_hex.trim_start_matches("0x");
-    let bytes = hex::decode(raw).map_err(|e| PyValueError::new_err(format!("invalid hex: {e}")))?;
-    let mut slice: &[u8] = &bytes;
+    let bytes = hex::decode(raw).map_err(|e| format!("invalid hex: {e}"))?;
+    let envelope = parse_envelope_raw(&bytes)?;
+    Ok((bytes, envelope))
+}
+
+/// Decode raw bytes as an EIP-2718 envelope (supports legacy/1559/2930/7702/4844)
+pub(crate) fn parse_envelope(bytes: &[u8]) -> PyResult<TxEnvelope> {
+    parse_envelope_raw(bytes).map_err(PyValueError::new_err)
+}
+
+fn parse_envelope_raw(bytes: &[u8]) -> Result<TxEnvelope, String> {
+    let mut slice: &[u8] = bytes;
+    let envelope: TxEnvelope =
+        Decodable::decode(&mut slice).map_err(|e| format!("rlp decode failed: {e}"))?;
+    METRICS.transactions_decoded.fetch_add(1, Ordering::Relaxed);
+    Ok(envelope)
+}
+
+/// `human=True` adds `value_eth` and `gas_price_gwei` floats for display
+/// (`gas_price_gwei` is the fee cap for dynamic-fee types). They are rounded
+/// to f64 (about 15-16 significant digits), so the exact string fields remain
+/// the source of truth.
+///
+/// With `recover_sender=False`, or when the signature does not recover, `from`
+/// is None and the remaining fields are still decoded.
+#[pyfunction]
+#[pyo3(signature = (tx_hex, include_offsets=false, human=false, recover_sender=true))]
+fn decode_transaction(
+    py: Python<'_>,
+    tx_hex: &str,
+    include_offsets: bool,
+    human: bool,
+    recover_sender: bool,
+) -> PyResult<PyObject> {
+    let (bytes, envelope) = decode_envelope(tx_hex)?;
+    let from = if recover_sender {
+        envelope.recover_signer().ok()
+    } else {
+        None
+    };
+    let out = transaction_dict_from(py, &bytes, &envelope, from, include_offsets)?;
+    if human {
+        let gas_price = envelope
+            .gas_price()
+            .unwrap_or_else(|| envelope.max_fee_per_gas());
+        out.set_item("value_eth", scaled_f64(envelope.value(), 18))?;
+        out.set_item("gas_price_gwei", scaled_f64(U256::from(gas_price), 9))?;
+    }
+    Ok(out.into_any().unbind())
+}
+
+/// Decode a batch of raw transactions, e.g. a mempool snapshot. Decoding and
+/// signer recovery run in parallel without the GIL. A malformed entry yields
+/// `{"error": "..."}` in its place instead of failing the batch.
+#[pyfunction]
+fn decode_transactions(py: Python<'_>, tx_hexes: Vec<String>) -> PyResult<PyObject> {
+    // Raw bytes, envelope and recovered sender of each entry
+    type Recovered = (Vec<u8>, TxEnvelope, Address);
+    let decoded: Vec<Result<Recovered, String>> = py.allow_threads(|| {
+        tx_hexes
+            .par_iter()
+            .map(|tx_hex| {
+                let (bytes, envelope) = decode_envelope_raw(tx_hex)?;
+                let from = envelope
+                    .recover_signer()
+                    .map_err(|e| format!("failed to recover signer: {e}"))?;
+                Ok((bytes, envelope, from))
+            })
+            .collect()
+    });
+    let out = PyList::empty(py);
+    for entry in decoded {
+        match entry {
+            Ok((bytes, envelope, from)) => out.append(transaction_dict_from(
+                py,
+                &bytes,
+                &envelope,
+                Some(from),
+                false,
+            )?)?,
+            Err(error) => {
+                let failed = PyDict::new(py);
+                failed.set_item("error", error)?;
+                out.append(failed)?;
+            }
+        }
+    }
+    Ok(out.unbind().into_any())
+}
+
+/// `value / 10^decimals` as a float, rounded once from the exact decimal string.
+fn scaled_f64(value: U256, decimals: i32) -> f64 {
+    let whole: f64 = value.to_string().parse().unwrap_or(f64::INFINITY);
+    whole / 10f64.powi(decimals)
+}
+
+/// Decode every transaction in a raw RLP block (`[header, transactions, ...]`).
+#[pyfunction]
+fn decode_block_transactions(py: Python<'_>, block_rlp_hex: &str) -> PyResult<PyObject> {
+    let raw = block_rlp_hex.trim_start_matches("0x");
+    let block = hex::decode(raw).map_err(|e| PyValueError::new_err(format!("invalid hex: {e}")))?;
+    let err = |e: String| PyValueError::new_err(format!("invalid block: {e}"));
+    // End of the item at `pos`, which must not run past `limit`, the end of
+    // the list holding it
+    let item_end = |pos: usize, header: usize, payload: usize, limit: usize| {
+        pos.checked_add(header)
+            .and_then(|start| start.checked_add(payload))
+            .filter(|&end| end <= limit)
+            .ok_or_else(|| err(format!("item at offset {pos} runs past its enclosing list")))
+    };
+
+    let (block_header, block_payload) = rlp_item_header(&block, 0).map_err(err)?;
+    let block_end = item_end(0, block_header, block_payload, block.len())?;
+    let (header_len, header_payload) = rlp_item_header(&block, block_header).map_err(err)?;
+    let txs_start = item_end(block_header, header_len, header_payload, block_end)?;
+    let (txs_header, txs_payload) = rlp_item_header(&block, txs_start).map_err(err)?;
+    let end = item_end(txs_start, txs_header, txs_payload, block_end)?;
 
-    // Decode as EIP-2718 envelope (supports legacy/1559/2930/7702/4844)
-    let envelope: EthereumTxEnvelope<TxEip4844> = Decodable::decode(&mut slice)
-        .map_err(|e| PyValueError::new_err(format!("rlp decode failed: {e}")))?;
+    let out = PyList::empty(py);
+    let mut cursor = txs_start + txs_header;
+    while cursor < end {
+        let (item_header, item_payload) = rlp_item_header(&block, cursor).map_err(err)?;
+        let next = item_end(cursor, item_header, item_payload, end)?;
+        // Legacy transactions are inline lists; typed ones are byte strings of type || payload
+        let tx_bytes = if block[cursor] >= 0xc0 {
+            &block[cursor..next]
+        } else {
+            &block[cursor + item_header..next]
+        };
+        let envelope = parse_envelope(tx_bytes)?;
+        out.append(transaction_dict(py, tx_bytes, &envelope, false)?)?;
+        cursor = next;
+    }
+    Ok(out.unbind().into_any())
+}
 
+fn transaction_dict<'py>(
+    py: Python<'py>,
+    bytes: &[u8],
+    envelope: &TxEnvelope,
+    include_offsets: bool,
+) -> PyResult<Bound<'py, PyDict>> {
     // Recover sender (requires alloy-consensus k256 feature)
     let from = envelope
         .recover_signer()
         .map_err(|e| PyValueError::new_err(format!("failed to recover signer: {e}")))?;
+    transaction_dict_from(py, bytes, envelope, Some(from), include_offsets)
+}
 
+/// `transaction_dict` for a sender that has already been recovered, or `None`
+/// when it is unknown.
+fn transaction_dict_from<'py>(
+    py: Python<'py>,
+    bytes: &[u8],
+    envelope: &TxEnvelope,
+    from: Option<Address>,
+    include_offsets: bool,
+) -> PyResult<Bound<'py, PyDict>> {
     let out = PyDict::new(py);
+    // EIP-2718 type byte and its name
+    let tx_type: u8 = envelope.tx_type().into();
+    out.set_item("tx_type", tx_type)?;
+    out.set_item("tx_type_name", tx_type_name(tx_type))?;
+    // None for pre-EIP-155 legacy transactions
+    out.set_item("chain_id", envelope.chain_id())?;
+    // Raw signature; legacy transactions also report the encoded `v`
+    let signature = envelope.signature();
+    let sig = PyDict::new(py);
+    sig.set_item("r", format!("{:#066x}", signature.r()))?;
+    sig.set_item("s", format!("{:#066x}", signature.s()))?;
+    sig.set_item("y_parity", u8::from(signature.v()))?;
+    if envelope.is_legacy() {
+        sig.set_item("v", to_eip155_value(signature.v(), envelope.chain_id()))?;
+    }
+    out.set_item("signature", sig)?;
     // Sender
-    out.set_item("from", format!("{:#x}", from))?;
+    out.set_item("from", from.as_ref().map(checksum))?;
     // Recipient (or None for contract creation)
     match envelope.to() {
-        Some(to) => out.set_item("to", format!("{:#x}", to))?,
-        None => out.set_item("to", Option::<String>::None)?,
+        Some(to) => out.set_item("to", checksum(&to))?,
+        None => {
+            out.set_item("to", Option::<String>::None)?;
+            // CREATE address: keccak256(rlp([sender, nonce]))[12..]
+            if let Some(from) = from {
+                out.set_item("created_contract", checksum(&from.create(envelope.nonce())))?;
+            }
+        }
     }
     // Nonce
     out.set_item("nonce", envelope.nonce())?;
     // Gas limit as string to preserve width
     out.set_item("gas", envelope.gas_limit().to_string())?;
-    // Gas price normalization: legacy has price; dynamic fee uses fee cap
-    if let Some(price) = envelope.gas_price() {
-        out.set_item("gas_price", price.to_string())?;
-    } else {
-        let fee_cap = envelope.max_fee_per_gas();
-        out.set_item("gas_price", fee_cap.to_string())?;
-    }
+    // Fees: legacy and EIP-2930 pay a gas price; dynamic-fee types carry both caps
+    // instead, and the keys that do not apply are None
+    out.set_item(
+        "gas_price",
+        envelope.gas_price().map(|price| price.to_string()),
+    )?;
+    let dynamic = envelope.is_dynamic_fee();
+    out.set_item(
+        "max_fee_per_gas",
+        dynamic.then(|| envelope.max_fee_per_gas().to_string()),
+    )?;
+    out.set_item(
+        "max_priority_fee_per_gas",
+        envelope
+            .max_priority_fee_per_gas()
+            .filter(|_| dynamic)
+            .map(|fee| fee.to_string()),
+    )?;
     // Value
     out.set_item("value", envelope.value().to_string())?;
     // Input data hex
     let input = envelope.input();
     out.set_item("input", format!("0x{}", hex::encode(input.as_ref())))?;
+    // Blob fields, only on EIP-4844 transactions
+    if let Some(hashes) = envelope.blob_versioned_hashes() {
+        let hashes: Vec<String> = hashes.iter().map(|h| format!("{:#x}", h)).collect();
+        out.set_item("blob_versioned_hashes", hashes)?;
+    }
+    if let Some(fee) = envelope.max_fee_per_blob_gas() {
+        out.set_item("max_fee_per_blob_gas", fee.to_string())?;
+    }
+    // EIP-7702 delegations, each with its recovered authority (None if invalid)
+    if let Some(authorizations) = envelope.authorization_list() {
+        let list = PyList::empty(py);
+        for auth in authorizations {
+            let entry = PyDict::new(py);
+            entry.set_item("chain_id", u256_to_py(py, *auth.chain_id())?)?;
+            entry.set_item("address", checksum(auth.address()))?;
+            entry.set_item("nonce", auth.nonce())?;
+            entry.set_item("y_parity", auth.y_parity())?;
+            entry.set_item("r", format!("{:#066x}", auth.r()))?;
+            entry.set_item("s", format!("{:#066x}", auth.s()))?;
+            entry.set_item(
+                "authority",
+                auth.recover_authority().ok().as_ref().map(checksum),
+            )?;
+            list.append(entry)?;
+        }
+        out.set_item("authorization_list", list)?;
+    }
+    // Access list entries; empty for legacy transactions
+    out.set_item("access_list", access_list_to_py(py, envelope)?)?;
+    // Heuristic label from the calldata selector
+    out.set_item(
+        "action",
+        classify_action(envelope.to().is_some(), input.as_ref()),
+    )?;
     // Transaction hash
     let hash = envelope.tx_hash();
     out.set_item("hash", format!("{:#x}", hash))?;
-    Ok(out.unbind().into_any())
+    // Byte spans of each RLP field, for patching the raw transaction in place
+    if include_offsets {
+        let offsets = PyDict::new(py);
+        for (name, start, len) in rlp_field_offsets(bytes).map_err(PyValueError::new_err)? {
+            offsets.set_item(name, (start, len))?;
+        }
+        out.set_item("field_offsets", offsets)?;
+    }
+    Ok(out)
+}
+
+fn tx_type_name(tx_type: u8) -> &'static str {
+    match tx_type {
+        0 => "legacy",
+        1 => "eip2930",
+        2 => "eip1559",
+        3 => "eip4844",
+        4 => "eip7702",
+        _ => "unknown",
+    }
+}
+
+/// Selectors of common Uniswap-style router swap entry points.
+const SWAP_SELECTORS: [[u8; 4]; 14] = [
+    [0x38, 0xed, 0x17, 0x39], // swapExactTokensForTokens
+    [0x88, 0x03, 0xdb, 0xee], // swapTokensForExactTokens
+    [0x7f, 0xf3, 0x6a, 0xb5], // swapExactETHForTokens
+    [0x4a, 0x25, 0xd9, 0x4a], // swapTokensForExactETH
+    [0x18, 0xcb, 0xaf, 0xe5], // swapExactTokensForETH
+    [0xfb, 0x3b, 0xdb, 0x41], // swapETHForExactTokens
+    [0x5c, 0x11, 0xd7, 0x95], // swapExactTokensForTokensSupportingFeeOnTransferTokens
+    [0xb6, 0xf9, 0xde, 0x95], // swapExactETHForTokensSupportingFeeOnTransferTokens
+    [0x79, 0x1a, 0xc9, 0x47], // swapExactTokensForETHSupportingFeeOnTransferTokens
+    [0x41, 0x4b, 0xf3, 0x89], // exactInputSingle
+    [0xc0, 0x4b, 0x8d, 0x59], // exactInput
+    [0xdb, 0x3e, 0x21, 0x98], // exactOutputSingle
+    [0xf2, 0x8c, 0x04, 0x98], // exactOutput
+    [0x35, 0x93, 0x56, 0x4c], // Universal Router execute(bytes,bytes[],uint256)
+];
+
+/// Label a call without its ABI: "contract_creation", "eth_transfer" (no
+/// calldata), "erc20_transfer", "approve", "swap" (known router selectors) or
+/// "unknown".
+fn classify_action(has_recipient: bool, input: &[u8]) -> &'static str {
+    if !has_recipient {
+        return "contract_creation";
+    }
+    if input.is_empty() {
+        return "eth_transfer";
+    }
+    let Some(selector) = input.get(..4) else {
+        return "unknown";
+    };
+    match selector {
+        [0xa9, 0x05, 0x9c, 0xbb] => "erc20_transfer",
+        [0x09, 0x5e, 0xa7, 0xb3] => "approve",
+        s if SWAP_SELECTORS.iter().any(|known| known == s) => "swap",
+        _ => "unknown",
+    }
+}
+
+/// Typed view over a decoded transaction; `to_dict()` returns the
+/// `decode_transaction` dict. Attributes cover a subset of its keys under the
+/// same names, except that the sender is `from_` (`from` is a Python keyword)
+/// and the dict's `gas` is `gas_limit`. `value`, `gas_limit` and the fee
+/// attributes are Python ints where the dict holds decimal strings.
+#[pyclass]
+pub struct DecodedTx {
+    bytes: Vec<u8>,
+    envelope: TxEnvelope,
+    // None when the signature does not recover
+    sender: Option<Address>,
+}
+
+#[pymethods]
+impl DecodedTx {
+    #[new]
+    fn new(tx_hex: &str) -> PyResult<Self> {
+        let (bytes, envelope) = decode_envelope(tx_hex)?;
+        let sender = envelope.recover_signer().ok();
+        Ok(Self {
+            bytes,
+            envelope,
+            sender,
+        })
+    }
+
+    #[getter]
+    fn tx_type(&self) -> u8 {
+        self.envelope.tx_type().into()
+    }
+
+    #[getter]
+    fn chain_id(&self) -> Option<u64> {
+        self.envelope.chain_id()
+    }
+
+    #[getter]
+    fn nonce(&self) -> u64 {
+        self.envelope.nonce()
+    }
+
+    /// None when the signature does not recover, like the dict's `from`.
+    #[getter]
+    fn from_(&self) -> Option<String> {
+        self.sender.as_ref().map(checksum)
+    }
+
+    #[getter]
+    fn to(&self) -> Option<String> {
+        self.envelope.to().as_ref().map(checksum)
+    }
+
+    #[getter]
+    fn value<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        u256_to_py(py, self.envelope.value())
+    }
+
+    #[getter]
+    fn gas_limit(&self) -> u64 {
+        self.envelope.gas_limit()
+    }
+
+    #[getter]
+    fn gas_price(&self) -> Option<u128> {
+        self.envelope.gas_price()
+    }
+
+    /// None unless the transaction is a dynamic-fee type, as in the dict.
+    #[getter]
+    fn max_fee_per_gas(&self) -> Option<u128> {
+        self.envelope
+            .is_dynamic_fee()
+            .then(|| self.envelope.max_fee_per_gas())
+    }
+
+    /// None unless the transaction is a dynamic-fee type, as in the dict.
+    #[getter]
+    fn max_priority_fee_per_gas(&self) -> Option<u128> {
+        self.envelope
+            .max_priority_fee_per_gas()
+            .filter(|_| self.envelope.is_dynamic_fee())
+    }
+
+    #[getter]
+    fn access_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        access_list_to_py(py, &self.envelope)
+    }
+
+    #[getter]
+    fn input(&self) -> String {
+        format!("0x{}", hex::encode(self.envelope.input().as_ref()))
+    }
+
+    #[getter]
+    fn hash(&self) -> String {
+        format!("{:#x}", self.envelope.tx_hash())
+    }
+
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(
+            transaction_dict_from(py, &self.bytes, &self.envelope, self.sender, false)?
+                .into_any()
+                .unbind(),
+        )
+    }
+}
+
+/// EIP-55 mixed-case spelling used for every address this module returns.
+pub(crate) fn checksum(address: &Address) -> String {
+    address.to_checksum(None)
+}
+
+/// EIP-55 checksum of a 20-byte hex address, with or without the `0x` prefix.
+#[pyfunction]
+fn checksum_address(addr: &str) -> PyResult<String> {
+    let digits = addr.strip_prefix("0x").unwrap_or(addr);
+    if digits.len() != 40 {
+        return Err(PyValueError::new_err(format!(
+            "address must be 20 bytes (40 hex digits), got {} digits",
+            digits.len()
+        )));
+    }
+    let address: Address = digits
+        .parse()
+        .map_err(|e| PyValueError::new_err(format!("invalid address {addr:?}: {e}")))?;
+    Ok(checksum(&address))
+}
+
+/// Python int for a U256 without truncating to a machine width.
+fn u256_to_py(py: Python<'_>, value: U256) -> PyResult<Bound<'_, PyAny>> {
+    py.get_type::<PyInt>().call1((value.to_string(),))
+}
+
+/// `[{address, storage_keys}]` with hex values; empty for types without one.
+fn access_list_to_py<'py>(py: Python<'py>, envelope: &TxEnvelope) -> PyResult<Bound<'py, PyList>> {
+    let out = PyList::empty(py);
+    for item in envelope
+        .access_list()
+        .map(|list| list.0.as_slice())
+        .unwrap_or_default()
+    {
+        let entry = PyDict::new(py);
+        entry.set_item("address", checksum(&item.address))?;
+        let keys: Vec<String> = item
+            .storage_keys
+            .iter()
+            .map(|k| format!("{:#x}", k))
+            .collect();
+        entry.set_item("storage_keys", keys)?;
+        out.append(entry)?;
+    }
+    Ok(out)
+}
+
+/// Walk the raw encoding and return `(field, start, len)` for every top-level
+/// transaction field. Spans cover the full RLP item (header plus payload).
+fn rlp_field_offsets(bytes: &[u8]) -> Result<Vec<(&'static str, usize, usize)>, String> {
+    let mut pos = 0usize;
+    // Network form wraps typed transactions in an RLP string header
+    if matches!(bytes.first(), Some(0x80..=0xbf)) {
+        let (header_len, _) = rlp_item_header(bytes, pos)?;
+        pos += header_len;
+    }
+    let ty = match bytes.get(pos) {
+        Some(&b) if b < 0x80 => {
+            pos += 1;
+            b
+        }
+        Some(_) => 0,
+        None => return Err("empty transaction".to_string()),
+    };
+    let names: &[&'static str] = match ty {
+        0 => &[
+            "nonce",
+            "gas_price",
+            "gas_limit",
+            "to",
+            "value",
+            "input",
+            "v",
+            "r",
+            "s",
+        ],
+        1 => &[
+            "chain_id",
+            "nonce",
+            "gas_price",
+            "gas_limit",
+            "to",
+            "value",
+            "input",
+            "access_list",
+            "y_parity",
+            "r",
+            "s",
+        ],
+        2 => &[
+            "chain_id",
+            "nonce",
+            "max_priority_fee_per_gas",
+            "max_fee_per_gas",
+            "gas_limit",
+            "to",
+            "value",
+            "input",
+            "access_list",
+            "y_parity",
+            "r",
+            "s",
+        ],
+        3 => &[
+            "chain_id",
+            "nonce",
+            "max_priority_fee_per_gas",
+            "max_fee_per_gas",
+            "gas_limit",
+            "to",
+            "value",
+            "input",
+            "access_list",
+            "max_fee_per_blob_gas",
+            "blob_versioned_hashes",
+            "y_parity",
+            "r",
+            "s",
+        ],
+        4 => &[
+            "chain_id",
+            "nonce",
+            "max_priority_fee_per_gas",
+            "max_fee_per_gas",
+            "gas_limit",
+            "to",
+            "value",
+            "input",
+            "access_list",
+            "authorization_list",
+            "y_parity",
+            "r",
+            "s",
+        ],
+        other => return Err(format!("unsupported transaction type {other}")),
+    };
+
+    let (mut header_len, mut payload_len) = rlp_item_header(bytes, pos)?;
+    // Blob transactions with a sidecar nest the signed transaction as the first item
+    if ty == 3 && matches!(bytes.get(pos + header_len), Some(0xc0..=0xff)) {
+        pos += header_len;
+        (header_len, payload_len) = rlp_item_header(bytes, pos)?;
+    }
+    let mut cursor = pos + header_len;
+    let end = cursor + payload_len;
+    let mut fields = Vec::with_capacity(names.len());
+    for name in names {
+        if cursor >= end {
+            return Err(format!("transaction list ended before field '{name}'"));
+        }
+        let (item_header, item_payload) = rlp_item_header(bytes, cursor)?;
+        let len = item_header + item_payload;
+        fields.push((*name, cursor, len));
+        cursor += len;
+    }
+    Ok(fields)
+}
+
+/// Return `(header_len, payload_len)` of the RLP item starting at `pos`.
+fn rlp_item_header(bytes: &[u8], pos: usize) -> Result<(usize, usize), String> {
+    let mut buf = bytes.get(pos..).ok_or("offset out of range")?;
+    let before = buf.len();
+    let header = alloy_rlp::Header::decode(&mut buf).map_err(|e| format!("rlp header: {e}"))?;
+    // Single bytes below 0x80 are their own payload, so no header is consumed
+    Ok((before - buf.len(), header.payload_length))
+}
+
+/// Next nonce `sender` is expected to use given its pending transactions:
+/// the highest nonce seen plus one, or `None` when none are from `sender`.
+#[pyfunction]
+fn next_nonce(tx_hexes: Vec<String>, sender: &str) -> PyResult<Option<u64>> {
+    let sender = sender.to_lowercase();
+    let mut highest: Option<u64> = None;
+    for tx_hex in &tx_hexes {
+        let (_, envelope) = decode_envelope(tx_hex)?;
+        let from = envelope
+            .recover_signer()
+            .map_err(|e| PyValueError::new_err(format!("failed to recover signer: {e}")))?;
+        if format!("{from:#x}") == sender {
+            highest = highest.max(Some(envelope.nonce()));
+        }
+    }
+    highest
+        .map(|n| {
+            n.checked_add(1)
+                .ok_or_else(|| PyValueError::new_err("nonce overflow"))
+        })
+        .transpose()
+}
+
+/// EIP-4844 blob base fee in wei for a block's `excess_blob_gas`.
+/// `update_fraction` defaults to the Cancun value; pass 5007716 for Prague.
+#[pyfunction]
+#[pyo3(signature = (excess_blob_gas, update_fraction=3_338_477))]
+fn blob_base_fee(excess_blob_gas: u64, update_fraction: u64) -> PyResult<u128> {
+    const MIN_BASE_FEE_PER_BLOB_GAS: u64 = 1;
+    if update_fraction == 0 {
+        return Err(PyValueError::new_err("update_fraction must be positive"));
+    }
+    fake_exponential(MIN_BASE_FEE_PER_BLOB_GAS, excess_blob_gas, update_fraction)
+        .ok_or_else(|| PyValueError::new_err("blob base fee exceeds u128"))
+}
+
+/// Integer approximation of `factor * e^(numerator / denominator)` from EIP-4844,
+/// or `None` if it exceeds `u128::MAX`. Every term is positive, so the sum
+/// stops as soon as it passes that bound rather than running out the series.
+fn fake_exponential(factor: u64, numerator: u64, denominator: u64) -> Option<u128> {
+    let (numerator, denominator) = (U512::from(numerator), U512::from(denominator));
+    // The output exceeds u128::MAX once the scaled sum reaches this
+    let limit = (U512::from(u128::MAX) + U512::from(1u8)) * denominator;
+    let mut output = U512::ZERO;
+    let mut accum = U512::from(factor) * denominator;
+    let mut i = U512::from(1u8);
+    while !accum.is_zero() {
+        output += accum;
+        if output >= limit {
+            return None;
+        }
+        // accum stays below limit < 2^192, so the product fits in U512
+        accum = accum * numerator / (denominator * i);
+        i += U512::from(1u8);
+    }
+    Some((output / denominator).to::<u128>())
+}
+
+/// Solidity parameter type understood by `decode_calldata`.
+#[derive(Clone, Debug)]
+enum AbiType {
+    Address,
+    Bool,
+    Uint(usize),
+    Int(usize),
+    FixedBytes(usize),
+    Bytes,
+    String,
+    Array(Box<AbiType>),
+    FixedArray(Box<AbiType>, usize),
+    Tuple(Vec<AbiType>),
+}
+
+impl AbiType {
+    fn parse(ty: &str) -> Result<Self, String> {
+        let ty = ty.trim();
+        if let Some(inner) = ty.strip_suffix(']') {
+            let open = inner
+                .rfind('[')
+                .ok_or_else(|| format!("unbalanced brackets in type {ty:?}"))?;
+            let element = Box::new(Self::parse(&inner[..open])?);
+            return match &inner[open + 1..] {
+                "" => Ok(Self::Array(element)),
+                len => len
+                    .parse()
+                    .map(|len| Self::FixedArray(element, len))
+                    .map_err(|_| format!("invalid array length in type {ty:?}")),
+            };
+        }
+        if let Some(inner) = ty.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            return split_abi_params(inner)?
+                .into_iter()
+                .map(Self::parse)
+                .collect::<Result<_, _>>()
+                .map(Self::Tuple);
+        }
+        let sized = |prefix: &str, default: usize| -> Option<Result<usize, String>> {
+            let bits = ty.strip_prefix(prefix)?;
+            if bits.is_empty() {
+                return Some(Ok(default));
+            }
+            Some(bits.parse().map_err(|_| format!("unknown type {ty:?}")))
+        };
+        match ty {
+            "address" => Ok(Self::Address),
+            "bool" => Ok(Self::Bool),
+            "string" => Ok(Self::String),
+            "bytes" => Ok(Self::Bytes),
+            _ => {
+                if let Some(bits) = sized("uint", 256) {
+                    let bits = bits?;
+                    if bits == 0 || bits > 256 || bits % 8 != 0 {
+                        return Err(format!("invalid integer width in type {ty:?}"));
+                    }
+                    Ok(Self::Uint(bits))
+                } else if let Some(bits) = sized("int", 256) {
+                    let bits = bits?;
+                    if bits == 0 || bits > 256 || bits % 8 != 0 {
+                        return Err(format!("invalid integer width in type {ty:?}"));
+                    }
+                    Ok(Self::Int(bits))
+                } else if let Some(len) = ty.strip_prefix("bytes") {
+                    match len.parse() {
+                        Ok(len @ 1..=32) => Ok(Self::FixedBytes(len)),
+                        _ => Err(format!("invalid fixed bytes length in type {ty:?}")),
+                    }
+                } else {
+                    Err(format!("unknown type {ty:?}"))
+                }
+            }
+        }
+    }
+
+    /// Canonical spelling used when hashing the selector.
+    fn canonical(&self) -> String {
+        match self {
+            Self::Address => "address".into(),
+            Self::Bool => "bool".into(),
+            Self::Uint(bits) => format!("uint{bits}"),
+            Self::Int(bits) => format!("int{bits}"),
+            Self::FixedBytes(len) => format!("bytes{len}"),
+            Self::Bytes => "bytes".into(),
+            Self::String => "string".into(),
+            Self::Array(element) => format!("{}[]", element.canonical()),
+            Self::FixedArray(element, len) => format!("{}[{len}]", element.canonical()),
+            Self::Tuple(types) => format!(
+                "({})",
+                types
+                    .iter()
+                    .map(Self::canonical)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+
+    fn is_dynamic(&self) -> bool {
+        match self {
+            Self::Bytes | Self::String | Self::Array(_) => true,
+            Self::FixedArray(element, _) => element.is_dynamic(),
+            Self::Tuple(types) => types.iter().any(Self::is_dynamic),
+            _ => false,
+        }
+    }
+
+    /// Bytes the type occupies in the head of its enclosing tuple. Errors
+    /// when a fixed array is too long for the size to fit in a `usize`.
+    fn head_size(&self) -> Result<usize, String> {
+        let too_large = || format!("type {} is too large", self.canonical());
+        match self {
+            _ if self.is_dynamic() => Ok(32),
+            Self::FixedArray(element, len) => {
+                element.head_size()?.checked_mul(*len).ok_or_else(too_large)
+            }
+            Self::Tuple(types) => types.iter().try_fold(0usize, |size, ty| {
+                size.checked_add(ty.head_size()?).ok_or_else(too_large)
+            }),
+            _ => Ok(32),
+        }
+    }
+}
+
+/// Split a parameter list on top-level commas.
+fn split_abi_params(params: &str) -> Result<Vec<&str>, String> {
+    if params.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let (mut out, mut depth, mut start) = (Vec::new(), 0usize, 0);
+    for (i, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| format!("unbalanced parentheses in {params:?}"))?
+            }
+            ',' if depth == 0 => {
+                out.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(format!("unbalanced parentheses in {params:?}"));
+    }
+    out.push(&params[start..]);
+    Ok(out)
+}
+
+fn abi_word(data: &[u8], pos: usize) -> Result<&[u8; 32], String> {
+    pos.checked_add(32)
+        .and_then(|end| data.get(pos..end))
+        .map(|word| word.try_into().expect("32-byte slice"))
+        .ok_or_else(|| format!("calldata truncated at byte {pos}"))
+}
+
+fn abi_usize(data: &[u8], pos: usize) -> Result<usize, String> {
+    usize::try_from(U256::from_be_bytes(*abi_word(data, pos)?))
+        .map_err(|_| format!("offset or length at byte {pos} is out of range"))
+}
+
+/// Decode a tuple encoding whose offsets are relative to the start of `frame`.
+fn decode_abi_sequence<'py>(
+    py: Python<'py>,
+    types: &[AbiType],
+    frame: &[u8],
+) -> PyResult<Bound<'py, PyList>> {
+    let out = PyList::empty(py);
+    let mut head = 0;
+    for ty in types {
+        if ty.is_dynamic() {
+            let offset = abi_usize(frame, head).map_err(PyValueError::new_err)?;
+            let tail = frame.get(offset..).ok_or_else(|| {
+                PyValueError::new_err(format!("offset {offset} points past the calldata"))
+            })?;
+            out.append(decode_abi_value(py, ty, tail)?)?;
+        } else {
+            out.append(decode_abi_value(
+                py,
+                ty,
+                frame.get(head..).unwrap_or_default(),
+            )?)?;
+        }
+        let size = ty.head_size().map_err(PyValueError::new_err)?;
+        head = head
+            .checked_add(size)
+            .ok_or_else(|| PyValueError::new_err("calldata head is too large"))?;
+    }
+    Ok(out)
+}
+
+fn decode_abi_value<'py>(
+    py: Python<'py>,
+    ty: &AbiType,
+    data: &[u8],
+) -> PyResult<Bound<'py, PyAny>> {
+    let word = |pos| abi_word(data, pos).map_err(PyValueError::new_err);
+    let length = |pos| abi_usize(data, pos).map_err(PyValueError::new_err);
+    let dirty = |what: &str| PyValueError::new_err(format!("invalid {what} padding"));
+    match ty {
+        AbiType::Address => {
+            let word = word(0)?;
+            if word[..12].iter().any(|&b| b != 0) {
+                return Err(dirty("address"));
+            }
+            Ok(checksum(&Address::from_slice(&word[12..]))
+                .into_pyobject(py)?
+                .into_any())
+        }
+        AbiType::Bool => match U256::from_be_bytes(*word(0)?) {
+            v if v.is_zero() => Ok(false.into_pyobject(py)?.to_owned().into_any()),
+            v if v == U256::from(1u64) => Ok(true.into_pyobject(py)?.to_owned().into_any()),
+            _ => Err(dirty("bool")),
+        },
+        AbiType::Uint(bits) => {
+            let value = U256::from_be_bytes(*word(0)?);
+            if value.bit_len() > *bits {
+                return Err(dirty(&ty.canonical()));
+            }
+            u256_to_py(py, value)
+        }
+        AbiType::Int(bits) => {
+            let value = I256::from_raw(U256::from_be_bytes(*word(0)?));
+            // Sign-extended values fit when every bit above the width matches the sign bit
+            if *bits < 256
+                && value.asr(bits - 1) != I256::ZERO
+                && value.asr(bits - 1) != I256::MINUS_ONE
+            {
+                return Err(dirty(&ty.canonical()));
+            }
+            py.get_type::<PyInt>().call1((value.to_string(),))
+        }
+        AbiType::FixedBytes(len) => {
+            let word = word(0)?;
+            if word[*len..].iter().any(|&b| b != 0) {
+                return Err(dirty(&ty.canonical()));
+            }
+            Ok(format!("0x{}", hex::encode(&word[..*len]))
+                .into_pyobject(py)?
+                .into_any())
+        }
+        AbiType::Bytes | AbiType::String => {
+            let len = length(0)?;
+            let content = 32usize
+                .checked_add(len)
+                .and_then(|end| data.get(32..end))
+                .ok_or_else(|| PyValueError::new_err("calldata truncated inside dynamic bytes"))?;
+            if matches!(ty, AbiType::Bytes) {
+                return Ok(format!("0x{}", hex::encode(content))
+                    .into_pyobject(py)?
+                    .into_any());
+            }
+            let text = std::str::from_utf8(content)
+                .map_err(|e| PyValueError::new_err(format!("string is not valid UTF-8: {e}")))?;
+            Ok(text.into_pyobject(py)?.into_any())
+        }
+        AbiType::Array(element) => {
+            let len = length(0)?;
+            // Every element takes at least one head word, so bound allocation by the data size
+            if len > data.len() / 32 {
+                return Err(PyValueError::new_err(format!(
+                    "array length {len} exceeds the calldata"
+                )));
+            }
+            let types = vec![(**element).clone(); len];
+            Ok(decode_abi_sequence(py, &types, &data[32..])?.into_any())
+        }
+        AbiType::FixedArray(element, len) => {
+            // As for dynamic arrays, every element takes at least one head word
+            if *len > data.len() / 32 {
+                return Err(PyValueError::new_err(format!(
+                    "array length {len} exceeds the calldata"
+                )));
+            }
+            let types = vec![(**element).clone(); *len];
+            Ok(decode_abi_sequence(py, &types, data)?.into_any())
+        }
+        AbiType::Tuple(types) => Ok(decode_abi_sequence(py, types, data)?.into_any()),
+    }
+}
+
+/// ABI-decode transaction `input` against a signature like
+/// `"transfer(address,uint256)"`, returning the parameters as a list.
+/// Integers become Python ints, addresses and bytes hex strings, and tuples
+/// and arrays nested lists. Raises ValueError when the selector differs.
+/// The decoder is hand-rolled because alloy's dynamic ABI crate
+/// (`alloy-dyn-abi`) is not available to the offline build.
+#[pyfunction]
+fn decode_calldata(py: Python<'_>, input_hex: &str, signature: &str) -> PyResult<PyObject> {
+    let signature = signature.trim();
+    let open = signature
+        .find('(')
+        .ok_or_else(|| PyValueError::new_err(format!("invalid signature {signature:?}")))?;
+    let (name, params) = (signature[..open].trim(), &signature[open..]);
+    let AbiType::Tuple(types) = AbiType::parse(params).map_err(PyValueError::new_err)? else {
+        return Err(PyValueError::new_err(format!(
+            "invalid signature {signature:?}"
+        )));
+    };
+    let canonical = format!("{name}{}", AbiType::Tuple(types.clone()).canonical());
+    let expected = &keccak256(canonical.as_bytes())[..4];
+
+    let input = hex::decode(input_hex.trim_start_matches("0x"))
+        .map_err(|e| PyValueError::new_err(format!("invalid hex: {e}")))?;
+    let (selector, args) = input
+        .split_at_checked(4)
+        .ok_or_else(|| PyValueError::new_err("calldata is shorter than a selector"))?;
+    if selector != expected {
+        return Err(PyValueError::new_err(format!(
+            "selector mismatch: calldata has 0x{}, {canonical} is 0x{}",
+            hex::encode(selector),
+            hex::encode(expected)
+        )));
+    }
+    Ok(decode_abi_sequence(py, &types, args)?.into_any().unbind())
+}
+
+// Reconnect backoff for subscriptions; reset once a subscription is confirmed
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(250);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Live `eth_subscribe("newHeads")` stream from `subscribe_new_heads`. Iterate
+/// it with `async for`; `stop()` (or dropping the handle) closes the
+/// connection and ends the iteration once queued headers are consumed.
+#[pyclass]
+pub struct HeadSubscription {
+    headers: Arc<tokio::sync::Mutex<HeadQueue>>,
+    stop: watch::Sender<bool>,
+}
+
+struct HeadQueue {
+    received: mpsc::UnboundedReceiver<Value>,
+    // A header whose awaiting task was cancelled before it could be handed
+    // over; the next `__anext__` yields it first
+    returned: Option<Value>,
+}
+
+#[pymethods]
+impl HeadSubscription {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let bridge = LoopFuture::new(py)?;
+        let awaitable = bridge.awaitable(py);
+
+        let headers = self.headers.clone();
+        runtime_handle()?.spawn(async move {
+            // Held until the header is delivered or returned, keeping order
+            let mut queue = headers.lock_owned().await;
+            let header = match queue.returned.take() {
+                Some(header) => Some(header),
+                None => queue.received.recv().await,
+            };
+            let _ = tokio::task::spawn_blocking(move || {
+                bridge.settle(
+                    (queue, header),
+                    |py, (queue, header)| {
+                        drop(queue);
+                        match header {
+                            Some(header) => Ok(header_to_py(py, &header)?.unbind().into_any()),
+                            None => Err(PyStopAsyncIteration::new_err(())),
+                        }
+                    },
+                    |(mut queue, header)| queue.returned = header,
+                )
+            })
+            .await;
+        });
+        Ok(awaitable)
+    }
+
+    /// Close the connection and stop reconnecting.
+    fn stop(&self) {
+        let _ = self.stop.send(true);
+    }
+}
+
+/// Subscribe to new block headers over a `ws://` or `wss://` JSON-RPC
+/// endpoint. Returns a `HeadSubscription` yielding `{number, hash,
+/// parent_hash, timestamp, gas_limit, gas_used, base_fee_per_gas, miner}`
+/// dicts; dropped connections are re-established with exponential backoff. A
+/// connection silent for `idle_timeout_secs` is pinged and, if it stays
+/// silent as long again, treated as dropped.
+#[pyfunction]
+#[pyo3(signature = (ws_url, idle_timeout_secs=30.0))]
+fn subscribe_new_heads(ws_url: &str, idle_timeout_secs: f64) -> PyResult<HeadSubscription> {
+    let idle_timeout = subscription_idle_timeout(ws_url, idle_timeout_secs)?;
+    let (header_tx, header_rx) = mpsc::unbounded_channel();
+    let (stop_tx, stop_rx) = watch::channel(false);
+    runtime_handle()?.spawn(stream_subscription(
+        ws_url.to_string(),
+        json!(["newHeads"]),
+        None,
+        idle_timeout,
+        stop_rx,
+        header_tx,
+    ));
+    Ok(HeadSubscription {
+        headers: Arc::new(tokio::sync::Mutex::new(HeadQueue {
+            received: header_rx,
+            returned: None,
+        })),
+        stop: stop_tx,
+    })
+}
+
+/// Validate a subscription's arguments up front; malformed URLs would
+/// otherwise only show up as endless reconnects.
+fn subscription_idle_timeout(ws_url: &str, idle_timeout_secs: f64) -> PyResult<Duration> {
+    crate::url::parse_url(ws_url, "ws").map_err(PyValueError::new_err)?;
+    if !idle_timeout_secs.is_finite() || idle_timeout_secs <= 0.0 {
+        return Err(PyValueError::new_err(format!(
+            "idle_timeout_secs must be a positive number, got {idle_timeout_secs}"
+        )));
+    }
+    Ok(Duration::from_secs_f64(idle_timeout_secs))
+}
+
+/// Forward `eth_subscribe(params)` notifications to `out` until stopped or
+/// `out` is closed, reconnecting with backoff whenever the session fails.
+/// With `follow_up`, each notification is instead passed as the sole param of
+/// that JSON-RPC method and its non-null result is forwarded.
+async fn stream_subscription(
+    url: String,
+    params: Value,
+    follow_up: Option<&'static str>,
+    idle_timeout: Duration,
+    mut stop: watch::Receiver<bool>,
+    out: mpsc::UnboundedSender<Value>,
+) {
+    let mut delay = RECONNECT_INITIAL_DELAY;
+    loop {
+        let session = async {
+            let mut ws = WsStream::connect(&url, idle_timeout).await?;
+            let request =
+                json!({"jsonrpc": "2.0", "id": 1, "method": "eth_subscribe", "params": params});
+            ws.send_text(&request.to_string()).await?;
+            let mut next_id = 1u64;
+            loop {
+                let text = ws
+                    .next_text()
+                    .await?
+                    .ok_or_else(|| "server closed the connection".to_string())?;
+                let message: Value = serde_json::from_str(&text)
+                    .map_err(|e| format!("invalid JSON-RPC message: {e}"))?;
+                if message.get("id") == Some(&json!(1)) {
+                    if let Some(error) = message.get("error") {
+                        return Err(format!("eth_subscribe failed: {error}"));
+                    }
+                    delay = RECONNECT_INITIAL_DELAY;
+                    continue;
+                }
+                let forward = match (message.pointer("/params/result"), follow_up) {
+                    (Some(result), Some(method)) => {
+                        next_id += 1;
+                        let request = json!({"jsonrpc": "2.0", "id": next_id, "method": method, "params": [result]});
+                        ws.send_text(&request.to_string()).await?;
+                        continue;
+                    }
+                    (Some(result), None) => result,
+                    // Follow-up responses; null means the node no longer has the item
+                    (None, _) => match message.get("result") {
+                        Some(result) if !result.is_null() => result,
+                        _ => continue,
+                    },
+                };
+                if out.send(forward.clone()).is_err() {
+                    return Ok(());
+                }
+            }
+        };
+        tokio::select! {
+            outcome = session => match outcome {
+                Ok(()) => return,
+                Err(e) => tracing::warn!(url = %url, "subscription interrupted: {e}"),
+            },
+            // Also resolves when the handle is dropped
+            _ = stop.wait_for(|stopped| *stopped) => return,
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = stop.wait_for(|stopped| *stopped) => return,
+        }
+        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+    }
+}
+
+/// Handle returned by `subscribe_pending_transactions`; keep it alive, since
+/// dropping it closes the connection.
+#[pyclass]
+pub struct PendingSubscription {
+    stop: watch::Sender<bool>,
+    // Held while the callback runs, so stop() can wait for an in-flight call;
+    // reentrant so the callback itself may call stop()
+    callback_gate: Arc<parking_lot::ReentrantMutex<()>>,
+}
+
+#[pymethods]
+impl PendingSubscription {
+    /// Close the connection. No callback runs after this returns.
+    fn stop(&self, py: Python<'_>) {
+        let _ = self.stop.send(true);
+        let gate = self.callback_gate.clone();
+        py.allow_threads(move || drop(gate.lock()));
+    }
+
+    fn is_running(&self) -> bool {
+        !*self.stop.borrow()
+    }
+}
+
+/// Which pending transactions reach the Python callback.
+struct PendingFilter {
+    to: Option<std::collections::HashSet<Address>>,
+    min_value: U256,
+}
+
+impl PendingFilter {
+    fn from_py(filter: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let Some(filter) = filter else {
+            return Ok(Self {
+                to: None,
+                min_value: U256::ZERO,
+            });
+        };
+        let to = match filter.get_item("to")? {
+            Some(to) if !to.is_none() => Some(
+                to.extract::<Vec<String>>()?
+                    .iter()
+                    .map(|addr| {
+                        addr.parse::<Address>().map_err(|e| {
+                            PyValueError::new_err(format!("invalid address {addr:?}: {e}"))
+                        })
+                    })
+                    .collect::<PyResult<_>>()?,
+            ),
+            _ => None,
+        };
+        let min_value = match filter.get_item("min_value")? {
+            Some(value) if !value.is_none() => U256::from(value.extract::<u128>()?),
+            _ => U256::ZERO,
+        };
+        Ok(Self { to, min_value })
+    }
+
+    fn matches(&self, envelope: &TxEnvelope) -> bool {
+        let to_matches = match &self.to {
+            Some(to) => envelope.to().is_some_and(|addr| to.contains(&addr)),
+            None => true,
+        };
+        to_matches && envelope.value() >= self.min_value
+    }
+}
+
+/// Stream pending transactions from a `ws://` or `wss://` endpoint into
+/// `callback`. `filter` may set `to` (a list of addresses) and `min_value`
+/// (wei); they are applied before the GIL is taken, so only matches reach
+/// Python, each as a `decode_transaction` dict. Raw transactions are fetched with
+/// `eth_getRawTransactionByHash`; dropped and silent connections are retried
+/// with backoff like `subscribe_new_heads`.
+#[pyfunction]
+#[pyo3(signature = (ws_url, callback, filter=None, idle_timeout_secs=30.0))]
+fn subscribe_pending_transactions(
+    ws_url: &str,
+    callback: PyObject,
+    filter: Option<&Bound<'_, PyDict>>,
+    idle_timeout_secs: f64,
+) -> PyResult<PendingSubscription> {
+    let idle_timeout = subscription_idle_timeout(ws_url, idle_timeout_secs)?;
+    let filter = PendingFilter::from_py(filter)?;
+    let (raw_tx, mut raw_rx) = mpsc::unbounded_channel();
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let callback_gate = Arc::new(parking_lot::ReentrantMutex::new(()));
+    runtime_handle()?.spawn(stream_subscription(
+        ws_url.to_string(),
+        json!(["newPendingTransactions"]),
+        Some("eth_getRawTransactionByHash"),
+        idle_timeout,
+        stop_rx.clone(),
+        raw_tx,
+    ));
+
+    let gate = callback_gate.clone();
+    runtime_handle()?.spawn(async move {
+        let callback = Arc::new(callback);
+        while let Some(raw) = raw_rx.recv().await {
+            let Some(raw) = raw.as_str() else { continue };
+            let (bytes, envelope) = match decode_envelope_raw(raw) {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    tracing::warn!("skipping undecodable pending transaction: {e}");
+                    continue;
+                }
+            };
+            if !filter.matches(&envelope) {
+                continue;
+            }
+            let (callback, gate, stop) = (callback.clone(), gate.clone(), stop_rx.clone());
+            let delivered = tokio::task::spawn_blocking(move || {
+                let _gate = gate.lock();
+                if *stop.borrow() {
+                    return false;
+                }
+                let from = envelope.recover_signer().ok();
+                Python::with_gil(|py| {
+                    let outcome = transaction_dict_from(py, &bytes, &envelope, from, false)
+                        .and_then(|tx| callback.call1(py, (tx,)));
+                    if let Err(e) = outcome {
+                        tracing::warn!("pending transaction callback failed: {e}");
+                    }
+                });
+                true
+            })
+            .await;
+            if !matches!(delivered, Ok(true)) {
+                break;
+            }
+        }
+    });
+    Ok(PendingSubscription {
+        stop: stop_tx,
+        callback_gate,
+    })
+}
+
+/// Block header dict from an `eth_subscription` header object; absent fields
+/// are None.
+fn header_to_py<'py>(py: Python<'py>, header: &Value) -> PyResult<Bound<'py, PyDict>> {
+    let out = PyDict::new(py);
+    for (key, field) in [
+        ("number", "number"),
+        ("timestamp", "timestamp"),
+        ("gas_limit", "gasLimit"),
+        ("gas_used", "gasUsed"),
+        ("base_fee_per_gas", "baseFeePerGas"),
+    ] {
+        let value = match header.get(field).and_then(Value::as_str) {
+            Some(hex) => Some(u256_to_py(
+                py,
+                U256::from_str_radix(hex.trim_start_matches("0x"), 16)
+                    .map_err(|e| PyValueError::new_err(format!("invalid {field} {hex:?}: {e}")))?,
+            )?),
+            None => None,
+        };
+        out.set_item(key, value)?;
+    }
+    out.set_item("hash", header.get("hash").and_then(Value::as_str))?;
+    out.set_item(
+        "parent_hash",
+        header.get("parentHash").and_then(Value::as_str),
+    )?;
+    let miner = match header.get("miner").and_then(Value::as_str) {
+        Some(miner) => Some(checksum_address(miner)?),
+        None => None,
+    };
+    out.set_item("miner", miner)?;
+    Ok(out)
 }
 
 pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(decode_transaction, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_transactions, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_block_transactions, m)?)?;
+    m.add_function(wrap_pyfunction!(next_nonce, m)?)?;
+    m.add_function(wrap_pyfunction!(blob_base_fee, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_calldata, m)?)?;
+    m.add_function(wrap_pyfunction!(checksum_address, m)?)?;
+    m.add_function(wrap_pyfunction!(subscribe_new_heads, m)?)?;
+    m.add_function(wrap_pyfunction!(subscribe_pending_transactions, m)?)?;
+    m.add_class::<DecodedTx>()?;
+    m.add_class::<HeadSubscription>()?;
+    m.add_class::<PendingSubscription>()?;
     Ok(())
 }
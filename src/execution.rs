@@ -1,8 +1,24 @@
 // execution.rs
+use alloy_primitives::{U256, U512};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
+use pyo3::types::{PyBytes, PyDict, PyInt, PyList, PyString};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::metrics::METRICS;
+use crate::{runtime_handle, LoopFuture};
+
+/// Tokens in at least this many pools have their pools relaxed across rayon's
+/// global pool (sized by `RAYON_NUM_THREADS`); others stay sequential.
+/// Relaxing costs about 50 ns a pool, and handing a node to rayon 5-13 µs on
+/// a single thread, so at 2048 pools (~100 µs of work) the split costs at most
+/// ~13% without spare cores and gains from two on; at 256 it costs ~50%.
+/// `benchmark_hub_expansion` in performance/benchmark.py times hub tokens.
+const PARALLEL_RELAX_MIN_POOLS: usize = 2048;
 
 #[derive(Clone, Debug)]
 struct Token {
@@ -15,222 +31,2878 @@ struct Pool {
     address: String,
     token0: Token,
     token1: Token,
-    fee: u32,
-    liquidity: u128,
+    fee: u32,        // parts per million
+    rebate_bps: u32, // portion of the fee returned to the router
+    // Directional overrides of `fee`, for pools that charge each way differently
+    fee_token0_to_token1: Option<u32>,
+    fee_token1_to_token0: Option<u32>,
+    liquidity: U256,
+    reserve0: U256,
+    reserve1: U256,
+    last_updated_ms: u64, // unix epoch millis of the latest reserve update
+    kind: PoolKind,
+}
+
+/// Pricing model of a pool, from the `kind` key of `update_pools`.
+#[derive(Clone, Debug)]
+enum PoolKind {
+    /// Constant product over `reserve0`/`reserve1`.
+    V2,
+    /// Uniswap V3 concentrated liquidity. `liquidity` is the active liquidity
+    /// and the reserves are the virtual reserves it implies at `sqrt_price_x96`.
+    V3 {
+        sqrt_price_x96: U256,
+        tick: i32,
+        tick_spacing: i32,
+    },
+    /// Curve stableswap with amplification `A`; the two balances are
+    /// `reserve0`/`reserve1`.
+    Stable { amplification: u128 },
+}
+
+impl Pool {
+    /// Fee in parts per million for a swap in the given direction, net of any
+    /// rebate, capped at 100%.
+    fn effective_fee(&self, zero_for_one: bool) -> u32 {
+        let fee = if zero_for_one {
+            self.fee_token0_to_token1
+        } else {
+            self.fee_token1_to_token0
+        };
+        fee.unwrap_or(self.fee)
+            .saturating_sub(self.rebate_bps * 100)
+            .min(1_000_000)
+    }
+
+    /// Marginal rate (output per unit of input, fees ignored) of a swap in the
+    /// given direction at the pool's current state, or `None` for an empty
+    /// pool. Each kind uses its own curve: the reserve ratio for V2, the price
+    /// implied by `sqrt_price_x96` for V3 and the slope of the invariant for
+    /// stableswap.
+    fn marginal_price(&self, zero_for_one: bool) -> Option<f64> {
+        let (reserve_in, reserve_out) = if zero_for_one {
+            (self.reserve0, self.reserve1)
+        } else {
+            (self.reserve1, self.reserve0)
+        };
+        if reserve_in.is_zero() || reserve_out.is_zero() {
+            return None;
+        }
+        match self.kind {
+            PoolKind::V2 => Some(f64::from(reserve_out) / f64::from(reserve_in)),
+            PoolKind::V3 { sqrt_price_x96, .. } => {
+                // token1 per token0 is (sqrt_price_x96 / 2^96)^2
+                let sqrt_price = f64::from(sqrt_price_x96) / 2f64.powi(96);
+                let price = sqrt_price * sqrt_price;
+                (price > 0.0 && price.is_finite()).then(|| {
+                    if zero_for_one {
+                        price
+                    } else {
+                        1.0 / price
+                    }
+                })
+            }
+            PoolKind::Stable { amplification } => {
+                // -dy/dx of ann·(x + y) + D = ann·D + D³ / (4xy), with ann = 2A as in the solver
+                let d = stableswap_invariant(amplification, reserve_in, reserve_out).ok()?;
+                let d = f64::from(d);
+                let ann = 2.0 * amplification as f64;
+                let (dx, dy) = (d / f64::from(reserve_in), d / f64::from(reserve_out));
+                Some((ann + dx * dx * dy / 4.0) / (ann + dx * dy * dy / 4.0))
+            }
+        }
+    }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct Route {
     path: Vec<String>,
-    output_amount: u128,
+    pools: Vec<String>,
+    #[serde(with = "decimal")]
+    amount_in: U256,
+    #[serde(with = "decimal")]
+    output_amount: U256,
+    confidence: f64, // 0-1, lower when the trade consumes more pool depth
+    // Shortfall against the fee-adjusted pre-trade spot rate; 10_000 on a dry pool
+    price_impact_bps: f64,
+    // Set when the request prices gas
+    gas_adjusted: Option<GasAdjusted>,
+    // One per pool; each hop's input is the previous hop's output
+    hops: Vec<Hop>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Hop {
+    #[serde(with = "decimal")]
+    amount_out: U256,
+    // Parts per million charged in the direction taken, net of rebates
+    fee: u32,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct GasAdjusted {
+    // Gas cost in input-token units, withheld from the input
+    #[serde(with = "decimal")]
+    gas_cost_input: U256,
+    #[serde(with = "decimal")]
+    net_output: U256,
+}
+
+impl Route {
+    /// Append `next`, whose first token must be this route's last token.
+    fn join(mut self, next: Route) -> Route {
+        self.path.extend(next.path.into_iter().skip(1));
+        self.pools.extend(next.pools);
+        self.hops.extend(next.hops);
+        self.output_amount = next.output_amount;
+        self.confidence *= next.confidence;
+        // Each leg keeps (1 - impact) of the spot output, so impacts compound
+        let kept =
+            (1.0 - self.price_impact_bps / 10_000.0) * (1.0 - next.price_impact_bps / 10_000.0);
+        self.price_impact_bps = (1.0 - kept) * 10_000.0;
+        self
+    }
+}
+
+/// What a cached route is keyed on: the request fields that, together with the
+/// pool generation, fully determine the search result.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct RouteKey {
+    token_in: String,
+    token_out: String,
+    #[serde(with = "decimal")]
+    amount_in: U256,
+    via: Vec<String>,
+    max_hops: Option<usize>,
+    // Absent from caches exported before the threshold existed
+    #[serde(default, with = "decimal")]
+    min_liquidity: U256,
+}
+
+/// Routes computed against the pools of one `update_pools` generation,
+/// evicting least recently used entries beyond `capacity`.
+#[derive(Default)]
+struct RouteCache {
+    generation: u64,
+    // None means unbounded; Some(0) disables caching
+    capacity: Option<usize>,
+    routes: HashMap<RouteKey, CachedRoute>,
+    // Recency is tracked with atomics so hits only need the read lock
+    clock: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct CachedRoute {
+    route: Route,
+    last_used: AtomicU64,
+}
+
+impl RouteCache {
+    fn enabled(&self) -> bool {
+        self.capacity != Some(0)
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Look up `key`, counting the hit or miss and refreshing its recency.
+    fn get(&self, key: &RouteKey) -> Option<&Route> {
+        if !self.enabled() {
+            return None;
+        }
+        match self.routes.get(key) {
+            Some(entry) => {
+                entry.last_used.store(self.tick(), Ordering::Relaxed);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(&entry.route)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, key: RouteKey, route: Route) {
+        if !self.enabled() {
+            return;
+        }
+        if let Some(capacity) = self.capacity {
+            if self.routes.len() >= capacity && !self.routes.contains_key(&key) {
+                // Evict an eighth at a time so bursts of misses don't rescan per insert
+                self.evict_oldest((capacity / 8).max(1));
+            }
+        }
+        let last_used = AtomicU64::new(self.tick());
+        self.routes.insert(key, CachedRoute { route, last_used });
+    }
+
+    fn evict_oldest(&mut self, count: usize) {
+        let mut ticks: Vec<u64> = self
+            .routes
+            .values()
+            .map(|entry| entry.last_used.load(Ordering::Relaxed))
+            .collect();
+        if count >= ticks.len() {
+            self.routes.clear();
+            return;
+        }
+        // Ticks are unique, so exactly `count` entries fall at or below the cutoff
+        let (_, &mut cutoff, _) = ticks.select_nth_unstable(count - 1);
+        self.routes
+            .retain(|_, entry| entry.last_used.load(Ordering::Relaxed) > cutoff);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RouteCacheExport {
+    generation: u64,
+    routes: Vec<(RouteKey, Route)>,
+}
+
+/// Which arrival at `token_out` a hop-by-hop search keeps.
+#[derive(Clone, Copy)]
+enum ArrivalPick {
+    /// Highest output over all hop counts.
+    MaxOutput,
+    /// The fewest-hop arrival.
+    First,
+    /// Highest output net of gas, withheld from the input as in `price_gas`.
+    NetOfGas(GasModel),
+}
+
+/// Gas pricing shared by every routing entry point: a fixed cost per route
+/// plus a cost per pool hop, in one gas unit (e.g. wei), and the price of one
+/// raw input-token unit in that unit. Gas is paid out of the input.
+#[derive(Clone, Copy)]
+struct GasModel {
+    fixed: f64,
+    per_hop: f64,
+    input_price: f64,
+}
+
+impl GasModel {
+    /// `gas_cost_wei` and `gas_per_hop`, priced by `token_price_in_gas` (or
+    /// its synonym `eth_per_input_token`); `None` when no gas is given.
+    fn from_params(params: &Bound<'_, PyDict>) -> PyResult<Option<Self>> {
+        let fixed: Option<f64> = optional_item(params, "gas_cost_wei")?;
+        let per_hop: Option<f64> = optional_item(params, "gas_per_hop")?;
+        let input_price = match (
+            optional_item::<f64>(params, "token_price_in_gas")?,
+            optional_item::<f64>(params, "eth_per_input_token")?,
+        ) {
+            (Some(_), Some(_)) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "give token_price_in_gas or eth_per_input_token, not both",
+                ))
+            }
+            (price, synonym) => price.or(synonym),
+        };
+        let Some(input_price) = input_price else {
+            if fixed.is_some() || per_hop.is_some() {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "gas_cost_wei and gas_per_hop need token_price_in_gas",
+                ));
+            }
+            return Ok(None);
+        };
+        if !(input_price.is_finite() && input_price > 0.0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "token_price_in_gas must be positive",
+            ));
+        }
+        let (fixed, per_hop) = (fixed.unwrap_or(0.0), per_hop.unwrap_or(0.0));
+        if !(fixed.is_finite() && fixed >= 0.0 && per_hop.is_finite() && per_hop >= 0.0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "gas_cost_wei and gas_per_hop must be non-negative",
+            ));
+        }
+        Ok(Some(Self {
+            fixed,
+            per_hop,
+            input_price,
+        }))
+    }
+
+    /// Gas for a route through `hops` pools, in raw input-token units rounded up.
+    fn cost_input(&self, hops: usize) -> U256 {
+        amount_from_f64(((self.fixed + self.per_hop * hops as f64) / self.input_price).ceil())
+    }
+
+    /// Output of `path` once its gas is withheld from `amount_in`; zero when
+    /// the gas costs the whole input.
+    fn net_output(
+        &self,
+        pools_snapshot: &[Pool],
+        (path, pools): (&[String], &[String]),
+        amount_in: U256,
+    ) -> U256 {
+        match amount_in.checked_sub(self.cost_input(pools.len())) {
+            Some(net_in) => ExecutionEngine::quote_path(pools_snapshot, (path, pools), net_in),
+            None => U256::ZERO,
+        }
+    }
+}
+
+/// Single-leg search: (pools, token_in, token_out, amount_in, token_caps, max_hops).
+type LegSearch =
+    fn(&[Pool], &str, &str, U256, &HashMap<String, U256>, Option<usize>) -> Option<Route>;
+
+/// Parsed `optimize_route` params shared by every routing entry point.
+#[derive(Clone)]
+struct RouteRequest {
+    token_in: String,
+    token_out: String,
+    amount_in: U256,
+    // Counterfactual reserves for this call only: address -> (reserve0, reserve1)
+    reserves_override: Option<HashMap<String, (U256, U256)>>,
+    // Hub tokens the route must visit, in order
+    via: Vec<String>,
+    // Skip pools whose reserves are older than this
+    max_pool_age_ms: Option<u64>,
+    // Gas the route pays, from `gas_cost_wei`, `gas_per_hop` and the price
+    // `token_price_in_gas`; with a per-hop cost the search maximizes net output
+    gas: Option<GasModel>,
+    // Longest route, in pools, the search may return
+    max_hops: Option<usize>,
+    // Number of alternative routes to return; above 1 the result is a list
+    k: usize,
+    // Most of a token (address -> raw amount) the route may hold mid-path
+    token_caps: HashMap<String, U256>,
+    // Slippage floor: routes still come back, flagged with `meets_minimum`
+    min_output_amount: Option<U256>,
+    // Pool addresses, and token addresses, the route must not go through
+    exclude_pools: std::collections::HashSet<String>,
+    exclude_tokens: std::collections::HashSet<String>,
+    // Pools with either reserve below this are skipped; 0 skips only drained ones
+    min_liquidity: U256,
+}
+
+impl RouteRequest {
+    fn from_params(params: &Bound<'_, PyDict>) -> PyResult<Self> {
+        Self {
+            token_in: normalize_token(
+                &params
+                    .get_item("token_in")?
+                    .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing 'token_in'"))?
+                    .extract::<String>()?,
+            )?,
+            token_out: normalize_token(
+                &params
+                    .get_item("token_out")?
+                    .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing 'token_out'"))?
+                    .extract::<String>()?,
+            )?,
+            amount_in: params
+                .get_item("amount_in")?
+                .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing 'amount_in'"))?
+                .extract::<Amount>()?
+                .0,
+            reserves_override: optional_item::<HashMap<String, (Amount, Amount)>>(
+                params,
+                "reserves_override",
+            )?
+            .map(|overrides| {
+                overrides
+                    .into_iter()
+                    .map(|(pool, (r0, r1))| (pool, (r0.0, r1.0)))
+                    .collect()
+            }),
+            via: normalize_tokens(match params.get_item("via")? {
+                Some(v) if v.is_instance_of::<pyo3::types::PyString>() => vec![v.extract()?],
+                Some(v) if !v.is_none() => v.extract()?,
+                _ => Vec::new(),
+            })?,
+            max_pool_age_ms: optional_item(params, "max_pool_age_ms")?,
+            gas: GasModel::from_params(params)?,
+            max_hops: optional_item(params, "max_hops")?,
+            k: optional_item(params, "k")?.unwrap_or(1),
+            token_caps: optional_item::<HashMap<String, Amount>>(params, "token_caps")?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(token, cap)| Ok((normalize_token(&token)?, cap.0)))
+                .collect::<PyResult<_>>()?,
+            min_output_amount: optional_item::<Amount>(params, "min_output_amount")?
+                .map(|amount| amount.0),
+            exclude_pools: optional_item::<Vec<String>>(params, "exclude_pools")?
+                .unwrap_or_default()
+                .into_iter()
+                .collect(),
+            exclude_tokens: normalize_tokens(
+                optional_item::<Vec<String>>(params, "exclude_tokens")?.unwrap_or_default(),
+            )?,
+            min_liquidity: optional_item::<Amount>(params, "min_liquidity")?
+                .map_or(U256::ZERO, |amount| amount.0),
+        }
+        .validated()
+    }
+
+    fn validated(self) -> PyResult<Self> {
+        // Net-of-gas selection scores whole routes, which only a single search has
+        if self.hop_gas().is_some() && (!self.via.is_empty() || self.k != 1) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "gas_per_hop cannot be combined with via or k",
+            ));
+        }
+        Ok(self)
+    }
+
+    /// The gas model, when it charges per hop and so steers route selection.
+    fn hop_gas(&self) -> Option<GasModel> {
+        self.gas.filter(|gas| gas.per_hop > 0.0)
+    }
+
+    /// Cache key, or `None` when the result also depends on per-call inputs
+    /// (overrides, the clock, gas pricing) and must not be cached.
+    fn cache_key(&self) -> Option<RouteKey> {
+        let per_call = self.reserves_override.is_some()
+            || self.max_pool_age_ms.is_some()
+            || self.gas.is_some()
+            || !self.token_caps.is_empty()
+            || self.k > 1
+            || !self.exclude_pools.is_empty()
+            || !self.exclude_tokens.is_empty();
+        (!per_call).then(|| RouteKey {
+            token_in: self.token_in.clone(),
+            token_out: self.token_out.clone(),
+            amount_in: self.amount_in,
+            via: self.via.clone(),
+            max_hops: self.max_hops,
+            min_liquidity: self.min_liquidity,
+        })
+    }
+
+    /// Pure search over an already-prepared snapshot; safe to run without the GIL.
+    fn search(&self, pools_snapshot: &[Pool]) -> Option<Route> {
+        if let Some(gas) = self.hop_gas() {
+            // Layers carry the hop count, and so the gas each arrival pays
+            let route = ExecutionEngine::layered_route(
+                pools_snapshot,
+                (&self.token_in, &self.token_out, self.amount_in),
+                &self.token_caps,
+                self.max_hops.unwrap_or(pools_snapshot.len()),
+                ArrivalPick::NetOfGas(gas),
+            )?;
+            return Some(self.price_gas(pools_snapshot, route));
+        }
+        self.search_with(pools_snapshot, ExecutionEngine::find_route_capped)
+    }
+
+    /// `search` with a caller-chosen per-leg search.
+    fn search_with(&self, pools_snapshot: &[Pool], find: LegSearch) -> Option<Route> {
+        // Chain one sub-search per leg: token_in -> via[0] -> ... -> token_out
+        let stops: Vec<&str> = std::iter::once(self.token_in.as_str())
+            .chain(self.via.iter().map(String::as_str))
+            .chain(std::iter::once(self.token_out.as_str()))
+            .collect();
+        let mut route: Option<Route> = None;
+        for leg in stops.windows(2) {
+            let amount = route.as_ref().map_or(self.amount_in, |r| r.output_amount);
+            // A via stop is itself an intermediate holding
+            if route.is_some() && self.token_caps.get(leg[0]).is_some_and(|cap| amount > *cap) {
+                return None;
+            }
+            // Hops already spent on earlier legs count against the limit
+            let spent = route.as_ref().map_or(0, |r| r.pools.len());
+            let hops_left = match self.max_hops {
+                Some(max) => Some(max.checked_sub(spent)?),
+                None => None,
+            };
+            let next = find(
+                pools_snapshot,
+                leg[0],
+                leg[1],
+                amount,
+                &self.token_caps,
+                hops_left,
+            )?;
+            route = Some(match route {
+                None => next,
+                Some(r) => r.join(next),
+            });
+        }
+        Some(self.price_gas(pools_snapshot, route?))
+    }
+
+    /// Attach the gas-adjusted net output when the request prices gas.
+    fn price_gas(&self, pools_snapshot: &[Pool], mut route: Route) -> Route {
+        if let Some(gas) = self.gas {
+            // Gas is paid out of the input, so net output re-quotes the same path
+            // with the gas-equivalent input withheld
+            route.gas_adjusted = Some(GasAdjusted {
+                gas_cost_input: gas.cost_input(route.pools.len()),
+                net_output: gas.net_output(
+                    pools_snapshot,
+                    (&route.path, &route.pools),
+                    self.amount_in,
+                ),
+            });
+        }
+        route
+    }
+
+    /// Up to `self.k` loopless routes by descending output (Yen's algorithm):
+    /// each further candidate deviates from an accepted route at a spur token,
+    /// keeping its root prefix and avoiding the pools accepted routes take next.
+    fn search_top_k(&self, pools_snapshot: &[Pool]) -> Vec<Route> {
+        let Some(first) = self.search(pools_snapshot) else {
+            return Vec::new();
+        };
+        let mut accepted = vec![first];
+        let mut candidates: Vec<Route> = Vec::new();
+        while accepted.len() < self.k {
+            let last = accepted.last().expect("non-empty");
+            for spur_idx in 0..last.pools.len() {
+                let root_path = &last.path[..=spur_idx];
+                let root_pools = &last.pools[..spur_idx];
+                let hops_left = match self.max_hops {
+                    Some(max) => match max.checked_sub(spur_idx) {
+                        Some(left) => Some(left),
+                        None => continue,
+                    },
+                    None => None,
+                };
+                // Pools that would repeat an accepted route sharing this root
+                let banned: std::collections::HashSet<&str> = accepted
+                    .iter()
+                    .filter(|r| r.path.len() > spur_idx + 1 && r.path[..=spur_idx] == *root_path)
+                    .filter(|r| r.pools[..spur_idx] == *root_pools)
+                    .map(|r| r.pools[spur_idx].as_str())
+                    .collect();
+                let root_tokens = &root_path[..spur_idx];
+                let touches_root = |pool: &Pool| {
+                    root_tokens.iter().any(|t| {
+                        pool.token0.address == *t
+                            || pool.token1.address == *t
+                            || pool.token0.symbol == *t
+                            || pool.token1.symbol == *t
+                    })
+                };
+                let spur_pools: Vec<Pool> = pools_snapshot
+                    .iter()
+                    .filter(|pool| !banned.contains(pool.address.as_str()) && !touches_root(pool))
+                    .cloned()
+                    .collect();
+                let root = ExecutionEngine::route_along(
+                    pools_snapshot,
+                    root_path.to_vec(),
+                    root_pools.to_vec(),
+                    self.amount_in,
+                );
+                let Some(spur) = ExecutionEngine::find_route_capped(
+                    &spur_pools,
+                    &root_path[spur_idx],
+                    &self.token_out,
+                    root.output_amount,
+                    &self.token_caps,
+                    hops_left,
+                ) else {
+                    continue;
+                };
+                let joined = root.join(spur);
+                // Re-quote against the full snapshot so confidence covers every hop
+                let full = ExecutionEngine::route_along(
+                    pools_snapshot,
+                    joined.path,
+                    joined.pools,
+                    self.amount_in,
+                );
+                let seen = accepted
+                    .iter()
+                    .chain(&candidates)
+                    .any(|r| r.pools == full.pools);
+                if !seen {
+                    candidates.push(self.price_gas(pools_snapshot, full));
+                }
+            }
+            // Highest output next; ties go to the lexicographically smallest pools
+            let Some(best) = candidates
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| {
+                    a.output_amount
+                        .cmp(&b.output_amount)
+                        .then_with(|| b.pools.cmp(&a.pools))
+                })
+                .map(|(i, _)| i)
+            else {
+                break;
+            };
+            accepted.push(candidates.swap_remove(best));
+        }
+        accepted
+    }
+
+    /// Split `amount_in` across up to `max_splits` pool-disjoint routes, taken
+    /// in order from the top-k search. The input is handed out in
+    /// `SPLIT_CHUNKS` equal chunks, each to the route whose output grows the
+    /// most from it; disjoint routes never move each other's pools, so every
+    /// route is quoted on its own. Returns each used route with its input.
+    fn search_split(&self, pools_snapshot: &[Pool], max_splits: usize) -> Vec<(Route, U256)> {
+        const SPLIT_CHUNKS: u64 = 100;
+        let candidates = RouteRequest {
+            k: max_splits * 3,
+            ..self.clone()
+        }
+        .search_top_k(pools_snapshot);
+        let mut routes: Vec<Route> = Vec::new();
+        for candidate in candidates {
+            let disjoint = routes
+                .iter()
+                .all(|r| r.pools.iter().all(|p| !candidate.pools.contains(p)));
+            if disjoint && routes.len() < max_splits {
+                routes.push(candidate);
+            }
+        }
+        if routes.is_empty() {
+            return Vec::new();
+        }
+
+        let chunks = self.amount_in.saturating_to::<u64>().clamp(1, SPLIT_CHUNKS);
+        let chunk = self.amount_in / U256::from(chunks);
+        let mut allocated = vec![U256::ZERO; routes.len()];
+        let mut outputs = vec![U256::ZERO; routes.len()];
+        for c in 0..chunks {
+            // The last chunk also carries the division remainder
+            let size = if c + 1 == chunks {
+                self.amount_in - chunk * U256::from(chunks - 1)
+            } else {
+                chunk
+            };
+            let (best, output) = routes
+                .iter()
+                .enumerate()
+                .map(|(i, route)| {
+                    let output = ExecutionEngine::quote_path(
+                        pools_snapshot,
+                        (&route.path, &route.pools),
+                        allocated[i] + size,
+                    );
+                    (i, output)
+                })
+                // Largest marginal output; ties go to the earlier (better) route
+                .max_by(|(i, a), (j, b)| {
+                    a.saturating_sub(outputs[*i])
+                        .cmp(&b.saturating_sub(outputs[*j]))
+                        .then(j.cmp(i))
+                })
+                .expect("at least one route");
+            allocated[best] += size;
+            outputs[best] = output;
+        }
+        routes
+            .into_iter()
+            .zip(allocated)
+            .filter(|(_, amount)| !amount.is_zero())
+            .map(|(route, amount)| {
+                let route =
+                    ExecutionEngine::route_along(pools_snapshot, route.path, route.pools, amount);
+                (route, amount)
+            })
+            .collect()
+    }
 }
 
 #[pyclass]
 pub struct ExecutionEngine {
     #[pyo3(get)]
     engine_id: String,
+    // Routes slower than this are logged with tracing::warn!
+    #[pyo3(get, set)]
+    slow_route_threshold_ms: Option<u64>,
     pools: Arc<RwLock<HashMap<String, Pool>>>, // address -> Pool
+    hub_hops: Arc<RwLock<HashMap<String, HashMap<String, usize>>>>, // hub -> token -> hops
+    route_cache: Arc<RwLock<RouteCache>>,
 }
 
 #[pymethods]
 impl ExecutionEngine {
+    /// `route_cache_capacity` bounds the route cache, evicting the least
+    /// recently used routes; `None` leaves it unbounded and `0` disables it.
     #[new]
-    fn new() -> Self {
+    #[pyo3(signature = (slow_route_threshold_ms=None, route_cache_capacity=None))]
+    fn new(slow_route_threshold_ms: Option<u64>, route_cache_capacity: Option<usize>) -> Self {
+        let route_cache = RouteCache {
+            capacity: route_cache_capacity,
+            ..RouteCache::default()
+        };
         Self {
             engine_id: uuid::Uuid::new_v4().to_string(),
+            slow_route_threshold_ms,
             pools: Arc::new(RwLock::new(HashMap::new())),
+            hub_hops: Arc::new(RwLock::new(HashMap::new())),
+            route_cache: Arc::new(RwLock::new(route_cache)),
         }
     }
 
+    /// `{hits, misses, size, capacity}` for the route cache. Hits and misses
+    /// count cacheable requests since the engine was created; requests with
+    /// per-call inputs (overrides, gas pricing, ...) are never looked up.
+    fn cache_stats(&self, py: Python) -> PyResult<PyObject> {
+        let cache = self.route_cache_read()?;
+        let stats = PyDict::new(py);
+        stats.set_item("hits", cache.hits.load(Ordering::Relaxed))?;
+        stats.set_item("misses", cache.misses.load(Ordering::Relaxed))?;
+        stats.set_item("size", cache.routes.len())?;
+        stats.set_item("capacity", cache.capacity)?;
+        Ok(stats.unbind().into_any())
+    }
+
+    /// Drop every cached route without starting a new pool generation. The
+    /// hit/miss counters keep running.
+    fn clear_cache(&self) -> PyResult<()> {
+        self.route_cache_write()?.routes.clear();
+        Ok(())
+    }
+
+    /// Incremented whenever the pool set changes; cached routes belong to one generation.
+    #[getter]
+    fn pool_generation(&self) -> PyResult<u64> {
+        Ok(self.route_cache_read()?.generation)
+    }
+
+    /// Best route for `params`, or `None` when the pair is unreachable. With
+    /// `min_output_amount` the route is still returned, carrying
+    /// `meets_minimum: False` when its `output_amount` falls below the floor.
+    /// Raises `RuntimeError` when no pools are loaded, as every routing method
+    /// does, so a failed pool refresh is not mistaken for an unreachable pair.
+    /// Pools with a reserve below `min_liquidity` (raw units, default 0) or an
+    /// empty reserve are skipped.
     #[pyo3(text_signature = "($self, params)")]
     fn optimize_route(&self, py: Python, params: &Bound<'_, PyDict>) -> PyResult<PyObject> {
-        // Parse inputs
-        let token_in: String = params
-            .get_item("token_in")?
-            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing 'token_in'"))?
-            .extract()?;
-        let token_out: String = params
-            .get_item("token_out")?
-            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing 'token_out'"))?
-            .extract()?;
-        let amount_in: u128 = params
-            .get_item("amount_in")?
-            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing 'amount_in'"))?
-            .extract()?;
+        let request = RouteRequest::from_params(params)?;
+        if request.k == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "k must be positive",
+            ));
+        }
+        if request.k > 1 {
+            if !request.via.is_empty() {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "k > 1 cannot be combined with via",
+                ));
+            }
+            let routes = match self.route_snapshot(&request)? {
+                Some(pools_snapshot) => {
+                    let started = Instant::now();
+                    let routes = py.allow_threads(|| request.search_top_k(&pools_snapshot));
+                    self.observe_route_time(&request, started.elapsed());
+                    routes
+                }
+                None => Vec::new(),
+            };
+            let out = PyList::empty(py);
+            for route in &routes {
+                out.append(Self::requested_route_to_py(py, &request, route)?)?;
+            }
+            return Ok(out.unbind().into_any());
+        }
+        let key = request.cache_key();
+        let generation = {
+            let cache = self.route_cache_read()?;
+            if let Some(route) = key.as_ref().and_then(|k| cache.get(k)) {
+                METRICS.route_cache_hits.fetch_add(1, Ordering::Relaxed);
+                return Self::requested_route_to_py(py, &request, route);
+            }
+            cache.generation
+        };
+        let Some(pools_snapshot) = self.route_snapshot(&request)? else {
+            return Ok(py.None());
+        };
 
         // Compute without the GIL
-        let pools_snapshot: Vec<Pool> = {
-            let guard = self
-                .pools
-                .read()
-                .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("pools lock poisoned"))?;
-            guard.values().cloned().collect()
+        let started = Instant::now();
+        let route = py.allow_threads(|| request.search(&pools_snapshot));
+        self.observe_route_time(&request, started.elapsed());
+
+        if let (Some(key), Some(r)) = (key, &route) {
+            let mut cache = self.route_cache_write()?;
+            // Pools changed mid-search: the route belongs to a stale generation
+            if cache.generation == generation {
+                cache.insert(key, r.clone());
+            }
+        }
+
+        match route {
+            Some(r) => Self::requested_route_to_py(py, &request, &r),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Awaitable variant of `optimize_route` for asyncio callers. The route is
+    /// computed on a `GLOBAL_RUNTIME` blocking thread, so the event loop keeps
+    /// running and many calls can be fanned out with `asyncio.gather`.
+    #[pyo3(text_signature = "($self, params)")]
+    fn optimize_route_async(
+        slf: Py<Self>,
+        py: Python,
+        params: &Bound<'_, PyDict>,
+    ) -> PyResult<PyObject> {
+        let bridge = LoopFuture::new(py)?;
+        let awaitable = bridge.awaitable(py);
+        // Later changes to the caller's dict must not leak into the search
+        let params = params.copy()?.unbind();
+
+        runtime_handle()?.spawn_blocking(move || {
+            // optimize_route releases the GIL for the search itself
+            let outcome =
+                Python::with_gil(move |py| slf.borrow(py).optimize_route(py, params.bind(py)));
+            bridge.resolve(move |_| outcome);
+        });
+        Ok(awaitable)
+    }
+
+    /// Split `params["amount_in"]` across up to `max_splits` disjoint routes to
+    /// cut price impact on large orders. Returns one route dict per path used,
+    /// with its `amount_in` and `fraction` of the total (fractions sum to 1.0),
+    /// or `None` when the pair is unreachable.
+    #[pyo3(signature = (params, max_splits=3))]
+    fn optimize_split_route(
+        &self,
+        py: Python,
+        params: &Bound<'_, PyDict>,
+        max_splits: usize,
+    ) -> PyResult<PyObject> {
+        let request = RouteRequest::from_params(params)?;
+        if max_splits == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "max_splits must be positive",
+            ));
+        }
+        if !request.via.is_empty() || request.hop_gas().is_some() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "split routes cannot be combined with via or gas_per_hop",
+            ));
+        }
+        let Some(pools_snapshot) = self.route_snapshot(&request)? else {
+            return Ok(py.None());
         };
+        let started = Instant::now();
+        let splits = py.allow_threads(|| request.search_split(&pools_snapshot, max_splits));
+        self.observe_route_time(&request, started.elapsed());
+        if splits.is_empty() {
+            return Ok(py.None());
+        }
+        let out = PyList::empty(py);
+        for (route, amount) in &splits {
+            let entry = Self::route_to_py(py, route)?;
+            entry.bind(py).set_item("amount_in", amount.to_string())?;
+            entry.bind(py).set_item(
+                "fraction",
+                f64::from(*amount) / f64::from(request.amount_in),
+            )?;
+            out.append(entry)?;
+        }
+        Ok(out.unbind().into_any())
+    }
 
-        let route = py.allow_threads(|| {
-            // Dijkstra-like maximization. Use addresses, not symbols.
-            use std::cmp::Ordering;
-            use std::collections::{BinaryHeap, HashMap};
+    /// Both ends of the price/gas tradeoff for `params`: `price_optimal` is the
+    /// highest-output route regardless of length, `gas_optimal` the best route
+    /// with the fewest hops. Each carries `net_output` (output after gas when
+    /// the params price it, e.g. via `gas_per_hop`).
+    #[pyo3(text_signature = "($self, params)")]
+    fn route_tradeoff(&self, py: Python, params: &Bound<'_, PyDict>) -> PyResult<PyObject> {
+        let request = RouteRequest::from_params(params)?;
+        let Some(pools_snapshot) = self.route_snapshot(&request)? else {
+            return Ok(py.None());
+        };
+        let (price_optimal, gas_optimal) = py.allow_threads(|| {
+            // Both legs pay the request's gas; neither is selected by it
+            (
+                request.search_with(&pools_snapshot, Self::find_route_capped),
+                request.search_with(&pools_snapshot, Self::find_route_fewest_hops),
+            )
+        });
+        let (Some(price_optimal), Some(gas_optimal)) = (price_optimal, gas_optimal) else {
+            return Ok(py.None());
+        };
+        let out = PyDict::new(py);
+        for (key, route) in [
+            ("price_optimal", price_optimal),
+            ("gas_optimal", gas_optimal),
+        ] {
+            let entry = Self::requested_route_to_py(py, &request, &route)?;
+            let entry = entry.downcast_bound::<PyDict>(py)?;
+            let net = route
+                .gas_adjusted
+                .map_or(route.output_amount, |gas| gas.net_output);
+            entry.set_item("net_output", net.to_string())?;
+            out.set_item(key, entry)?;
+        }
+        Ok(out.unbind().into_any())
+    }
 
-            #[derive(Eq, PartialEq)]
-            struct Node {
-                amt: u128,
-                token: String,
-            }
-            impl Ord for Node {
-                fn cmp(&self, other: &Self) -> Ordering {
-                    self.amt.cmp(&other.amt)
-                }
+    /// Split `total_amount` into `num_slices` child orders spaced `interval_ms`
+    /// apart. `profile` is "even", "front" (larger slices first) or "back".
+    /// When both tokens are given, each slice carries its expected output
+    /// against the current pools.
+    #[pyo3(signature = (total_amount, num_slices, start_ts, interval_ms, profile="even", token_in=None, token_out=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn twap_schedule(
+        &self,
+        py: Python,
+        total_amount: Amount,
+        num_slices: usize,
+        start_ts: u64,
+        interval_ms: u64,
+        profile: &str,
+        token_in: Option<String>,
+        token_out: Option<String>,
+    ) -> PyResult<PyObject> {
+        if num_slices == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "num_slices must be positive",
+            ));
+        }
+        let token_in = token_in.as_deref().map(normalize_token).transpose()?;
+        let token_out = token_out.as_deref().map(normalize_token).transpose()?;
+        let total_amount = total_amount.0;
+        let weights: Vec<U256> = match profile {
+            "even" => vec![U256::from(1u8); num_slices],
+            "front" => (1..=num_slices).rev().map(U256::from).collect(),
+            "back" => (1..=num_slices).map(U256::from).collect(),
+            _ => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "profile must be 'even', 'front' or 'back'",
+                ))
             }
-            impl PartialOrd for Node {
-                fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                    Some(self.cmp(other))
-                }
+        };
+        let weight_sum: U256 = weights.iter().sum();
+        let mut amounts: Vec<U256> = weights
+            .iter()
+            .map(|w| total_amount / weight_sum * w + total_amount % weight_sum * w / weight_sum)
+            .collect();
+        // Integer rounding leftovers go to the final slice so the schedule sums exactly
+        let allocated: U256 = amounts.iter().sum();
+        if let Some(last) = amounts.last_mut() {
+            *last += total_amount - allocated;
+        }
+
+        let pools_snapshot = match (&token_in, &token_out) {
+            (Some(_), Some(_)) => Some(self.snapshot_pools()?),
+            _ => None,
+        };
+
+        let slices = PyList::empty(py);
+        for (i, amount) in amounts.iter().enumerate() {
+            let ts = (i as u64)
+                .checked_mul(interval_ms)
+                .and_then(|offset| start_ts.checked_add(offset))
+                .ok_or_else(|| {
+                    pyo3::exceptions::PyValueError::new_err(format!(
+                        "timestamp of slice {i} overflows u64"
+                    ))
+                })?;
+            let slice = PyDict::new(py);
+            slice.set_item("ts", ts)?;
+            slice.set_item("amount", amount.to_string())?;
+            if let (Some(pools), Some(tin), Some(tout)) = (&pools_snapshot, &token_in, &token_out) {
+                let expected = Self::find_route(pools, tin, tout, *amount)
+                    .map(|r| r.output_amount.to_string());
+                slice.set_item("expected_output", expected)?;
             }
+            slices.append(slice)?;
+        }
+        Ok(slices.unbind().into_any())
+    }
 
-            let mut dist: HashMap<String, u128> = HashMap::new();
-            let mut prev: HashMap<String, String> = HashMap::new();
-            let mut pq: BinaryHeap<Node> = BinaryHeap::new();
+    /// Deterministic encoding of a route dict for signed commitments, plus its
+    /// keccak hash. Layout: version byte `0x01`, then `path` and `pools` each as
+    /// a u32 count followed by u32-length-prefixed UTF-8 strings, then
+    /// `amount_in` and `output_amount` as 32-byte big-endian words, so any
+    /// U256 amount fits. All lengths are big-endian, and other keys
+    /// (confidence, gas fields) are not committed to.
+    #[pyo3(text_signature = "($self, route)")]
+    fn route_canonical_bytes<'py>(
+        &self,
+        py: Python<'py>,
+        route: &Bound<'py, PyDict>,
+    ) -> PyResult<(Bound<'py, PyBytes>, String)> {
+        let field = |key: &str| {
+            route
+                .get_item(key)?
+                .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err(format!("missing '{key}'")))
+        };
+        let path: Vec<String> = field("path")?.extract()?;
+        let pools: Vec<String> = field("pools")?.extract()?;
+        let amount_in = field("amount_in")?.extract::<Amount>()?.0;
+        let output_amount = field("output_amount")?.extract::<Amount>()?.0;
 
-            dist.insert(token_in.clone(), amount_in);
-            pq.push(Node {
-                amt: amount_in,
-                token: token_in.clone(),
-            });
+        let mut buf = vec![0x01u8];
+        for list in [&path, &pools] {
+            let count = u32::try_from(list.len())
+                .map_err(|_| pyo3::exceptions::PyValueError::new_err("route too long"))?;
+            buf.extend_from_slice(&count.to_be_bytes());
+            for item in list {
+                let len = u32::try_from(item.len())
+                    .map_err(|_| pyo3::exceptions::PyValueError::new_err("route entry too long"))?;
+                buf.extend_from_slice(&len.to_be_bytes());
+                buf.extend_from_slice(item.as_bytes());
+            }
+        }
+        buf.extend_from_slice(&amount_in.to_be_bytes::<32>());
+        buf.extend_from_slice(&output_amount.to_be_bytes::<32>());
+        let hash = alloy_primitives::keccak256(&buf);
+        Ok((PyBytes::new(py, &buf), format!("{hash:#x}")))
+    }
 
-            while let Some(Node { amt, token }) = pq.pop() {
-                if amt < *dist.get(&token).unwrap_or(&0) {
+    /// Evaluate `token_in -> hub -> token_out` for every candidate hub and
+    /// return the hub with the highest output alongside its route.
+    #[pyo3(text_signature = "($self, token_in, token_out, amount_in, hubs)")]
+    fn best_hub(
+        &self,
+        py: Python,
+        token_in: String,
+        token_out: String,
+        amount_in: Amount,
+        hubs: Vec<String>,
+    ) -> PyResult<PyObject> {
+        let amount_in = amount_in.0;
+        let (token_in, token_out) = (normalize_token(&token_in)?, normalize_token(&token_out)?);
+        let hubs: Vec<String> = normalize_tokens(hubs)?;
+        let pools_snapshot = self.snapshot_pools()?;
+        let best = py.allow_threads(|| {
+            let mut best: Option<(String, Route)> = None;
+            for hub in hubs {
+                let Some(first) = Self::find_route(&pools_snapshot, &token_in, &hub, amount_in)
+                else {
+                    continue;
+                };
+                let Some(second) =
+                    Self::find_route(&pools_snapshot, &hub, &token_out, first.output_amount)
+                else {
                     continue;
+                };
+                let route = first.join(second);
+                if best
+                    .as_ref()
+                    .is_none_or(|(_, b)| route.output_amount > b.output_amount)
+                {
+                    best = Some((hub, route));
                 }
+            }
+            best
+        });
+        match best {
+            Some((hub, route)) => {
+                let out = PyDict::new(py);
+                out.set_item("hub", hub)?;
+                out.set_item("route", Self::route_to_py(py, &route)?)?;
+                Ok(out.unbind().into_any())
+            }
+            None => Ok(py.None()),
+        }
+    }
 
-                for pool in &pools_snapshot {
-                    // Determine direction by matching address or symbol if address missing
-                    let mut nexts = Vec::new();
-                    if pool.token0.address == token || pool.token0.symbol == token {
-                        let out = Self::calculate_amount_out(pool, amt);
-                        nexts.push((&pool.token1, out));
-                    }
-                    if pool.token1.address == token || pool.token1.symbol == token {
-                        let out = Self::calculate_amount_out(pool, amt);
-                        nexts.push((&pool.token0, out));
-                    }
-                    for (nt, out_amt) in nexts {
-                        if out_amt > *dist.get(&nt.address).unwrap_or(&0) {
-                            dist.insert(nt.address.clone(), out_amt);
-                            prev.insert(nt.address.clone(), token.clone());
-                            pq.push(Node {
-                                amt: out_amt,
-                                token: nt.address.clone(),
-                            });
-                        }
+    /// Run a BFS from each hub token and cache hop counts to every reachable
+    /// token. `optimize_route` consults the table to prune; pool updates clear it.
+    #[pyo3(text_signature = "($self, tokens)")]
+    fn precompute_hubs(&self, py: Python, tokens: Vec<String>) -> PyResult<()> {
+        let tokens: Vec<String> = normalize_tokens(tokens)?;
+        let pools_snapshot = self.snapshot_pools()?;
+        let tables: Vec<(String, HashMap<String, usize>)> = py.allow_threads(|| {
+            tokens
+                .into_iter()
+                .map(|hub| {
+                    let hops = Self::hop_distances(&pools_snapshot, &hub);
+                    (hub, hops)
+                })
+                .collect()
+        });
+        let mut hub_hops = self
+            .hub_hops
+            .write()
+            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("hub table lock poisoned"))?;
+        hub_hops.extend(tables);
+        Ok(())
+    }
+
+    /// Whether `token_out` is reachable from `token_in` within `max_hops` pools.
+    /// A BFS over the pool graph (or a precomputed hub table), so no amounts
+    /// are quoted.
+    #[pyo3(text_signature = "($self, token_in, token_out, max_hops)")]
+    fn is_routable(
+        &self,
+        py: Python,
+        token_in: String,
+        token_out: String,
+        max_hops: usize,
+    ) -> PyResult<bool> {
+        let (token_in, token_out) = (normalize_token(&token_in)?, normalize_token(&token_out)?);
+        // An empty engine raises before the hub table can answer
+        drop(self.loaded_pools()?);
+        if let Some(hops) = self.hub_hops_read()?.get(&token_in) {
+            return Ok(hops.get(&token_out).is_some_and(|h| *h <= max_hops));
+        }
+        let pools_snapshot = self.snapshot_pools()?;
+        let hops = py.allow_threads(|| Self::hop_distances(&pools_snapshot, &token_in));
+        Ok(hops.get(&token_out).is_some_and(|h| *h <= max_hops))
+    }
+
+    /// Spot mid price of `token_a` in units of `token_b` (fees ignored) from
+    /// the pools pairing them directly, weighted by pool liquidity when several
+    /// do. `None` if no pool with reserves pairs the two tokens.
+    #[pyo3(text_signature = "($self, token_a, token_b)")]
+    fn spot_price(&self, token_a: &str, token_b: &str) -> PyResult<Option<f64>> {
+        let (token_a, token_b) = (&*normalize_token(token_a)?, &*normalize_token(token_b)?);
+        let matches =
+            |token: &Token, wanted: &str| token.address == wanted || token.symbol == wanted;
+        let pools = self.pools_read()?;
+        let quotes: Vec<(f64, f64)> = pools
+            .values()
+            .filter_map(|pool| {
+                let zero_for_one =
+                    if matches(&pool.token0, token_a) && matches(&pool.token1, token_b) {
+                        true
+                    } else if matches(&pool.token1, token_a) && matches(&pool.token0, token_b) {
+                        false
+                    } else {
+                        return None;
+                    };
+                let price = pool.marginal_price(zero_for_one)?;
+                Some((price, f64::from(pool.liquidity)))
+            })
+            .collect();
+        if quotes.is_empty() {
+            return Ok(None);
+        }
+        let total_liquidity: f64 = quotes.iter().map(|(_, weight)| weight).sum();
+        // Pools reporting no liquidity count equally
+        let price = if total_liquidity > 0.0 {
+            quotes.iter().map(|(p, weight)| p * weight).sum::<f64>() / total_liquidity
+        } else {
+            quotes.iter().map(|(p, _)| p).sum::<f64>() / quotes.len() as f64
+        };
+        Ok(Some(price))
+    }
+
+    /// Spot price of `base_token` (other token per base, fees ignored) in pool
+    /// `pool_address` after selling it `amount_in` of `base_token`. The stored
+    /// pool is left untouched.
+    #[pyo3(text_signature = "($self, pool_address, base_token, amount_in)")]
+    fn price_after_trade(
+        &self,
+        pool_address: &str,
+        base_token: &str,
+        amount_in: Amount,
+    ) -> PyResult<f64> {
+        let amount_in = amount_in.0;
+        let base_token = &*normalize_token(base_token)?;
+        let mut pool = self
+            .pools
+            .read()
+            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("pools lock poisoned"))?
+            .get(pool_address)
+            .cloned()
+            .ok_or_else(|| {
+                pyo3::exceptions::PyKeyError::new_err(format!("unknown pool '{pool_address}'"))
+            })?;
+        let zero_for_one = if pool.token0.address == base_token || pool.token0.symbol == base_token
+        {
+            true
+        } else if pool.token1.address == base_token || pool.token1.symbol == base_token {
+            false
+        } else {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "token '{base_token}' is not in pool '{pool_address}'"
+            )));
+        };
+        let no_reserve = || {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "pool '{pool_address}' has no {base_token} reserve"
+            ))
+        };
+        if let PoolKind::V3 {
+            sqrt_price_x96,
+            tick,
+            tick_spacing,
+        } = pool.kind
+        {
+            // A V3 swap moves the price; only the price is read, so the tick stays
+            let (_, next) = Self::v3_swap(
+                &pool,
+                (sqrt_price_x96, tick, tick_spacing),
+                zero_for_one,
+                amount_in,
+            )
+            .ok_or_else(no_reserve)?;
+            pool.kind = PoolKind::V3 {
+                sqrt_price_x96: next,
+                tick,
+                tick_spacing,
+            };
+            (pool.reserve0, pool.reserve1) = virtual_reserves(pool.liquidity, next);
+        } else {
+            let out = Self::calculate_amount_out(&pool, zero_for_one, amount_in);
+            // The whole input, fee included, stays in the pool
+            if zero_for_one {
+                pool.reserve0 = pool.reserve0.saturating_add(amount_in);
+                pool.reserve1 -= out;
+            } else {
+                pool.reserve1 = pool.reserve1.saturating_add(amount_in);
+                pool.reserve0 -= out;
+            }
+        }
+        pool.marginal_price(zero_for_one).ok_or_else(no_reserve)
+    }
+
+    /// Largest `amount_in` whose best route keeps price impact (shortfall of the
+    /// execution price against the fee-adjusted spot rate along the route) at
+    /// or below `max_impact_bps`. Found by doubling then binary search; returns
+    /// `{"amount_in", "impact_bps", "route"}`, or `None` if no size qualifies.
+    #[pyo3(text_signature = "($self, token_in, token_out, max_impact_bps)")]
+    fn max_size_for_slippage(
+        &self,
+        py: Python,
+        token_in: String,
+        token_out: String,
+        max_impact_bps: f64,
+    ) -> PyResult<PyObject> {
+        if max_impact_bps < 0.0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "max_impact_bps must be non-negative",
+            ));
+        }
+        let (token_in, token_out) = (normalize_token(&token_in)?, normalize_token(&token_out)?);
+        let pools_snapshot = self.snapshot_pools()?;
+        let best = py.allow_threads(|| {
+            // None: no route at this size (dust rounds to zero output)
+            let quote = |amount: U256| {
+                let route = Self::find_route(&pools_snapshot, &token_in, &token_out, amount)?;
+                let impact = route.price_impact_bps;
+                Some((route, impact))
+            };
+            // Double until the cap is breached, then bisect between the last two sizes
+            let mut best: Option<(U256, Route, f64)> = None;
+            let mut hi = U256::from(1u8);
+            loop {
+                match quote(hi) {
+                    Some((route, impact)) if impact <= max_impact_bps => {
+                        best = Some((hi, route, impact))
                     }
+                    _ if best.is_some() => break,
+                    // Dust that rounds away in integer swap math reads as impact
+                    _ => {}
                 }
+                if hi > U256::MAX / U256::from(4u8) {
+                    return best;
+                }
+                hi <<= 1;
             }
-
-            // Reconstruct path by addresses
-            if !dist.contains_key(&token_out) {
-                return None;
+            let mut best = best?;
+            let mut lo = best.0;
+            while hi - lo > U256::from(1u8) {
+                let mid = lo + ((hi - lo) >> 1);
+                match quote(mid) {
+                    Some((route, impact)) if impact <= max_impact_bps => {
+                        best = (mid, route, impact);
+                        lo = mid;
+                    }
+                    _ => hi = mid,
+                }
             }
-            let mut path = Vec::new();
-            let mut cur = token_out.clone();
-            while let Some(p) = prev.get(&cur) {
-                path.push(cur.clone());
-                cur = p.clone();
-            }
-            path.push(token_in.clone());
-            path.reverse();
-            Some(Route {
-                path,
-                output_amount: *dist.get(&token_out).unwrap_or(&0),
-            })
+            Some(best)
         });
-
-        match route {
-            Some(r) => {
+        match best {
+            Some((amount_in, route, impact_bps)) => {
                 let out = PyDict::new(py);
-                let path_list = PyList::new(py, &r.path)?;
-                out.set_item("path", path_list)?;
-                out.set_item("output_amount", r.output_amount)?;
+                out.set_item("amount_in", amount_in.to_string())?;
+                out.set_item("impact_bps", impact_bps)?;
+                out.set_item("route", Self::route_to_py(py, &route)?)?;
                 Ok(out.unbind().into_any())
             }
             None => Ok(py.None()),
         }
     }
 
+    /// Swaps that move `current` holdings toward `target_weights`, valued in
+    /// `base_token`. Overweight tokens are sold into the base token and the
+    /// proceeds (plus any excess base) buy underweight tokens, scaled down when
+    /// proceeds fall short. Returns the swaps with their routes and the
+    /// expected resulting holdings.
+    #[pyo3(text_signature = "($self, current, target_weights, base_token)")]
+    fn rebalance_quote(
+        &self,
+        py: Python,
+        current: HashMap<String, Amount>,
+        target_weights: HashMap<String, f64>,
+        base_token: String,
+    ) -> PyResult<PyObject> {
+        let invalid = |w: &f64| !w.is_finite() || *w < 0.0;
+        if target_weights.values().any(invalid) || target_weights.values().all(|w| *w == 0.0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "target weights must be non-negative with a positive sum",
+            ));
+        }
+        let current: HashMap<String, U256> = current
+            .into_iter()
+            .map(|(token, amount)| Ok((normalize_token(&token)?, amount.0)))
+            .collect::<PyResult<_>>()?;
+        let target_weights: HashMap<String, f64> = target_weights
+            .into_iter()
+            .map(|(token, weight)| Ok((normalize_token(&token)?, weight)))
+            .collect::<PyResult<_>>()?;
+        let base_token = normalize_token(&base_token)?;
+        let pools_snapshot = self.snapshot_pools()?;
+
+        let (swaps, holdings) = py
+            .allow_threads(|| -> Result<_, String> {
+                let mut tokens: Vec<&String> =
+                    current.keys().chain(target_weights.keys()).collect();
+                tokens.sort();
+                tokens.dedup();
+                let weights = fixed_point_weights(
+                    &tokens
+                        .iter()
+                        .map(|t| target_weights.get(*t).copied().unwrap_or(0.0))
+                        .collect::<Vec<_>>(),
+                );
+                let weight_sum = U512::from(weights.iter().sum::<U256>());
+
+                let value_in_base = |token: &str, amount: U256| -> U256 {
+                    if token == base_token || amount.is_zero() {
+                        amount
+                    } else {
+                        Self::find_route(&pools_snapshot, token, &base_token, amount)
+                            .map_or(U256::ZERO, |r| r.output_amount)
+                    }
+                };
+                let values: Vec<(&String, U256, U256)> = tokens
+                    .iter()
+                    .map(|t| {
+                        let amount = current.get(*t).copied().unwrap_or_default();
+                        (*t, amount, value_in_base(t, amount))
+                    })
+                    .collect();
+                let total_value = values
+                    .iter()
+                    .try_fold(U256::ZERO, |sum, (_, _, v)| sum.checked_add(*v))
+                    .ok_or("portfolio value overflows U256")?;
+                let underflow = |token: &str, amount: U256| {
+                    format!("holdings of {token} cannot cover selling {amount}")
+                };
+                let overflow = |token: &str| format!("holdings of {token} overflow U256");
+
+                let mut holdings: HashMap<String, U256> = current.clone();
+                let mut swaps: Vec<(String, String, U256, Route)> = Vec::new();
+                let mut base_available = U256::ZERO;
+                let mut deficits: Vec<(&String, U256)> = Vec::new();
+
+                for ((token, amount, value), weight) in values.iter().zip(&weights) {
+                    // Below total_value, so it fits back into U256
+                    let target_value =
+                        (U512::from(total_value) * U512::from(*weight) / weight_sum).to::<U256>();
+                    let Some(excess) = value.checked_sub(target_value) else {
+                        if **token != base_token {
+                            deficits.push((token, target_value - *value));
+                        }
+                        continue;
+                    };
+                    if excess.is_zero() {
+                        continue;
+                    }
+                    if **token == base_token {
+                        base_available = base_available
+                            .checked_add(excess)
+                            .ok_or_else(|| overflow(&base_token))?;
+                        let held = holdings.entry(base_token.clone()).or_default();
+                        *held = held
+                            .checked_sub(excess)
+                            .ok_or_else(|| underflow(&base_token, excess))?;
+                        continue;
+                    }
+                    // An exact share of the amount, so a zero target sells all of it
+                    let sell = if excess == *value {
+                        *amount
+                    } else {
+                        (U512::from(*amount) * U512::from(excess) / U512::from(*value)).to::<U256>()
+                    };
+                    if let Some(route) = Self::find_route(&pools_snapshot, token, &base_token, sell)
+                    {
+                        base_available = base_available
+                            .checked_add(route.output_amount)
+                            .ok_or_else(|| overflow(&base_token))?;
+                        let held = holdings.entry((*token).clone()).or_default();
+                        *held = held
+                            .checked_sub(sell)
+                            .ok_or_else(|| underflow(token, sell))?;
+                        swaps.push(((*token).clone(), base_token.clone(), sell, route));
+                    }
+                }
+
+                let deficit_total = deficits
+                    .iter()
+                    .try_fold(U256::ZERO, |sum, (_, d)| sum.checked_add(*d))
+                    .ok_or("rebalance deficits overflow U256")?;
+                for (token, deficit) in deficits {
+                    let spend = if deficit_total > base_available {
+                        (U512::from(deficit) * U512::from(base_available)
+                            / U512::from(deficit_total))
+                        .to::<U256>()
+                    } else {
+                        deficit
+                    };
+                    if spend.is_zero() {
+                        continue;
+                    }
+                    if let Some(route) =
+                        Self::find_route(&pools_snapshot, &base_token, token, spend)
+                    {
+                        let held = holdings.entry(token.clone()).or_default();
+                        *held = held
+                            .checked_add(route.output_amount)
+                            .ok_or_else(|| overflow(token))?;
+                        swaps.push((base_token.clone(), token.clone(), spend, route));
+                    }
+                }
+                // Unspent proceeds stay in the base token
+                let spent: U256 = swaps
+                    .iter()
+                    .filter(|(tin, _, _, _)| *tin == base_token)
+                    .map(|(_, _, amount, _)| *amount)
+                    .sum();
+                let held = holdings.entry(base_token.clone()).or_default();
+                *held = held
+                    .checked_add(base_available.saturating_sub(spent))
+                    .ok_or_else(|| overflow(&base_token))?;
+                Ok((swaps, holdings))
+            })
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+
+        let out = PyDict::new(py);
+        let swap_list = PyList::empty(py);
+        for (token_in, token_out, amount_in, route) in &swaps {
+            let swap = PyDict::new(py);
+            swap.set_item("token_in", token_in)?;
+            swap.set_item("token_out", token_out)?;
+            swap.set_item("amount_in", amount_in.to_string())?;
+            swap.set_item("route", Self::route_to_py(py, route)?)?;
+            swap_list.append(swap)?;
+        }
+        out.set_item("swaps", swap_list)?;
+        let holdings: HashMap<String, String> = holdings
+            .into_iter()
+            .map(|(token, amount)| (token, amount.to_string()))
+            .collect();
+        out.set_item("holdings", holdings.into_pyobject(py)?)?;
+        Ok(out.unbind().into_any())
+    }
+
+    /// Serialize the cached routes together with their pool generation.
+    fn export_route_cache<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let cache = self.route_cache_read()?;
+        let export = RouteCacheExport {
+            generation: cache.generation,
+            routes: cache
+                .routes
+                .iter()
+                .map(|(k, entry)| (k.clone(), entry.route.clone()))
+                .collect(),
+        };
+        let bytes = serde_json::to_vec(&export)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    /// Load routes from `export_route_cache`. They are kept only when
+    /// `pool_generation`, the exported generation and this engine's current
+    /// generation all agree; otherwise the payload is discarded. Returns
+    /// whether the routes were imported.
+    #[pyo3(text_signature = "($self, data, pool_generation)")]
+    fn import_route_cache(&self, data: &[u8], pool_generation: u64) -> PyResult<bool> {
+        let export: RouteCacheExport = serde_json::from_slice(data).map_err(|e| {
+            pyo3::exceptions::PyValueError::new_err(format!("invalid route cache: {e}"))
+        })?;
+        let mut cache = self.route_cache_write()?;
+        if export.generation != pool_generation || cache.generation != pool_generation {
+            return Ok(false);
+        }
+        for (key, route) in export.routes {
+            cache.insert(key, route);
+        }
+        Ok(true)
+    }
+
     #[pyo3(text_signature = "($self, pools_data)")]
     fn update_pools(&self, _py: Python, pools_data: &Bound<'_, PyList>) -> PyResult<()> {
-        let mut map = self
-            .pools
-            .write()
-            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("pools lock poisoned"))?;
+        // Parse everything first so a malformed pool leaves the old set in place
+        let pools = pools_data
+            .iter()
+            .map(|pool_any| Self::parse_pool(pool_any.downcast()?))
+            .collect::<PyResult<Vec<_>>>()?;
+        let mut map = self.pools_write()?;
+        self.invalidate_derived()?;
         map.clear();
-        for pool_any in pools_data.iter() {
-            let pool_dict: &Bound<PyDict> = pool_any.downcast()?;
-            let token0_item = pool_dict
-                .get_item("token0")?
-                .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing token0"))?;
-            let token0: &Bound<PyDict> = token0_item.downcast()?;
-            let token1_item = pool_dict
-                .get_item("token1")?
-                .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing token1"))?;
-            let token1: &Bound<PyDict> = token1_item.downcast()?;
-            let pool = Pool {
-                address: pool_dict
-                    .get_item("address")?
-                    .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing address"))?
-                    .extract()?,
-                token0: Token {
-                    address: token0
-                        .get_item("address")?
-                        .ok_or_else(|| {
-                            pyo3::exceptions::PyKeyError::new_err("missing token0.address")
-                        })?
-                        .extract()?,
-                    symbol: token0
-                        .get_item("symbol")?
-                        .ok_or_else(|| {
-                            pyo3::exceptions::PyKeyError::new_err("missing token0.symbol")
-                        })?
-                        .extract()?,
-                },
-                token1: Token {
-                    address: token1
+        for pool in pools {
+            map.insert(pool.address.clone(), pool);
+            METRICS.pools_loaded.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Insert or replace a single pool, keyed by its address, leaving the other
+    /// pools untouched. Like `update_pools` this starts a new pool generation.
+    #[pyo3(text_signature = "($self, pool_data)")]
+    fn upsert_pool(&self, pool_data: &Bound<'_, PyDict>) -> PyResult<()> {
+        let pool = Self::parse_pool(pool_data)?;
+        let mut map = self.pools_write()?;
+        self.invalidate_derived()?;
+        map.insert(pool.address.clone(), pool);
+        METRICS.pools_loaded.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Drop the pool at `address`. Returns whether a pool was removed.
+    #[pyo3(text_signature = "($self, address)")]
+    fn remove_pool(&self, address: &str) -> PyResult<bool> {
+        let mut map = self.pools_write()?;
+        if map.remove(address).is_none() {
+            return Ok(false);
+        }
+        self.invalidate_derived()?;
+        Ok(true)
+    }
+
+    fn pool_count(&self) -> PyResult<usize> {
+        Ok(self.pools_read()?.len())
+    }
+
+    /// The loaded pool at `address` in the shape `update_pools` accepts, or `None`.
+    #[pyo3(text_signature = "($self, address)")]
+    fn get_pool(&self, py: Python, address: &str) -> PyResult<Option<PyObject>> {
+        match self.pools_read()?.get(address) {
+            Some(pool) => Ok(Some(Self::pool_to_py(py, pool)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+// Pure Rust helper
+impl ExecutionEngine {
+    fn snapshot_pools(&self) -> PyResult<Vec<Pool>> {
+        Ok(self.loaded_pools()?.values().cloned().collect())
+    }
+
+    /// The pool map for a routing call. An empty engine raises `RuntimeError`
+    /// so a failed pool refresh is not mistaken for an unreachable pair.
+    fn loaded_pools(&self) -> PyResult<std::sync::RwLockReadGuard<'_, HashMap<String, Pool>>> {
+        let pools = self.pools_read()?;
+        if pools.is_empty() {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err("no pools loaded"));
+        }
+        Ok(pools)
+    }
+
+    fn pools_read(&self) -> PyResult<std::sync::RwLockReadGuard<'_, HashMap<String, Pool>>> {
+        self.pools
+            .read()
+            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("pools lock poisoned"))
+    }
+
+    /// Pools to search for `request` with its overrides and liquidity floor
+    /// applied, or `None` when a precomputed hub table already proves the pair
+    /// unreachable.
+    fn route_snapshot(&self, request: &RouteRequest) -> PyResult<Option<Vec<Pool>>> {
+        let mut pools_snapshot = self.snapshot_pools()?;
+        // A hub's precomputed hop table answers unreachable pairs without searching
+        if let Some(hops) = self.hub_hops_read()?.get(&request.token_in) {
+            if !hops.contains_key(&request.token_out) {
+                return Ok(None);
+            }
+        }
+        if let Some(max_age) = request.max_pool_age_ms {
+            let now = now_ms();
+            pools_snapshot.retain(|pool| now.saturating_sub(pool.last_updated_ms) <= max_age);
+        }
+        if !request.exclude_pools.is_empty() || !request.exclude_tokens.is_empty() {
+            pools_snapshot.retain(|pool| {
+                !request.exclude_pools.contains(&pool.address)
+                    && !request.exclude_tokens.contains(&pool.token0.address)
+                    && !request.exclude_tokens.contains(&pool.token1.address)
+            });
+        }
+        if let Some(overrides) = &request.reserves_override {
+            for pool in pools_snapshot.iter_mut() {
+                if let Some(&(r0, r1)) = overrides.get(&pool.address) {
+                    pool.reserve0 = r0;
+                    pool.reserve1 = r1;
+                }
+            }
+        }
+        // Drained pools quote nonsense, so they never enter the search
+        pools_snapshot.retain(|pool| {
+            let shallowest = pool.reserve0.min(pool.reserve1);
+            !shallowest.is_zero() && shallowest >= request.min_liquidity
+        });
+        Ok(Some(pools_snapshot))
+    }
+
+    /// Record a route computation in the metrics and warn when it took longer
+    /// than `slow_route_threshold_ms`.
+    fn observe_route_time(&self, request: &RouteRequest, elapsed: Duration) {
+        METRICS.record_route(elapsed);
+        if let Some(threshold_ms) = self.slow_route_threshold_ms {
+            if elapsed > Duration::from_millis(threshold_ms) {
+                tracing::warn!(
+                    engine_id = %self.engine_id,
+                    token_in = %request.token_in,
+                    token_out = %request.token_out,
+                    amount_in = %request.amount_in,
+                    elapsed_ms = elapsed.as_secs_f64() * 1000.0,
+                    threshold_ms,
+                    "slow route computation"
+                );
+            }
+        }
+    }
+
+    fn pools_write(&self) -> PyResult<std::sync::RwLockWriteGuard<'_, HashMap<String, Pool>>> {
+        self.pools
+            .write()
+            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("pools lock poisoned"))
+    }
+
+    /// Start a new pool generation: cached routes and hub tables describe the
+    /// old pool set and are dropped.
+    fn invalidate_derived(&self) -> PyResult<()> {
+        self.hub_hops
+            .write()
+            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("hub table lock poisoned"))?
+            .clear();
+        let mut cache = self.route_cache_write()?;
+        cache.generation += 1;
+        cache.routes.clear();
+        Ok(())
+    }
+
+    fn route_cache_read(&self) -> PyResult<std::sync::RwLockReadGuard<'_, RouteCache>> {
+        self.route_cache
+            .read()
+            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("route cache lock poisoned"))
+    }
+
+    fn route_cache_write(&self) -> PyResult<std::sync::RwLockWriteGuard<'_, RouteCache>> {
+        self.route_cache
+            .write()
+            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("route cache lock poisoned"))
+    }
+
+    fn hub_hops_read(
+        &self,
+    ) -> PyResult<std::sync::RwLockReadGuard<'_, HashMap<String, HashMap<String, usize>>>> {
+        self.hub_hops
+            .read()
+            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("hub table lock poisoned"))
+    }
+
+    /// Hop count from `source` to every token reachable through the pool graph.
+    /// Tokens match by address or symbol, as `relax_through` matches them, and
+    /// every token past the source is keyed by address like a route's path.
+    fn hop_distances(pools_snapshot: &[Pool], source: &str) -> HashMap<String, usize> {
+        let by_token = Self::pools_by_token(pools_snapshot);
+        let mut hops: HashMap<String, usize> = HashMap::new();
+        let mut queue = std::collections::VecDeque::new();
+        hops.insert(source.to_string(), 0);
+        queue.push_back(source.to_string());
+        while let Some(token) = queue.pop_front() {
+            let depth = hops[&token];
+            for pool in by_token.get(token.as_str()).into_iter().flatten() {
+                let pool = &pools_snapshot[*pool];
+                for (side, next) in [(&pool.token0, &pool.token1), (&pool.token1, &pool.token0)] {
+                    let matched = side.address == token || side.symbol == token;
+                    if matched && !hops.contains_key(&next.address) {
+                        hops.insert(next.address.clone(), depth + 1);
+                        queue.push_back(next.address.clone());
+                    }
+                }
+            }
+        }
+        hops
+    }
+
+    /// Route dict; `hops` breaks the route down per pool as `{pool_address,
+    /// token_in, token_out, amount_in, amount_out, fee}`.
+    fn route_to_py(py: Python, route: &Route) -> PyResult<PyObject> {
+        let out = PyDict::new(py);
+        let path_list = PyList::new(py, &route.path)?;
+        out.set_item("path", path_list)?;
+        out.set_item("pools", PyList::new(py, &route.pools)?)?;
+        out.set_item("amount_in", route.amount_in.to_string())?;
+        out.set_item("output_amount", route.output_amount.to_string())?;
+        out.set_item("confidence", route.confidence)?;
+        out.set_item("price_impact_bps", route.price_impact_bps)?;
+        let effective_price = if route.amount_in.is_zero() {
+            0.0
+        } else {
+            f64::from(route.output_amount) / f64::from(route.amount_in)
+        };
+        out.set_item("effective_price", effective_price)?;
+        if let Some(gas) = route.gas_adjusted {
+            out.set_item("gas_cost_input", gas.gas_cost_input.to_string())?;
+            out.set_item("net_output", gas.net_output.to_string())?;
+        }
+        let hops = PyList::empty(py);
+        let mut amount_in = route.amount_in;
+        for ((tokens, pool_address), hop) in
+            route.path.windows(2).zip(&route.pools).zip(&route.hops)
+        {
+            let entry = PyDict::new(py);
+            entry.set_item("pool_address", pool_address)?;
+            entry.set_item("token_in", &tokens[0])?;
+            entry.set_item("token_out", &tokens[1])?;
+            entry.set_item("amount_in", amount_in.to_string())?;
+            entry.set_item("amount_out", hop.amount_out.to_string())?;
+            entry.set_item("fee", hop.fee)?;
+            hops.append(entry)?;
+            amount_in = hop.amount_out;
+        }
+        out.set_item("hops", hops)?;
+        Ok(out.unbind().into_any())
+    }
+
+    /// `route_to_py` plus the fields that depend on the request, not the route.
+    fn requested_route_to_py(
+        py: Python,
+        request: &RouteRequest,
+        route: &Route,
+    ) -> PyResult<PyObject> {
+        let out = Self::route_to_py(py, route)?;
+        if let Some(min_output_amount) = request.min_output_amount {
+            out.bind(py)
+                .set_item("meets_minimum", route.output_amount >= min_output_amount)?;
+        }
+        Ok(out)
+    }
+
+    fn pool_to_py(py: Python, pool: &Pool) -> PyResult<PyObject> {
+        let out = PyDict::new(py);
+        out.set_item("address", &pool.address)?;
+        for (key, token) in [("token0", &pool.token0), ("token1", &pool.token1)] {
+            let token_dict = PyDict::new(py);
+            token_dict.set_item("address", &token.address)?;
+            token_dict.set_item("symbol", &token.symbol)?;
+            out.set_item(key, token_dict)?;
+        }
+        out.set_item("fee", pool.fee)?;
+        out.set_item("rebate_bps", pool.rebate_bps)?;
+        if let Some(fee) = pool.fee_token0_to_token1 {
+            out.set_item("fee_token0_to_token1", fee)?;
+        }
+        if let Some(fee) = pool.fee_token1_to_token0 {
+            out.set_item("fee_token1_to_token0", fee)?;
+        }
+        out.set_item("liquidity", pool.liquidity.to_string())?;
+        out.set_item("reserve0", pool.reserve0.to_string())?;
+        out.set_item("reserve1", pool.reserve1.to_string())?;
+        out.set_item("last_updated", pool.last_updated_ms)?;
+        match &pool.kind {
+            PoolKind::V2 => out.set_item("kind", "v2")?,
+            PoolKind::V3 {
+                sqrt_price_x96,
+                tick,
+                tick_spacing,
+            } => {
+                out.set_item("kind", "v3")?;
+                let sqrt_price = py
+                    .import("builtins")?
+                    .getattr("int")?
+                    .call1((sqrt_price_x96.to_string(),))?;
+                out.set_item("sqrt_price_x96", sqrt_price)?;
+                out.set_item("tick", tick)?;
+                out.set_item("tick_spacing", tick_spacing)?;
+            }
+            PoolKind::Stable { amplification } => {
+                out.set_item("kind", "stable")?;
+                out.set_item("amplification", amplification)?;
+                out.set_item(
+                    "balances",
+                    [pool.reserve0.to_string(), pool.reserve1.to_string()],
+                )?;
+            }
+        }
+        Ok(out.unbind().into_any())
+    }
+
+    fn find_route(
+        pools_snapshot: &[Pool],
+        token_in: &str,
+        token_out: &str,
+        amount_in: U256,
+    ) -> Option<Route> {
+        Self::find_route_capped(
+            pools_snapshot,
+            token_in,
+            token_out,
+            amount_in,
+            &HashMap::new(),
+            None,
+        )
+    }
+
+    /// `find_route` with exposure caps: hops that would leave more than the cap
+    /// of an intermediate token are pruned. With `max_hops` the search runs
+    /// hop by hop, since a max-output search cannot bound path length.
+    fn find_route_capped(
+        pools_snapshot: &[Pool],
+        token_in: &str,
+        token_out: &str,
+        amount_in: U256,
+        token_caps: &HashMap<String, U256>,
+        max_hops: Option<usize>,
+    ) -> Option<Route> {
+        if let Some(max_hops) = max_hops {
+            return Self::layered_route(
+                pools_snapshot,
+                (token_in, token_out, amount_in),
+                token_caps,
+                max_hops,
+                ArrivalPick::MaxOutput,
+            );
+        }
+        // Dijkstra-like maximization. Use addresses, not symbols.
+        use std::cmp::Ordering;
+        use std::collections::BinaryHeap;
+
+        #[derive(Eq, PartialEq)]
+        struct Node {
+            amt: U256,
+            token: String,
+        }
+        impl Ord for Node {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.amt.cmp(&other.amt)
+            }
+        }
+        impl PartialOrd for Node {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        let by_token = Self::pools_by_token(pools_snapshot);
+        let mut dist: HashMap<String, U256> = HashMap::new();
+        // token -> (previous token, pool address used for the hop)
+        let mut prev: HashMap<String, (String, String)> = HashMap::new();
+        let mut pq: BinaryHeap<Node> = BinaryHeap::new();
+
+        dist.insert(token_in.to_string(), amount_in);
+        pq.push(Node {
+            amt: amount_in,
+            token: token_in.to_string(),
+        });
+
+        while let Some(Node { amt, token }) = pq.pop() {
+            if amt < dist.get(&token).copied().unwrap_or_default() {
+                continue;
+            }
+
+            // Candidates are merged in pool order either way, so the result does
+            // not depend on how the expansion was scheduled
+            let adjacent = by_token.get(token.as_str()).map_or(&[][..], Vec::as_slice);
+            let relax = |&i: &usize| Self::relax_through(&pools_snapshot[i], &token, amt);
+            let candidates: Vec<(&Pool, &Token, U256)> =
+                if adjacent.len() >= PARALLEL_RELAX_MIN_POOLS {
+                    adjacent.par_iter().flat_map_iter(relax).collect()
+                } else {
+                    adjacent.iter().flat_map(relax).collect()
+                };
+            for (pool, nt, out_amt) in candidates {
+                // Coming back to the input is a cycle, never a better start
+                if nt.address == token_in {
+                    continue;
+                }
+                let over_cap = token_caps
+                    .get(&nt.address)
+                    .is_some_and(|cap| out_amt > *cap);
+                if over_cap && nt.address != token_out {
+                    continue;
+                }
+                let best = dist.get(&nt.address).copied();
+                if out_amt > best.unwrap_or_default() {
+                    dist.insert(nt.address.clone(), out_amt);
+                    prev.insert(nt.address.clone(), (token.clone(), pool.address.clone()));
+                    pq.push(Node {
+                        amt: out_amt,
+                        token: nt.address.clone(),
+                    });
+                } else if best == Some(out_amt) {
+                    // Equal-output parallel pools: keep the smallest pool address
+                    // so the chosen route never depends on map iteration order.
+                    // Never re-point at a token whose own chain runs through `nt`.
+                    let loops_back = Self::chain_contains(&prev, &token, &nt.address);
+                    if let Some(entry) = prev.get_mut(&nt.address) {
+                        if pool.address < entry.1 && !loops_back {
+                            *entry = (token.clone(), pool.address.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        // Reconstruct path by addresses
+        if !dist.contains_key(token_out) {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut pools = Vec::new();
+        let mut cur = token_out.to_string();
+        let mut visited = std::collections::HashSet::new();
+        while cur != token_in {
+            // A cyclic chain (e.g. a profitable loop elsewhere in the graph) has no
+            // path back to token_in; fall back to the loop-free hop-by-hop search
+            if !visited.insert(cur.clone()) {
+                tracing::warn!(
+                    token_in,
+                    token_out,
+                    token = %cur,
+                    "cycle in route predecessors; using hop-by-hop search"
+                );
+                return Self::layered_route(
+                    pools_snapshot,
+                    (token_in, token_out, amount_in),
+                    token_caps,
+                    pools_snapshot.len(),
+                    ArrivalPick::MaxOutput,
+                );
+            }
+            let Some((p, pool)) = prev.get(&cur) else {
+                break;
+            };
+            path.push(cur.clone());
+            pools.push(pool.clone());
+            cur = p.clone();
+        }
+        path.push(token_in.to_string());
+        path.reverse();
+        pools.reverse();
+        Some(Self::route_along(pools_snapshot, path, pools, amount_in))
+    }
+
+    /// Indices of the pools holding each token, keyed by address and by symbol
+    /// (the forms `relax_through` matches), in pool order.
+    fn pools_by_token(pools_snapshot: &[Pool]) -> HashMap<&str, Vec<usize>> {
+        let mut index: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, pool) in pools_snapshot.iter().enumerate() {
+            let keys = [
+                pool.token0.address.as_str(),
+                pool.token0.symbol.as_str(),
+                pool.token1.address.as_str(),
+                pool.token1.symbol.as_str(),
+            ];
+            for (k, key) in keys.iter().enumerate() {
+                // A pool is listed once under a key, however many fields match
+                if !keys[..k].contains(key) {
+                    index.entry(key).or_default().push(i);
+                }
+            }
+        }
+        index
+    }
+
+    /// Each token reachable from `token` through `pool`, with the amount out.
+    /// Direction is matched by address, or by symbol if the address is missing.
+    fn relax_through<'p>(
+        pool: &'p Pool,
+        token: &str,
+        amt: U256,
+    ) -> impl Iterator<Item = (&'p Pool, &'p Token, U256)> {
+        let forward = (pool.token0.address == token || pool.token0.symbol == token).then(|| {
+            (
+                pool,
+                &pool.token1,
+                Self::calculate_amount_out(pool, true, amt),
+            )
+        });
+        let backward = (pool.token1.address == token || pool.token1.symbol == token).then(|| {
+            (
+                pool,
+                &pool.token0,
+                Self::calculate_amount_out(pool, false, amt),
+            )
+        });
+        forward.into_iter().chain(backward)
+    }
+
+    /// Whether walking predecessors back from `from` reaches `target`.
+    fn chain_contains(prev: &HashMap<String, (String, String)>, from: &str, target: &str) -> bool {
+        let mut seen = std::collections::HashSet::new();
+        let mut cur = from;
+        while seen.insert(cur) {
+            if cur == target {
+                return true;
+            }
+            match prev.get(cur) {
+                Some((p, _)) => cur = p,
+                None => return false,
+            }
+        }
+        false
+    }
+
+    /// Best route using as few pools as possible: the hop count is the BFS
+    /// distance, raised only if exposure caps rule out every shorter route.
+    fn find_route_fewest_hops(
+        pools_snapshot: &[Pool],
+        token_in: &str,
+        token_out: &str,
+        amount_in: U256,
+        token_caps: &HashMap<String, U256>,
+        max_hops: Option<usize>,
+    ) -> Option<Route> {
+        Self::hop_distances(pools_snapshot, token_in).get(token_out)?;
+        let max_hops = max_hops.unwrap_or(pools_snapshot.len());
+        Self::layered_route(
+            pools_snapshot,
+            (token_in, token_out, amount_in),
+            token_caps,
+            max_hops,
+            ArrivalPick::First,
+        )
+    }
+
+    /// Hop-by-hop search: layer k holds the best (amount, path, pools) reaching
+    /// each token in exactly k hops, without revisiting a token. Returns the
+    /// arrival at `token_out` within `max_hops` chosen by `pick`.
+    fn layered_route(
+        pools_snapshot: &[Pool],
+        (token_in, token_out, amount_in): (&str, &str, U256),
+        token_caps: &HashMap<String, U256>,
+        max_hops: usize,
+        pick: ArrivalPick,
+    ) -> Option<Route> {
+        type Arrival = (U256, Vec<String>, Vec<String>);
+        let mut layer: HashMap<String, Arrival> = HashMap::new();
+        layer.insert(
+            token_in.to_string(),
+            (amount_in, vec![token_in.to_string()], Vec::new()),
+        );
+        let by_token = Self::pools_by_token(pools_snapshot);
+        let mut best: Option<(U256, Arrival)> = None;
+        for _ in 0..max_hops.min(pools_snapshot.len()) {
+            let mut next: HashMap<String, Arrival> = HashMap::new();
+            for (token, (amt, path, used)) in &layer {
+                let adjacent = by_token.get(token.as_str()).into_iter().flatten();
+                for pool in adjacent.map(|&i| &pools_snapshot[i]) {
+                    let (zero_for_one, nt) =
+                        if pool.token0.address == *token || pool.token0.symbol == *token {
+                            (true, &pool.token1.address)
+                        } else if pool.token1.address == *token || pool.token1.symbol == *token {
+                            (false, &pool.token0.address)
+                        } else {
+                            continue;
+                        };
+                    if path.contains(nt) {
+                        continue;
+                    }
+                    let out = Self::calculate_amount_out(pool, zero_for_one, *amt);
+                    let over_cap = token_caps.get(nt).is_some_and(|cap| out > *cap);
+                    if out.is_zero() || (over_cap && nt != token_out) {
+                        continue;
+                    }
+                    let better = next.get(nt).is_none_or(|(best, _, best_pools)| {
+                        out > *best || (out == *best && pool.address < *best_pools.last().unwrap())
+                    });
+                    if better {
+                        let mut path = path.clone();
+                        path.push(nt.clone());
+                        let mut used = used.clone();
+                        used.push(pool.address.clone());
+                        next.insert(nt.clone(), (out, path, used));
+                    }
+                }
+            }
+            if let Some(arrival) = next.remove(token_out) {
+                let net = match pick {
+                    ArrivalPick::NetOfGas(gas) => {
+                        gas.net_output(pools_snapshot, (&arrival.1, &arrival.2), amount_in)
+                    }
+                    _ => arrival.0,
+                };
+                if best.as_ref().is_none_or(|(best_net, _)| net > *best_net) {
+                    best = Some((net, arrival));
+                }
+                if matches!(pick, ArrivalPick::First) {
+                    break;
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            layer = next;
+        }
+        let (_, (_, path, used)) = best?;
+        Some(Self::route_along(pools_snapshot, path, used, amount_in))
+    }
+
+    /// Build a `Route` for a fixed path, re-quoting each hop to get the per-hop
+    /// amounts, the output and confidence.
+    fn route_along(
+        pools_snapshot: &[Pool],
+        path: Vec<String>,
+        pools: Vec<String>,
+        amount_in: U256,
+    ) -> Route {
+        let by_address: HashMap<&str, &Pool> = pools_snapshot
+            .iter()
+            .map(|p| (p.address.as_str(), p))
+            .collect();
+        let mut amount = amount_in;
+        // Confidence shrinks with the share of each pool's input reserve consumed
+        let mut confidence = 1.0;
+        let mut hops = Vec::with_capacity(pools.len());
+        for (hop, address) in path.windows(2).zip(&pools) {
+            let pool = by_address[address.as_str()];
+            let zero_for_one = pool.token0.address == hop[0] || pool.token0.symbol == hop[0];
+            let reserve_in = f64::from(if zero_for_one {
+                pool.reserve0
+            } else {
+                pool.reserve1
+            });
+            confidence *= if reserve_in > 0.0 {
+                reserve_in / (reserve_in + f64::from(amount))
+            } else {
+                0.0
+            };
+            amount = Self::calculate_amount_out(pool, zero_for_one, amount);
+            hops.push(Hop {
+                amount_out: amount,
+                fee: pool.effective_fee(zero_for_one),
+            });
+        }
+        let price_impact_bps = Self::path_impact_bps(&by_address, &path, &pools, amount_in);
+        Route {
+            path,
+            pools,
+            amount_in,
+            output_amount: amount,
+            confidence,
+            price_impact_bps,
+            gas_adjusted: None,
+            hops,
+        }
+    }
+
+    /// Price impact of `amount_in` along a path in basis points: how far the
+    /// output falls short of `amount_in` at the marginal, fee-adjusted spot
+    /// rate of every hop. Every hop follows its own curve in floating point so
+    /// integer rounding on tiny trades does not read as impact. A hop through
+    /// an empty pool is a total loss, 10_000.
+    fn path_impact_bps(
+        by_address: &HashMap<&str, &Pool>,
+        path: &[String],
+        pools: &[String],
+        amount_in: U256,
+    ) -> f64 {
+        const TOTAL_LOSS_BPS: f64 = 10_000.0;
+        let (mut output, mut ideal) = (f64::from(amount_in), f64::from(amount_in));
+        for (hop, address) in path.windows(2).zip(pools) {
+            let pool = by_address[address.as_str()];
+            let zero_for_one = pool.token0.address == hop[0] || pool.token0.symbol == hop[0];
+            let Some(spot) = pool.marginal_price(zero_for_one) else {
+                return TOTAL_LOSS_BPS;
+            };
+            let fee_kept = 1.0 - pool.effective_fee(zero_for_one) as f64 / 1_000_000.0;
+            ideal *= spot * fee_kept;
+            output = match pool.kind {
+                PoolKind::V2 => {
+                    let (reserve_in, reserve_out) = if zero_for_one {
+                        (f64::from(pool.reserve0), f64::from(pool.reserve1))
+                    } else {
+                        (f64::from(pool.reserve1), f64::from(pool.reserve0))
+                    };
+                    reserve_out * output * fee_kept / (reserve_in + output * fee_kept)
+                }
+                PoolKind::V3 {
+                    sqrt_price_x96,
+                    tick,
+                    tick_spacing,
+                } => Self::v3_amount_out_f64(
+                    pool,
+                    (sqrt_price_x96, tick, tick_spacing),
+                    zero_for_one,
+                    output,
+                ),
+                PoolKind::Stable { amplification } => {
+                    let balances = if zero_for_one {
+                        (pool.reserve0, pool.reserve1)
+                    } else {
+                        (pool.reserve1, pool.reserve0)
+                    };
+                    let fee = pool.effective_fee(zero_for_one);
+                    match stableswap_amount_out_f64(amplification, balances, fee, output) {
+                        Some(out) => out,
+                        None => return TOTAL_LOSS_BPS,
+                    }
+                }
+            };
+        }
+        if ideal <= 0.0 {
+            return TOTAL_LOSS_BPS;
+        }
+        (1.0 - output / ideal) * 10_000.0
+    }
+
+    /// Output of pushing `amount_in` along an already-chosen path and its pools.
+    fn quote_path(
+        pools_snapshot: &[Pool],
+        (path, pools): (&[String], &[String]),
+        amount_in: U256,
+    ) -> U256 {
+        let by_address: HashMap<&str, &Pool> = pools_snapshot
+            .iter()
+            .map(|p| (p.address.as_str(), p))
+            .collect();
+        path.windows(2)
+            .zip(pools)
+            .fold(amount_in, |amount, (hop, address)| {
+                let pool = by_address[address.as_str()];
+                let zero_for_one = pool.token0.address == hop[0] || pool.token0.symbol == hop[0];
+                Self::calculate_amount_out(pool, zero_for_one, amount)
+            })
+    }
+
+    fn parse_pool(pool_dict: &Bound<'_, PyDict>) -> PyResult<Pool> {
+        let token0_item = pool_dict
+            .get_item("token0")?
+            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing token0"))?;
+        let token0: &Bound<PyDict> = token0_item.downcast()?;
+        let token1_item = pool_dict
+            .get_item("token1")?
+            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing token1"))?;
+        let token1: &Bound<PyDict> = token1_item.downcast()?;
+        let liquidity = pool_dict
+            .get_item("liquidity")?
+            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing liquidity"))?
+            .extract::<Amount>()?
+            .0;
+        let address: String = pool_dict
+            .get_item("address")?
+            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing address"))?
+            .extract()?;
+        let kind = match optional_item::<String>(pool_dict, "kind")?.as_deref() {
+            None | Some("v2") => PoolKind::V2,
+            Some("v3") => Self::parse_v3_kind(pool_dict)?,
+            Some("stable") => {
+                let amplification: u128 = pool_dict
+                    .get_item("amplification")?
+                    .ok_or_else(|| {
+                        pyo3::exceptions::PyKeyError::new_err(
+                            "missing amplification for stable pool",
+                        )
+                    })?
+                    .extract()?;
+                if amplification == 0 {
+                    return Err(pyo3::exceptions::PyValueError::new_err(
+                        "amplification must be positive",
+                    ));
+                }
+                PoolKind::Stable { amplification }
+            }
+            Some(other) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "unknown pool kind '{other}' for pool '{address}'"
+                )))
+            }
+        };
+        let (reserve0, reserve1) = match &kind {
+            PoolKind::V2 => {
+                // Pools without explicit reserves split liquidity evenly across both sides
+                let reserve0: Option<Amount> = optional_item(pool_dict, "reserve0")?;
+                let reserve1: Option<Amount> = optional_item(pool_dict, "reserve1")?;
+                if reserve0.is_none() || reserve1.is_none() {
+                    tracing::warn!(
+                        pool = %address,
+                        liquidity = %liquidity,
+                        "pool has no reserve0/reserve1; splitting liquidity evenly"
+                    );
+                }
+                let half = liquidity >> 1;
+                (
+                    reserve0.map_or(half, |r| r.0),
+                    reserve1.map_or(half, |r| r.0),
+                )
+            }
+            PoolKind::V3 { sqrt_price_x96, .. } => virtual_reserves(liquidity, *sqrt_price_x96),
+            PoolKind::Stable { amplification } => {
+                let balances: Vec<Amount> = pool_dict
+                    .get_item("balances")?
+                    .ok_or_else(|| {
+                        pyo3::exceptions::PyKeyError::new_err("missing balances for stable pool")
+                    })?
+                    .extract()?;
+                let [Amount(balance0), Amount(balance1)] = balances[..] else {
+                    return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                        "stable pool '{address}' needs exactly two balances, got {}",
+                        balances.len()
+                    )));
+                };
+                // Reject pools whose invariant cannot be solved up front
+                stableswap_invariant(*amplification, balance0, balance1).map_err(|e| {
+                    pyo3::exceptions::PyValueError::new_err(format!("stable pool '{address}': {e}"))
+                })?;
+                (balance0, balance1)
+            }
+        };
+        let fee: u32 = pool_dict
+            .get_item("fee")?
+            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing fee"))?
+            .extract()?;
+        let fee_token0_to_token1: Option<u32> = optional_item(pool_dict, "fee_token0_to_token1")?;
+        let fee_token1_to_token0: Option<u32> = optional_item(pool_dict, "fee_token1_to_token0")?;
+        // Rebates are in basis points; fees are parts per million
+        let rebate_bps: u32 = optional_item(pool_dict, "rebate_bps")?.unwrap_or(0);
+        let lowest_fee = fee_token0_to_token1
+            .unwrap_or(fee)
+            .min(fee_token1_to_token0.unwrap_or(fee));
+        if u64::from(rebate_bps) * 100 > u64::from(lowest_fee) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "rebate_bps {rebate_bps} exceeds pool fee {lowest_fee}"
+            )));
+        }
+        Ok(Pool {
+            address,
+            token0: Token {
+                address: normalize_token(
+                    &token0
                         .get_item("address")?
                         .ok_or_else(|| {
-                            pyo3::exceptions::PyKeyError::new_err("missing token1.address")
+                            pyo3::exceptions::PyKeyError::new_err("missing token0.address")
                         })?
-                        .extract()?,
-                    symbol: token1
-                        .get_item("symbol")?
+                        .extract::<String>()?,
+                )?,
+                symbol: token0
+                    .get_item("symbol")?
+                    .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing token0.symbol"))?
+                    .extract()?,
+            },
+            token1: Token {
+                address: normalize_token(
+                    &token1
+                        .get_item("address")?
                         .ok_or_else(|| {
-                            pyo3::exceptions::PyKeyError::new_err("missing token1.symbol")
+                            pyo3::exceptions::PyKeyError::new_err("missing token1.address")
                         })?
-                        .extract()?,
-                },
-                fee: pool_dict
-                    .get_item("fee")?
-                    .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing fee"))?
+                        .extract::<String>()?,
+                )?,
+                symbol: token1
+                    .get_item("symbol")?
+                    .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing token1.symbol"))?
                     .extract()?,
-                liquidity: pool_dict
-                    .get_item("liquidity")?
-                    .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("missing liquidity"))?
-                    .extract()?,
-            };
-            map.insert(pool.address.clone(), pool);
+            },
+            fee,
+            rebate_bps,
+            fee_token0_to_token1,
+            fee_token1_to_token0,
+            liquidity,
+            reserve0,
+            reserve1,
+            last_updated_ms: optional_item(pool_dict, "last_updated")?.unwrap_or_else(now_ms),
+            kind,
+        })
+    }
+
+    fn parse_v3_kind(pool_dict: &Bound<'_, PyDict>) -> PyResult<PoolKind> {
+        let required = |key: &str| {
+            pool_dict.get_item(key)?.ok_or_else(|| {
+                pyo3::exceptions::PyKeyError::new_err(format!("missing {key} for v3 pool"))
+            })
+        };
+        // uint160 on chain, so it may not fit in a u128
+        let sqrt_price_text = required("sqrt_price_x96")?.str()?.to_string();
+        let sqrt_price_x96: U256 = sqrt_price_text.parse().map_err(|e| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "invalid sqrt_price_x96 {sqrt_price_text:?}: {e}"
+            ))
+        })?;
+        let tick: i32 = required("tick")?.extract()?;
+        let tick_spacing: i32 = required("tick_spacing")?.extract()?;
+        if tick_spacing <= 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "tick_spacing must be positive",
+            ));
         }
-        Ok(())
+        Ok(PoolKind::V3 {
+            sqrt_price_x96,
+            tick,
+            tick_spacing,
+        })
     }
-}
 
-// Pure Rust helper
-impl ExecutionEngine {
+    /// Constant-product output for swapping `amount_in` through `pool`.
+    /// `zero_for_one` is true when `amount_in` is denominated in token0.
+    /// Uniswap V2 form with the fee taken from the input:
+    /// `reserve_out * in_with_fee / (reserve_in * 1e6 + in_with_fee)` where
+    /// `in_with_fee = amount_in * (1e6 - fee)`; U512 intermediates keep large
+    /// reserves from overflowing.
     #[inline]
-    fn calculate_amount_out(pool: &Pool, amount_in: u128) -> u128 {
-        // Replace with correct AMM formula later
-        amount_in.saturating_sub((amount_in as u128 * pool.fee as u128) / 1_000_000u128)
+    fn calculate_amount_out(pool: &Pool, zero_for_one: bool, amount_in: U256) -> U256 {
+        match pool.kind {
+            PoolKind::V2 => {}
+            PoolKind::V3 {
+                sqrt_price_x96,
+                tick,
+                tick_spacing,
+            } => {
+                return Self::v3_amount_out(
+                    pool,
+                    (sqrt_price_x96, tick, tick_spacing),
+                    zero_for_one,
+                    amount_in,
+                )
+            }
+            PoolKind::Stable { amplification } => {
+                let balances = if zero_for_one {
+                    (pool.reserve0, pool.reserve1)
+                } else {
+                    (pool.reserve1, pool.reserve0)
+                };
+                return stableswap_amount_out(
+                    amplification,
+                    balances,
+                    pool.effective_fee(zero_for_one),
+                    amount_in,
+                )
+                .unwrap_or_else(|e| {
+                    tracing::warn!(pool = %pool.address, amount_in = %amount_in, "{e}");
+                    U256::ZERO
+                });
+            }
+        }
+        let (reserve_in, reserve_out) = if zero_for_one {
+            (pool.reserve0, pool.reserve1)
+        } else {
+            (pool.reserve1, pool.reserve0)
+        };
+        if reserve_in.is_zero() || reserve_out.is_zero() {
+            return U256::ZERO;
+        }
+        let fee_denominator = U512::from(1_000_000u64);
+        let amount_in_with_fee = U512::from(amount_in)
+            * (fee_denominator - U512::from(pool.effective_fee(zero_for_one)));
+        let numerator = U512::from(reserve_out) * amount_in_with_fee;
+        let denominator = U512::from(reserve_in) * fee_denominator + amount_in_with_fee;
+        // Output is always below reserve_out, so it fits back into U256
+        (numerator / denominator).to::<U256>()
+    }
+
+    /// Uniswap V3 exact-input swap against the active liquidity, with the
+    /// SqrtPriceMath rounding (next price rounded towards the start, output
+    /// down). First cut: liquidity is assumed constant from the current tick
+    /// range through the adjacent one, so the price may cross at most one
+    /// initialized boundary; input that would move it past the far edge of the
+    /// adjacent range is not filled.
+    fn v3_amount_out(
+        pool: &Pool,
+        v3: (U256, i32, i32),
+        zero_for_one: bool,
+        amount_in: U256,
+    ) -> U256 {
+        Self::v3_swap(pool, v3, zero_for_one, amount_in).map_or(U256::ZERO, |(out, _)| out)
+    }
+
+    /// `v3_amount_out` together with the sqrt price the swap leaves behind, or
+    /// `None` when the pool has no liquidity in the swap's direction.
+    fn v3_swap(
+        pool: &Pool,
+        (sqrt_price_x96, tick, tick_spacing): (U256, i32, i32),
+        zero_for_one: bool,
+        amount_in: U256,
+    ) -> Option<(U256, U256)> {
+        let liquidity = U512::from(pool.liquidity);
+        let sqrt_price = U512::from(sqrt_price_x96);
+        if liquidity.is_zero() || sqrt_price.is_zero() {
+            return None;
+        }
+        let q96: U512 = U512::from(1u8) << 96;
+        let amount = U512::from(amount_in)
+            * U512::from(1_000_000 - pool.effective_fee(zero_for_one))
+            / U512::from(1_000_000u32);
+        let lower_tick = tick.div_euclid(tick_spacing) * tick_spacing;
+        let (out, next) = if zero_for_one {
+            let limit = U512::from(sqrt_price_at_tick(lower_tick - tick_spacing));
+            if limit >= sqrt_price {
+                return None;
+            }
+            // Price falls: sqrt_next = L * Q96 * sqrt_p / (L * Q96 + amount * sqrt_p)
+            let numerator = liquidity * q96 * sqrt_price;
+            let denominator = liquidity * q96 + amount * sqrt_price;
+            let next = numerator.div_ceil(denominator).max(limit);
+            (liquidity * (sqrt_price - next) / q96, next)
+        } else {
+            let limit = U512::from(sqrt_price_at_tick(lower_tick + 2 * tick_spacing));
+            if limit <= sqrt_price {
+                return None;
+            }
+            // Price rises: sqrt_next = sqrt_p + amount * Q96 / L
+            let next = (sqrt_price + amount * q96 / liquidity).min(limit);
+            (
+                liquidity * q96 * (next - sqrt_price) / next / sqrt_price,
+                next,
+            )
+        };
+        Some((out.saturating_to::<U256>(), next.saturating_to::<U256>()))
+    }
+
+    /// `v3_amount_out` in floating point, for impact estimates that integer
+    /// rounding would swamp on small trades. Within the range the output is
+    /// written without the difference of nearby sqrt prices; past the limit it
+    /// is the whole amount the range holds.
+    fn v3_amount_out_f64(
+        pool: &Pool,
+        (sqrt_price_x96, tick, tick_spacing): (U256, i32, i32),
+        zero_for_one: bool,
+        amount_in: f64,
+    ) -> f64 {
+        let q96 = 2f64.powi(96);
+        let liquidity = f64::from(pool.liquidity);
+        let sqrt_price = f64::from(sqrt_price_x96) / q96;
+        if liquidity <= 0.0 || sqrt_price <= 0.0 {
+            return 0.0;
+        }
+        let amount = amount_in * (1.0 - f64::from(pool.effective_fee(zero_for_one)) / 1_000_000.0);
+        let lower_tick = tick.div_euclid(tick_spacing) * tick_spacing;
+        if zero_for_one {
+            let limit = f64::from(sqrt_price_at_tick(lower_tick - tick_spacing)) / q96;
+            let next = liquidity * sqrt_price / (liquidity + amount * sqrt_price);
+            if next <= limit {
+                (liquidity * (sqrt_price - limit)).max(0.0)
+            } else {
+                liquidity * sqrt_price * sqrt_price * amount / (liquidity + amount * sqrt_price)
+            }
+        } else {
+            let limit = f64::from(sqrt_price_at_tick(lower_tick + 2 * tick_spacing)) / q96;
+            let next = sqrt_price + amount / liquidity;
+            if next >= limit {
+                (liquidity * (limit - sqrt_price) / (limit * sqrt_price)).max(0.0)
+            } else {
+                amount / (next * sqrt_price)
+            }
+        }
+    }
+}
+
+/// Newton iterations allowed for each stableswap solve, as in Curve's pools.
+const STABLESWAP_MAX_ITERATIONS: usize = 255;
+
+/// Stableswap invariant `D` of a two-coin pool: the solution of
+/// `A·n^n·Σx + D = A·n^n·D + D^(n+1) / (n^n·Πx)` with n = 2, by Newton's
+/// method from `D = Σx`. Errors if it has not converged to within one unit
+/// after `STABLESWAP_MAX_ITERATIONS` steps.
+fn stableswap_invariant(
+    amplification: u128,
+    balance0: U256,
+    balance1: U256,
+) -> Result<U512, String> {
+    let (x0, x1) = (U512::from(balance0), U512::from(balance1));
+    let sum = x0 + x1;
+    if sum.is_zero() {
+        return Ok(U512::ZERO);
+    }
+    if x0.is_zero() || x1.is_zero() {
+        return Err("stableswap balances must both be positive".to_string());
+    }
+    let n = U512::from(2u8);
+    let ann = U512::from(amplification) * n;
+    let mut d = sum;
+    for _ in 0..STABLESWAP_MAX_ITERATIONS {
+        // D_P = D^(n+1) / (n^n · Πx)
+        let d_p = d * d / (x0 * n) * d / (x1 * n);
+        let previous = d;
+        d = (ann * sum + d_p * n) * d / ((ann - U512::from(1u8)) * d + (n + U512::from(1u8)) * d_p);
+        if d.abs_diff(previous) <= U512::from(1u8) {
+            return Ok(d);
+        }
+    }
+    Err(format!(
+        "stableswap invariant did not converge in {STABLESWAP_MAX_ITERATIONS} iterations"
+    ))
+}
+
+/// Stableswap output for selling `amount_in` of the coin whose balance is
+/// `balances.0`: solve the invariant for the new balance of the other coin
+/// (Curve's `get_y`), then charge `fee` (parts per million) on the output.
+fn stableswap_amount_out(
+    amplification: u128,
+    (balance_in, balance_out): (U256, U256),
+    fee: u32,
+    amount_in: U256,
+) -> Result<U256, String> {
+    if balance_in.is_zero() || balance_out.is_zero() {
+        return Ok(U256::ZERO);
+    }
+    let d = stableswap_invariant(amplification, balance_in, balance_out)?;
+    let n = U512::from(2u8);
+    let ann = U512::from(amplification) * n;
+    let x = U512::from(balance_in) + U512::from(amount_in);
+    // y^2 + (b - D)·y = c with b = x + D / Ann and c = D^(n+1) / (n^n · x · Ann)
+    let c = d * d / (x * n) * d / (ann * n);
+    let b = x + d / ann;
+    let mut y = d;
+    for _ in 0..STABLESWAP_MAX_ITERATIONS {
+        let previous = y;
+        y = (y * y + c) / (n * y + b - d);
+        if y.abs_diff(previous) <= U512::from(1u8) {
+            // One unit kept back against rounding, as in Curve's exchange
+            let out = U512::from(balance_out).saturating_sub(y + U512::from(1u8));
+            let out_fee = out * U512::from(fee) / U512::from(1_000_000u32);
+            return Ok((out - out_fee).saturating_to::<U256>());
+        }
+    }
+    Err(format!(
+        "stableswap output did not converge in {STABLESWAP_MAX_ITERATIONS} iterations"
+    ))
+}
+
+/// `stableswap_amount_out` in floating point, for impact estimates that integer
+/// rounding would swamp on small trades. Solves the invariant for the output
+/// `t` directly: in units of `D`, `a·t² - b·t + c = 0` with `a = ann·x·y`,
+/// `b = a·(s + y) + 1/4` and `c = s·y·(a + 1 / (4·(x + s)))`, taking the root
+/// below the output balance in a form free of cancellation.
+fn stableswap_amount_out_f64(
+    amplification: u128,
+    (balance_in, balance_out): (U256, U256),
+    fee: u32,
+    amount_in: f64,
+) -> Option<f64> {
+    let d = f64::from(stableswap_invariant(amplification, balance_in, balance_out).ok()?);
+    if d <= 0.0 {
+        return None;
+    }
+    let (x, y, s) = (
+        f64::from(balance_in) / d,
+        f64::from(balance_out) / d,
+        amount_in / d,
+    );
+    let a = 2.0 * amplification as f64 * x * y;
+    let b = a * (s + y) + 0.25;
+    let c = s * y * (a + 0.25 / (x + s));
+    let out = 2.0 * c / (b + (b * b - 4.0 * a * c).max(0.0).sqrt()) * d;
+    Some(out * (1.0 - f64::from(fee) / 1_000_000.0))
+}
+
+/// Virtual reserves `(L / sqrt_p, L * sqrt_p)` of a V3 position, so spot
+/// prices and confidence treat V3 pools like V2 ones.
+fn virtual_reserves(liquidity: U256, sqrt_price_x96: U256) -> (U256, U256) {
+    if sqrt_price_x96.is_zero() {
+        return (U256::ZERO, U256::ZERO);
+    }
+    let liquidity = U512::from(liquidity);
+    let sqrt_price = U512::from(sqrt_price_x96);
+    let q96: U512 = U512::from(1u8) << 96;
+    (
+        (liquidity * q96 / sqrt_price).saturating_to::<U256>(),
+        (liquidity * sqrt_price / q96).saturating_to::<U256>(),
+    )
+}
+
+/// `sqrt(1.0001^tick) * 2^96`, clamped to the V3 tick range. Computed in
+/// floating point, which is exact enough for locating range boundaries.
+fn sqrt_price_at_tick(tick: i32) -> U256 {
+    const MAX_TICK: i32 = 887_272;
+    let tick = tick.clamp(-MAX_TICK, MAX_TICK);
+    let sqrt_price = 1.0001f64.powf(f64::from(tick) / 2.0);
+    // Scale by 2^96 through the binary exponent so no mantissa bits are lost
+    let bits = sqrt_price.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i32 - 1075 + 96;
+    let mantissa = U256::from((bits & ((1u64 << 52) - 1)) | (1u64 << 52));
+    if exponent >= 0 {
+        mantissa << exponent as usize
+    } else {
+        mantissa >> (-exponent) as usize
+    }
+}
+
+/// Awaitable that routes `params` on every engine concurrently, on the shared
+/// tokio runtime's blocking threads, and resolves to the highest-output route
+/// tagged with the winning `engine_id`, or `None` when no engine reaches
+/// `token_out`. Params and pool snapshots are checked before it returns.
+#[pyfunction]
+pub fn best_route_across(
+    py: Python,
+    engines: Vec<PyRef<'_, ExecutionEngine>>,
+    params: &Bound<'_, PyDict>,
+) -> PyResult<PyObject> {
+    let request = Arc::new(RouteRequest::from_params(params)?);
+    let mut jobs = Vec::with_capacity(engines.len());
+    for engine in &engines {
+        if let Some(pools_snapshot) = engine.route_snapshot(&request)? {
+            jobs.push((engine.engine_id.clone(), pools_snapshot));
+        }
+    }
+    let bridge = LoopFuture::new(py)?;
+    let awaitable = bridge.awaitable(py);
+
+    runtime_handle()?.spawn(async move {
+        let tasks = jobs.into_iter().map(|(engine_id, pools_snapshot)| {
+            let request = Arc::clone(&request);
+            tokio::task::spawn_blocking(move || {
+                let started = Instant::now();
+                let route = request.search(&pools_snapshot);
+                METRICS.record_route(started.elapsed());
+                route.map(|route| (engine_id, route))
+            })
+        });
+        let mut best: Result<Option<(String, Route)>, String> = Ok(None);
+        for result in futures::future::join_all(tasks).await {
+            match (result, &mut best) {
+                (Err(e), _) => best = Err(format!("route task failed: {e}")),
+                (Ok(Some((engine_id, route))), Ok(current))
+                    if current
+                        .as_ref()
+                        .is_none_or(|(_, b)| route.output_amount > b.output_amount) =>
+                {
+                    *current = Some((engine_id, route));
+                }
+                _ => {}
+            }
+        }
+        let _ = tokio::task::spawn_blocking(move || {
+            bridge.resolve(move |py| match best {
+                Ok(Some((engine_id, route))) => {
+                    let out = ExecutionEngine::requested_route_to_py(py, &request, &route)?;
+                    out.bind(py).set_item("engine_id", engine_id)?;
+                    Ok(out)
+                }
+                Ok(None) => Ok(py.None()),
+                Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e)),
+            })
+        })
+        .await;
+    });
+    Ok(awaitable)
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Token identifiers that look like addresses (`0x`-prefixed, or bare 40-digit
+/// hex) must be exactly 20 bytes and are lowercased, so case never affects
+/// matching. Anything else is kept verbatim and matches by symbol.
+fn normalize_token(value: &str) -> PyResult<String> {
+    let is_hex = |digits: &str| digits.bytes().all(|b| b.is_ascii_hexdigit());
+    let digits = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(digits) => digits,
+        None if value.len() == 40 && is_hex(value) => value,
+        None => return Ok(value.to_string()),
+    };
+    if digits.len() != 40 || !is_hex(digits) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "malformed token address {value:?}: expected 20 bytes of hex"
+        )));
+    }
+    Ok(format!("0x{}", digits.to_ascii_lowercase()))
+}
+
+fn normalize_tokens<C: FromIterator<String>>(
+    tokens: impl IntoIterator<Item = String>,
+) -> PyResult<C> {
+    tokens.into_iter().map(|t| normalize_token(&t)).collect()
+}
+
+/// A raw token amount from Python: an int or a decimal string, up to
+/// 2^256 - 1, so amounts wider than u128 survive the trip. Amounts going back
+/// to Python are decimal strings for the same reason.
+#[derive(Clone, Copy)]
+struct Amount(U256);
+
+impl<'py> FromPyObject<'py> for Amount {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let text = if value.is_instance_of::<PyString>() {
+            value.extract::<String>()?
+        } else if value.is_instance_of::<PyInt>() {
+            value.str()?.to_string()
+        } else {
+            return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "amount must be an int or a decimal string, got {}",
+                value.get_type().name()?
+            )));
+        };
+        if text.starts_with('-') {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "amount must be non-negative, got {text}"
+            )));
+        }
+        U256::from_str_radix(&text, 10).map(Amount).map_err(|e| {
+            pyo3::exceptions::PyValueError::new_err(format!("invalid amount {text:?}: {e}"))
+        })
+    }
+}
+
+/// Non-negative finite `weights` as integers on one binary scale: each is its
+/// exact f64 value times the power of two that puts the largest at 181 bits,
+/// so zero stays zero and only bits 2^-128 below the largest weight are lost.
+fn fixed_point_weights(weights: &[f64]) -> Vec<U256> {
+    // value = mantissa * 2^exponent, subnormals included
+    let parts: Vec<(U256, i32)> = weights
+        .iter()
+        .map(|w| {
+            let bits = w.to_bits();
+            let biased = ((bits >> 52) & 0x7ff) as i32;
+            let fraction = bits & ((1u64 << 52) - 1);
+            match biased {
+                0 => (U256::from(fraction), 1 - 1075),
+                _ => (U256::from(fraction | (1u64 << 52)), biased - 1075),
+            }
+        })
+        .collect();
+    let top = parts
+        .iter()
+        .filter(|(mantissa, _)| !mantissa.is_zero())
+        .map(|(_, exponent)| *exponent)
+        .max()
+        .unwrap_or(0);
+    parts
+        .into_iter()
+        .map(|(mantissa, exponent)| match exponent - top + 128 {
+            shift if shift >= 0 => mantissa << shift as usize,
+            shift => mantissa.checked_shr((-shift) as usize).unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// `value` truncated towards zero, saturating like an `as` cast.
+fn amount_from_f64(value: f64) -> U256 {
+    U256::saturating_from(value.trunc())
+}
+
+/// Route amounts serialize as decimal strings; U256 has no serde support here.
+mod decimal {
+    use alloy_primitives::U256;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<U256, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        U256::from_str_radix(&text, 10).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Extract an optional key from a params dict, treating Python `None` as absent.
+fn optional_item<'py, T: FromPyObject<'py>>(
+    dict: &Bound<'py, PyDict>,
+    key: &str,
+) -> PyResult<Option<T>> {
+    match dict.get_item(key)? {
+        Some(value) if !value.is_none() => Ok(Some(value.extract()?)),
+        _ => Ok(None),
     }
 }
 
 pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<ExecutionEngine>()?;
+    m.add_function(wrap_pyfunction!(best_route_across, m)?)?;
     Ok(())
 }
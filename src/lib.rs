@@ -1,46 +1,323 @@
 // lib.rs
-use once_cell::sync::{Lazy, OnceCell};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
-use tokio::runtime::{Builder, Runtime};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::logging::{LogFormat, LOG_LEVELS};
 
 mod chain_monitor;
 mod execution;
+mod logging;
 mod market_data;
+mod metrics;
+mod rpc;
+mod tasks;
 mod transaction;
+mod url;
+mod ws;
+
+const DEFAULT_MAX_BLOCKING_THREADS: usize = 1024;
 
-static GLOBAL_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
-    Builder::new_multi_thread()
-        .enable_all()
-        .thread_name("platform-rt")
-        .max_blocking_threads(1024)
-        .build()
-        .expect("tokio runtime")
-});
+/// Settings the runtime was built with; `None` workers means tokio's default
+/// of one per core.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct RuntimeConfig {
+    worker_threads: Option<usize>,
+    max_blocking_threads: usize,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            worker_threads: None,
+            max_blocking_threads: DEFAULT_MAX_BLOCKING_THREADS,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    fn build(self) -> std::io::Result<Runtime> {
+        let mut builder = Builder::new_multi_thread();
+        builder
+            .enable_all()
+            .thread_name("platform-rt")
+            .max_blocking_threads(self.max_blocking_threads);
+        if let Some(workers) = self.worker_threads {
+            builder.worker_threads(workers);
+        }
+        builder.build()
+    }
+}
+
+/// The runtime is owned behind a lock so `shutdown_rust_runtime` can take it;
+/// the handle stays valid for the process lifetime.
+struct GlobalRuntime {
+    runtime: Mutex<Option<Runtime>>,
+    handle: Handle,
+    config: RuntimeConfig,
+}
 
-static TRACING_INIT: OnceCell<()> = OnceCell::new();
+impl GlobalRuntime {
+    fn new(config: RuntimeConfig) -> std::io::Result<Self> {
+        let runtime = config.build()?;
+        Ok(Self {
+            handle: runtime.handle().clone(),
+            runtime: Mutex::new(Some(runtime)),
+            config,
+        })
+    }
+}
+
+// Built by the first `initialize_rust_runtime`, or with defaults by the first
+// `runtime_handle` if nothing initialized it explicitly
+static GLOBAL_RUNTIME: OnceCell<GlobalRuntime> = OnceCell::new();
+// Once set, the runtime is gone for good; nothing may spawn onto it again
+static RUNTIME_SHUT_DOWN: AtomicBool = AtomicBool::new(false);
+
+// The log filter the subscriber was installed with
+static TRACING_INIT: OnceCell<String> = OnceCell::new();
+
+fn shut_down_error() -> PyErr {
+    PyRuntimeError::new_err("tokio runtime has been shut down")
+}
+
+/// Start tracing and the tokio runtime, returning the active log filter.
+/// `worker_threads` and `max_blocking_threads` only take effect on the call
+/// that builds the runtime; later calls that ask for different values raise
+/// `RuntimeError`. Likewise `log_level` (one of `"error"`, `"warn"`, `"info"`,
+/// `"debug"`, `"trace"`; `RUST_LOG` when omitted) only applies on the first
+/// call, so the return value shows which filter actually won. `log_format`
+/// (`"text"`, the default, or `"json"` for one JSON object per line) is
+/// fixed by the first call in the same way.
+#[pyfunction]
+#[pyo3(signature = (worker_threads=None, max_blocking_threads=None, log_level=None, log_format=None))]
+pub fn initialize_rust_runtime(
+    worker_threads: Option<usize>,
+    max_blocking_threads: Option<usize>,
+    log_level: Option<&str>,
+    log_format: Option<&str>,
+) -> PyResult<String> {
+    if worker_threads == Some(0) || max_blocking_threads == Some(0) {
+        return Err(PyValueError::new_err("thread counts must be positive"));
+    }
+    let log_level = log_level
+        .map(|level| {
+            let lowered = level.to_ascii_lowercase();
+            if LOG_LEVELS.contains(&lowered.as_str()) {
+                Ok(lowered)
+            } else {
+                Err(PyValueError::new_err(format!(
+                    "unknown log_level {level:?}; expected one of {}",
+                    LOG_LEVELS.join(", ")
+                )))
+            }
+        })
+        .transpose()?;
+    let log_format = log_format
+        .map(LogFormat::parse)
+        .transpose()
+        .map_err(PyValueError::new_err)?
+        .unwrap_or(LogFormat::Text);
+    if RUNTIME_SHUT_DOWN.load(Ordering::Acquire) {
+        return Err(shut_down_error());
+    }
+    let active_filter = TRACING_INIT
+        .get_or_init(|| logging::init(log_level.as_deref(), log_format))
+        .clone();
+    let requested = RuntimeConfig {
+        worker_threads,
+        max_blocking_threads: max_blocking_threads.unwrap_or(DEFAULT_MAX_BLOCKING_THREADS),
+    };
+    let mut built = false;
+    let active = GLOBAL_RUNTIME
+        .get_or_try_init(|| {
+            built = true;
+            GlobalRuntime::new(requested)
+        })
+        .map_err(|e| PyRuntimeError::new_err(format!("failed to build tokio runtime: {e}")))?
+        .config;
+    // Unspecified parameters accept whatever the runtime already uses
+    let conflicts = worker_threads.is_some_and(|n| Some(n) != active.worker_threads)
+        || max_blocking_threads.is_some_and(|n| n != active.max_blocking_threads);
+    if !built && conflicts {
+        let workers = active
+            .worker_threads
+            .map_or_else(|| "default".to_string(), |n| n.to_string());
+        return Err(PyRuntimeError::new_err(format!(
+            "tokio runtime already initialized with worker_threads={workers}, \
+             max_blocking_threads={}",
+            active.max_blocking_threads
+        )));
+    }
+    Ok(active_filter)
+}
 
+/// Stop the runtime's worker threads, giving running tasks (subscriptions,
+/// pending submissions) up to `timeout_secs` to wind down. This is final:
+/// afterwards every function that needs the runtime raises `RuntimeError`,
+/// and the runtime cannot be initialized again in this process. Repeated
+/// calls are no-ops.
 #[pyfunction]
-pub fn initialize_rust_runtime() -> PyResult<()> {
-    // Idempotent, no env-filter feature required
-    let _ = TRACING_INIT.get_or_init(|| {
-        let _ = tracing_subscriber::fmt::try_init();
-        ()
-    });
-    Lazy::force(&GLOBAL_RUNTIME);
+#[pyo3(signature = (timeout_secs=5.0))]
+pub fn shutdown_rust_runtime(py: Python, timeout_secs: f64) -> PyResult<()> {
+    if !timeout_secs.is_finite() || timeout_secs < 0.0 {
+        return Err(PyValueError::new_err(format!(
+            "timeout_secs must be a non-negative number, got {timeout_secs}"
+        )));
+    }
+    // Dropping a runtime from one of its own threads panics
+    if Handle::try_current().is_ok() {
+        return Err(PyRuntimeError::new_err(
+            "shutdown_rust_runtime cannot be called from a runtime thread",
+        ));
+    }
+    if RUNTIME_SHUT_DOWN.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+    let Some(runtime) = GLOBAL_RUNTIME.get().and_then(|g| g.runtime.lock().take()) else {
+        return Ok(());
+    };
+    // Blocking tasks may be waiting on the GIL to finish
+    py.allow_threads(|| runtime.shutdown_timeout(Duration::from_secs_f64(timeout_secs)));
     Ok(())
 }
 
+// How long interpreter exit waits for in-flight runtime work to finish
+const EXIT_SHUTDOWN_TIMEOUT_SECS: f64 = 1.0;
+
+/// Registered with `atexit`. A runtime thread still holding or waiting for
+/// the GIL once the interpreter starts finalizing crashes the process, so the
+/// runtime is stopped while Python is still fully alive.
+#[pyfunction]
+fn shutdown_at_exit(py: Python) -> PyResult<()> {
+    shutdown_rust_runtime(py, EXIT_SHUTDOWN_TIMEOUT_SECS)
+}
+
+/// Handle to the shared runtime, building it with defaults on first use.
+/// Fails once `shutdown_rust_runtime` has been called.
 #[inline]
-pub fn runtime_handle() -> &'static tokio::runtime::Handle {
-    GLOBAL_RUNTIME.handle()
+pub fn runtime_handle() -> PyResult<&'static Handle> {
+    if RUNTIME_SHUT_DOWN.load(Ordering::Acquire) {
+        return Err(shut_down_error());
+    }
+    let global = GLOBAL_RUNTIME
+        .get_or_init(|| GlobalRuntime::new(RuntimeConfig::default()).expect("tokio runtime"));
+    Ok(&global.handle)
+}
+
+type Settle = Box<dyn FnOnce(Python<'_>, Option<&Bound<'_, PyAny>>) -> PyResult<()> + Send>;
+
+/// Callback scheduled onto the awaiting event loop by `LoopFuture::settle`.
+#[pyclass]
+struct SettleFuture {
+    future: PyObject,
+    settle: Mutex<Option<Settle>>,
+}
+
+#[pymethods]
+impl SettleFuture {
+    fn __call__(&self, py: Python) -> PyResult<()> {
+        match self.settle.lock().take() {
+            Some(settle) => settle(py, Some(self.future.bind(py))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Awaitable bridge from work on the shared runtime back to the caller's
+/// asyncio loop. The result is converted and set on the loop thread itself, so
+/// a runtime thread's last Python call is the `call_soon_threadsafe` hand-off
+/// and nothing it does can race the awaiting coroutine resuming.
+pub(crate) struct LoopFuture {
+    event_loop: PyObject,
+    future: PyObject,
+}
+
+impl LoopFuture {
+    /// Create a future on the running loop; raises `RuntimeError` outside one.
+    pub(crate) fn new(py: Python) -> PyResult<Self> {
+        let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+        let future = event_loop.call_method0("create_future")?.unbind();
+        Ok(Self {
+            event_loop: event_loop.unbind(),
+            future,
+        })
+    }
+
+    /// The object to hand back to the awaiting caller.
+    pub(crate) fn awaitable(&self, py: Python) -> PyObject {
+        self.future.clone_ref(py)
+    }
+
+    /// Resolve the future with `produce`'s result (or its error).
+    pub(crate) fn resolve<F>(self, produce: F)
+    where
+        F: FnOnce(Python<'_>) -> PyResult<PyObject> + Send + 'static,
+    {
+        self.settle((), move |py, ()| produce(py), |()| {});
+    }
+
+    /// Resolve the future with `produce(value)`. If the awaiting task was
+    /// cancelled or its loop has closed, `value` goes to `restore` instead so
+    /// the caller can keep it for the next await. Call from a runtime thread
+    /// that does not hold the GIL.
+    pub(crate) fn settle<T, F, R>(self, value: T, produce: F, restore: R)
+    where
+        T: Send + 'static,
+        F: FnOnce(Python<'_>, T) -> PyResult<PyObject> + Send + 'static,
+        R: FnOnce(T) + Send + 'static,
+    {
+        let settle: Settle = Box::new(move |py, future| match future {
+            Some(future) if !future.call_method0("done")?.extract::<bool>()? => {
+                match produce(py, value) {
+                    Ok(result) => future.call_method1("set_result", (result,))?,
+                    Err(e) => future.call_method1("set_exception", (e.into_value(py),))?,
+                };
+                Ok(())
+            }
+            _ => {
+                restore(value);
+                Ok(())
+            }
+        });
+        Python::with_gil(|py| {
+            let callback = SettleFuture {
+                future: self.future,
+                settle: Mutex::new(Some(settle)),
+            };
+            let Ok(callback) = Bound::new(py, callback) else {
+                return;
+            };
+            // A closed loop has nobody left to resume
+            if self
+                .event_loop
+                .call_method1(py, "call_soon_threadsafe", (&callback,))
+                .is_err()
+            {
+                if let Some(settle) = callback.borrow().settle.lock().take() {
+                    let _ = settle(py, None);
+                }
+            }
+        });
+    }
 }
 
 #[pymodule]
 fn platform_rust(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(initialize_rust_runtime, m)?)?;
+    m.add_function(wrap_pyfunction!(shutdown_rust_runtime, m)?)?;
+    m.py()
+        .import("atexit")?
+        .call_method1("register", (wrap_pyfunction!(shutdown_at_exit, m)?,))?;
     execution::register(m)?;
     chain_monitor::register(m)?;
     market_data::register(m)?;
     transaction::register(m)?;
+    metrics::register(m)?;
+    tasks::register(m)?;
     Ok(())
 }
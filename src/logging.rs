@@ -0,0 +1,156 @@
+// logging.rs
+//! Tracing subscriber setup for `initialize_rust_runtime`, with a hand-rolled
+//! JSON-lines formatter (the crate is built without tracing-subscriber's
+//! `json` feature).
+use serde_json::{Map, Value};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+pub(crate) const LOG_LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub(crate) fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown log_format {name:?}; expected 'text' or 'json'"
+            )),
+        }
+    }
+}
+
+/// Install the fmt subscriber filtered by `log_level`, else by `RUST_LOG`
+/// (`target=level` directives; no env-filter feature required), else at
+/// `info`. Returns the filter in effect.
+pub(crate) fn init(log_level: Option<&str>, format: LogFormat) -> String {
+    let from_env = || {
+        std::env::var("RUST_LOG")
+            .ok()
+            .filter(|directives| directives.parse::<Targets>().is_ok())
+    };
+    let active = log_level
+        .map(str::to_string)
+        .or_else(from_env)
+        .unwrap_or_else(|| "info".to_string());
+    let targets: Targets = active.parse().expect("validated filter");
+    let (text, json) = match format {
+        LogFormat::Text => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => (
+            None,
+            Some(tracing_subscriber::fmt::layer().event_format(JsonFormat)),
+        ),
+    };
+    let _ = tracing_subscriber::registry()
+        .with(text)
+        .with(json)
+        .with(targets)
+        .try_init();
+    active
+}
+
+/// One JSON object per event: `timestamp` (RFC 3339, UTC), `level`,
+/// `target`, `fields` (including `message`) and, inside spans, `spans`
+/// listing their names from the root.
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut fields = FieldMap(Map::new());
+        event.record(&mut fields);
+        let mut line = Map::new();
+        line.insert("timestamp".into(), Value::String(rfc3339_now()));
+        line.insert("level".into(), Value::String(metadata.level().to_string()));
+        line.insert(
+            "target".into(),
+            Value::String(metadata.target().to_string()),
+        );
+        line.insert("fields".into(), Value::Object(fields.0));
+        if let Some(scope) = ctx.event_scope() {
+            let spans: Vec<Value> = scope
+                .from_root()
+                .map(|span| Value::String(span.name().to_string()))
+                .collect();
+            line.insert("spans".into(), Value::Array(spans));
+        }
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+struct FieldMap(Map<String, Value>);
+
+impl Visit for FieldMap {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{value:?}").into());
+    }
+}
+
+/// Current UTC time as `YYYY-MM-DDTHH:MM:SS.ffffffZ`.
+fn rfc3339_now() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let (days, secs) = ((now.as_secs() / 86_400) as i64, now.as_secs() % 86_400);
+    // Howard Hinnant's days-to-civil conversion
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z",
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60,
+        now.subsec_micros()
+    )
+}
@@ -1,55 +1,1229 @@
 // market_data.rs
-use pyo3::exceptions::{PyKeyError, PyTypeError};
+use pyo3::exceptions::{PyKeyError, PyRuntimeError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
+use pyo3::types::{PyDict, PyFloat, PyList};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::RwLock;
 
+/// Sum size per price level for each side. Prices and sizes may be ints,
+/// floats or decimal strings (or `Decimal`s); they are aggregated as exact
+/// fixed-point values so `0.015` and `0.0151` stay separate levels and sums do
+/// not pick up float error. Output values keep their input form: ints stay
+/// ints, any string or `Decimal` input gives decimal strings, otherwise floats.
+/// Also reports `bid_vwap`/`ask_vwap`, the size-weighted average price of each
+/// side, and the top of book: `best_bid`, `best_ask`, `spread` (ask minus bid,
+/// negative when crossed) and `mid_price` (a float unless prices are decimal
+/// strings). Each is `None` when a side it needs is empty.
+///
+/// `depth` keeps only the best N levels of each side in `bids`/`asks` (still in
+/// ascending price order); the other fields are computed over the full book.
+///
+/// When any entry carries a `source` (its venue), levels become
+/// `(price, size, {source: size})` so orders can be routed to where the
+/// liquidity sits; untagged entries are listed under `None`.
+///
+/// Zero sizes are ignored and negative ones raise ValueError, unless
+/// `allow_negative` treats them as removals for venues that publish deltas;
+/// levels netting to zero or below are then left out.
 #[pyfunction]
-pub fn aggregate_order_books(py: Python, books: &Bound<'_, PyList>) -> PyResult<PyObject> {
-    let mut bids: Vec<(i64, i64)> = Vec::new();
-    let mut asks: Vec<(i64, i64)> = Vec::new();
+#[pyo3(signature = (books, depth=None, allow_negative=false))]
+pub fn aggregate_order_books(
+    py: Python,
+    books: &Bound<'_, PyList>,
+    depth: Option<usize>,
+    allow_negative: bool,
+) -> PyResult<PyObject> {
+    let book = AggregatedBook::from_entries(books, allow_negative)?;
 
-    for any in books.iter() {
+    let out = PyDict::new(py);
+    let depth = depth.unwrap_or(usize::MAX);
+    let best_bids = book.bids.iter().skip(book.bids.len().saturating_sub(depth));
+    let best_asks = book.asks.iter().take(depth);
+    for (key, side, levels) in [
+        ("bids", Side::Bid, best_bids.collect::<Vec<_>>()),
+        ("asks", Side::Ask, best_asks.collect()),
+    ] {
+        let list = PyList::empty(py);
+        for (&price, &size) in levels {
+            let (price_py, size_py) = (book.price_py(py, price)?, book.size_py(py, size)?);
+            match &book.sources {
+                Some(sources) => {
+                    let by_source = PyDict::new(py);
+                    for (source, &contributed) in &sources[side as usize][&price] {
+                        by_source.set_item(source, book.size_py(py, contributed)?)?;
+                    }
+                    list.append((price_py, size_py, by_source))?;
+                }
+                None => list.append((price_py, size_py))?,
+            }
+        }
+        out.set_item(key, list)?;
+    }
+    out.set_item("bid_vwap", vwap(&book.bids, book.price_scale))?;
+    out.set_item("ask_vwap", vwap(&book.asks, book.price_scale))?;
+
+    // Top of book; a crossed book keeps its extremes and a negative spread
+    let best_bid = book.bids.last_key_value().map(|(&p, _)| p);
+    let best_ask = book.asks.first_key_value().map(|(&p, _)| p);
+    let price = |p: Option<i128>| p.map(|p| book.price_py(py, p)).transpose();
+    out.set_item("best_bid", price(best_bid)?)?;
+    out.set_item("best_ask", price(best_ask)?)?;
+    let (mid_price, spread) = match (best_bid, best_ask) {
+        (Some(bid), Some(ask)) => {
+            // Halve exactly by moving to one more decimal place
+            let mid = bid
+                .checked_add(ask)
+                .and_then(|sum| sum.checked_mul(5))
+                .ok_or_else(|| PyValueError::new_err("mid price overflows"))?;
+            (
+                Some(book.price_kind.max(NumberKind::Float).to_py(
+                    py,
+                    mid,
+                    book.price_scale + 1,
+                )?),
+                price(Some(ask - bid))?,
+            )
+        }
+        _ => (None, None),
+    };
+    out.set_item("mid_price", mid_price)?;
+    out.set_item("spread", spread)?;
+    Ok(out.unbind().into_any())
+}
+
+/// Bid/ask volume imbalance over the best `depth` levels of each side of
+/// aggregated `{side, price, size}` entries: `{bid_volume, ask_volume,
+/// imbalance}` with `imbalance = (bid - ask) / (bid + ask)`, or 0.0 when both
+/// are empty. `cumulative_bids`/`cumulative_asks` list `(price, size up to and
+/// including that level)` best first, to find the price that fills a size.
+#[pyfunction]
+pub fn order_book_imbalance(
+    py: Python,
+    books: &Bound<'_, PyList>,
+    depth: usize,
+) -> PyResult<PyObject> {
+    let book = AggregatedBook::from_entries(books, false)?;
+    let (bids, asks): (Vec<_>, Vec<_>) = (
+        book.bids.iter().rev().take(depth).collect(),
+        book.asks.iter().take(depth).collect(),
+    );
+
+    let out = PyDict::new(py);
+    let mut volumes = [0i128; 2];
+    for ((key, levels), volume) in [("cumulative_bids", bids), ("cumulative_asks", asks)]
+        .into_iter()
+        .zip(volumes.iter_mut())
+    {
+        let list = PyList::empty(py);
+        for (&price, &size) in levels {
+            *volume = volume
+                .checked_add(size)
+                .ok_or_else(|| PyValueError::new_err("cumulative size overflows"))?;
+            list.append((book.price_py(py, price)?, book.size_py(py, *volume)?))?;
+        }
+        out.set_item(key, list)?;
+    }
+    let [bid_volume, ask_volume] = volumes;
+    let total = bid_volume as f64 + ask_volume as f64;
+    let imbalance = if total == 0.0 {
+        0.0
+    } else {
+        (bid_volume as f64 - ask_volume as f64) / total
+    };
+    out.set_item("bid_volume", book.size_py(py, bid_volume)?)?;
+    out.set_item("ask_volume", book.size_py(py, ask_volume)?)?;
+    out.set_item("imbalance", imbalance)?;
+    Ok(out.unbind().into_any())
+}
+
+type LevelSources = BTreeMap<i128, BTreeMap<Option<String>, i128>>;
+
+/// OHLCV candles from `{timestamp, price, size}` trades (timestamps in
+/// seconds, in any order). Each candle covers `[open_time, open_time +
+/// interval_secs)`; intervals without trades are skipped. Trades sharing a
+/// timestamp keep their input order for open/close.
+#[pyfunction]
+pub fn build_candles(
+    py: Python,
+    trades: &Bound<'_, PyList>,
+    interval_secs: u64,
+) -> PyResult<PyObject> {
+    if interval_secs == 0 {
+        return Err(PyValueError::new_err("interval_secs must be positive"));
+    }
+    let mut parsed: Vec<(f64, f64, f64)> = Vec::with_capacity(trades.len());
+    for any in trades.iter() {
         let d: &Bound<PyDict> = any.downcast()?;
-        let side: String = d
-            .get_item("side")?
-            .ok_or_else(|| PyKeyError::new_err("missing 'side'"))?
-            .extract()?;
-        let price: i64 = d
-            .get_item("price")?
-            .ok_or_else(|| PyKeyError::new_err("missing 'price'"))?
-            .extract()?;
-        let size: i64 = d
-            .get_item("size")?
-            .ok_or_else(|| PyKeyError::new_err("missing 'size'"))?
-            .extract()?;
-        match side.as_str() {
-            "bid" => bids.push((price, size)),
-            "ask" => asks.push((price, size)),
-            _ => return Err(PyTypeError::new_err("side must be 'bid' or 'ask'")),
-        }
-    }
-    // simple aggregate by price
-    use std::collections::BTreeMap;
-    let mut bid_map = BTreeMap::new();
-    let mut ask_map = BTreeMap::new();
-    for (p, s) in bids {
-        *bid_map.entry(p).or_insert(0) += s;
-    }
-    for (p, s) in asks {
-        *ask_map.entry(p).or_insert(0) += s;
+        let field = |key: &str| -> PyResult<f64> {
+            d.get_item(key)?
+                .ok_or_else(|| PyKeyError::new_err(format!("missing '{key}'")))?
+                .extract()
+        };
+        let (timestamp, price, size) = (field("timestamp")?, field("price")?, field("size")?);
+        if !timestamp.is_finite() {
+            return Err(PyValueError::new_err("timestamp must be finite"));
+        }
+        parsed.push((timestamp, price, size));
+    }
+    parsed.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let interval = interval_secs as f64;
+    let candles = PyList::empty(py);
+    let mut current: Option<(i64, f64, f64, f64, f64, f64)> = None;
+    let flush = |candle: (i64, f64, f64, f64, f64, f64)| -> PyResult<()> {
+        let (open_time, open, high, low, close, volume) = candle;
+        let out = PyDict::new(py);
+        out.set_item("open_time", open_time)?;
+        out.set_item("open", open)?;
+        out.set_item("high", high)?;
+        out.set_item("low", low)?;
+        out.set_item("close", close)?;
+        out.set_item("volume", volume)?;
+        candles.append(out)
+    };
+    for (timestamp, price, size) in parsed {
+        let open_time = ((timestamp / interval).floor() * interval) as i64;
+        match current.as_mut() {
+            Some(candle) if candle.0 == open_time => {
+                candle.2 = candle.2.max(price);
+                candle.3 = candle.3.min(price);
+                candle.4 = price;
+                candle.5 += size;
+            }
+            _ => {
+                if let Some(done) = current.take() {
+                    flush(done)?;
+                }
+                current = Some((open_time, price, price, price, price, size));
+            }
+        }
+    }
+    if let Some(done) = current {
+        flush(done)?;
+    }
+    Ok(candles.unbind().into_any())
+}
+
+/// `{side, price, size}` entries summed per price level, as exact fixed-point
+/// integers on one scale per field.
+struct AggregatedBook {
+    bids: BTreeMap<i128, i128>,
+    asks: BTreeMap<i128, i128>,
+    // Size per `source` at each level (None for untagged entries), kept only
+    // when some entry names a source
+    sources: Option<[LevelSources; 2]>,
+    price_kind: NumberKind,
+    size_kind: NumberKind,
+    price_scale: u32,
+    size_scale: u32,
+}
+
+impl AggregatedBook {
+    /// Zero sizes are dropped. Negative sizes raise with the entry's index
+    /// unless `allow_negative`, where they are deltas and levels netting to
+    /// zero or below are removed.
+    fn from_entries(books: &Bound<'_, PyList>, allow_negative: bool) -> PyResult<Self> {
+        let mut entries: Vec<(Side, Fixed, Fixed, Option<String>)> = Vec::new();
+        let (mut price_kind, mut size_kind) = (NumberKind::Int, NumberKind::Int);
+
+        for (index, any) in books.iter().enumerate() {
+            let d: &Bound<PyDict> = any.downcast()?;
+            let side: String = d
+                .get_item("side")?
+                .ok_or_else(|| PyKeyError::new_err("missing 'side'"))?
+                .extract()?;
+            let (price, kind) = Fixed::extract(
+                &d.get_item("price")?
+                    .ok_or_else(|| PyKeyError::new_err("missing 'price'"))?,
+            )?;
+            price_kind = price_kind.max(kind);
+            let (size, kind) = Fixed::extract(
+                &d.get_item("size")?
+                    .ok_or_else(|| PyKeyError::new_err("missing 'size'"))?,
+            )?;
+            size_kind = size_kind.max(kind);
+            if size.mantissa < 0 && !allow_negative {
+                return Err(PyValueError::new_err(format!(
+                    "entry {index} has negative size {size}"
+                )));
+            }
+            if size.mantissa == 0 {
+                continue;
+            }
+            let source: Option<String> = match d.get_item("source")? {
+                Some(source) if !source.is_none() => Some(source.extract()?),
+                _ => None,
+            };
+            entries.push((Side::parse(&side)?, price, size, source));
+        }
+        let mut sources = entries
+            .iter()
+            .any(|e| e.3.is_some())
+            .then(<[LevelSources; 2]>::default);
+        // Aggregate on a common scale per field, keyed by the scaled integer price
+        let price_scale = entries.iter().map(|e| e.1.scale).max().unwrap_or(0);
+        let size_scale = entries.iter().map(|e| e.2.scale).max().unwrap_or(0);
+        let mut bid_map: BTreeMap<i128, i128> = BTreeMap::new();
+        let mut ask_map: BTreeMap<i128, i128> = BTreeMap::new();
+        for (side, price, size, source) in entries {
+            let levels = match side {
+                Side::Bid => &mut bid_map,
+                Side::Ask => &mut ask_map,
+            };
+            let (price, size) = (price.rescaled(price_scale)?, size.rescaled(size_scale)?);
+            let overflow = || PyValueError::new_err("aggregated size overflows");
+            let level = levels.entry(price).or_insert(0);
+            *level = level.checked_add(size).ok_or_else(overflow)?;
+            if let Some(sources) = sources.as_mut() {
+                let by_source = sources[side as usize].entry(price).or_default();
+                let contributed = by_source.entry(source).or_insert(0);
+                *contributed = contributed.checked_add(size).ok_or_else(overflow)?;
+            }
+        }
+        if allow_negative {
+            for (side, levels) in [(Side::Bid, &mut bid_map), (Side::Ask, &mut ask_map)] {
+                levels.retain(|&price, &mut size| {
+                    let keep = size > 0;
+                    if let (false, Some(sources)) = (keep, sources.as_mut()) {
+                        sources[side as usize].remove(&price);
+                    }
+                    keep
+                });
+            }
+        }
+
+        Ok(Self {
+            bids: bid_map,
+            asks: ask_map,
+            sources,
+            price_kind,
+            size_kind,
+            price_scale,
+            size_scale,
+        })
+    }
+
+    fn price_py<'py>(&self, py: Python<'py>, price: i128) -> PyResult<Bound<'py, PyAny>> {
+        self.price_kind.to_py(py, price, self.price_scale)
+    }
+
+    fn size_py<'py>(&self, py: Python<'py>, size: i128) -> PyResult<Bound<'py, PyAny>> {
+        self.size_kind.to_py(py, size, self.size_scale)
+    }
+}
+
+/// Size-weighted average price of scaled `price -> size` levels; `None`
+/// without size.
+fn vwap(levels: &BTreeMap<i128, i128>, price_scale: u32) -> Option<f64> {
+    let size: i128 = levels.values().sum();
+    if size == 0 {
+        return None;
+    }
+    let notional: f64 = levels.iter().map(|(&p, &s)| p as f64 * s as f64).sum();
+    Some(notional / size as f64 / 10f64.powi(price_scale as i32))
+}
+
+// Finer inputs are rejected rather than rounded
+const MAX_BOOK_DECIMALS: u32 = 18;
+
+/// Input form of a book number, ordered so the widest one decides the output.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum NumberKind {
+    Int,
+    Float,
+    Decimal,
+}
+
+impl NumberKind {
+    fn to_py<'py>(
+        self,
+        py: Python<'py>,
+        mantissa: i128,
+        scale: u32,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let text = Fixed { mantissa, scale }.to_string();
+        match self {
+            NumberKind::Int => Ok(mantissa.into_pyobject(py)?.into_any()),
+            NumberKind::Float => Ok(text
+                .parse::<f64>()
+                .expect("formatted decimal")
+                .into_pyobject(py)?
+                .into_any()),
+            NumberKind::Decimal => Ok(text.into_pyobject(py)?.into_any()),
+        }
+    }
+}
+
+/// Exact decimal `mantissa * 10^-scale`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Fixed {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Fixed {
+    fn extract(value: &Bound<'_, PyAny>) -> PyResult<(Self, NumberKind)> {
+        if let Ok(int) = value.extract::<i128>() {
+            return Ok((
+                Self {
+                    mantissa: int,
+                    scale: 0,
+                },
+                NumberKind::Int,
+            ));
+        }
+        // Float repr is the shortest string that round-trips, e.g. "0.015"
+        let kind = if value.is_instance_of::<PyFloat>() {
+            NumberKind::Float
+        } else {
+            NumberKind::Decimal
+        };
+        let text = value.str()?.to_string();
+        Ok((text.parse().map_err(PyValueError::new_err)?, kind))
+    }
+
+    fn rescaled(self, scale: u32) -> PyResult<i128> {
+        10i128
+            .checked_pow(scale - self.scale)
+            .and_then(|factor| self.mantissa.checked_mul(factor))
+            .ok_or_else(|| PyValueError::new_err(format!("{self} overflows at {scale} decimals")))
+    }
+}
+
+impl std::str::FromStr for Fixed {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid decimal number {text:?}");
+        let trimmed = text.trim();
+        let (number, exponent) = match trimmed.find(['e', 'E']) {
+            Some(i) => (
+                &trimmed[..i],
+                trimmed[i + 1..].parse::<i32>().map_err(|_| invalid())?,
+            ),
+            None => (trimmed, 0),
+        };
+        let (negative, number) = match number.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, number.strip_prefix('+').unwrap_or(number)),
+        };
+        let (int_part, frac_part) = number.split_once('.').unwrap_or((number, ""));
+        let digits = format!("{int_part}{frac_part}");
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let mut mantissa: i128 = digits
+            .parse()
+            .map_err(|_| format!("{text:?} is too large"))?;
+        let mut scale = frac_part.len() as i64 - exponent as i64;
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        if scale < 0 {
+            mantissa = u32::try_from(-scale)
+                .ok()
+                .and_then(|exp| 10i128.checked_pow(exp))
+                .and_then(|factor| mantissa.checked_mul(factor))
+                .ok_or_else(|| format!("{text:?} is too large"))?;
+            scale = 0;
+        }
+        if scale > MAX_BOOK_DECIMALS as i64 {
+            return Err(format!(
+                "{text:?} has more than {MAX_BOOK_DECIMALS} decimal places"
+            ));
+        }
+        Ok(Self {
+            mantissa: if negative { -mantissa } else { mantissa },
+            scale: scale as u32,
+        })
+    }
+}
+
+impl std::fmt::Display for Fixed {
+    /// Plain decimal without trailing zeros, e.g. `0.0151`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = format!(
+            "{:0>width$}",
+            self.mantissa.unsigned_abs(),
+            width = self.scale as usize + 1
+        );
+        let (int_part, frac_part) = digits.split_at(digits.len() - self.scale as usize);
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        match frac_part.trim_end_matches('0') {
+            "" => write!(f, "{sign}{int_part}"),
+            frac => write!(f, "{sign}{int_part}.{frac}"),
+        }
+    }
+}
+
+/// Track one price level across sequential snapshots and flag iceberg-like
+/// replenishment: the level is drawn down and then refilled repeatedly.
+#[pyfunction]
+#[pyo3(signature = (snapshots, price, min_refills=3))]
+pub fn detect_icebergs(
+    py: Python,
+    snapshots: &Bound<'_, PyList>,
+    price: i64,
+    min_refills: usize,
+) -> PyResult<PyObject> {
+    let mut sizes: Vec<i64> = Vec::new();
+    for any in snapshots.iter() {
+        let d: &Bound<PyDict> = any.downcast()?;
+        let mut size = 0i64;
+        for side in ["bids", "asks"] {
+            if let Some(levels) = d.get_item(side)? {
+                for (p, s) in levels.extract::<Vec<(i64, i64)>>()? {
+                    if p == price {
+                        size = size
+                            .checked_add(s)
+                            .ok_or_else(|| PyValueError::new_err("size at price overflows"))?;
+                    }
+                }
+            }
+        }
+        sizes.push(size);
+    }
+
+    // A refill is an increase that follows at least one decrease
+    let mut refills = 0usize;
+    let mut depleted = false;
+    for w in sizes.windows(2) {
+        if w[1] < w[0] {
+            depleted = true;
+        } else if w[1] > w[0] && depleted {
+            refills += 1;
+            depleted = false;
+        }
     }
 
     let out = PyDict::new(py);
-    let bids_vec: Vec<(i64, i64)> = bid_map.into_iter().collect();
-    let asks_vec: Vec<(i64, i64)> = ask_map.into_iter().collect();
-    let bids_list = PyList::new(py, &bids_vec)?;
-    let asks_list = PyList::new(py, &asks_vec)?;
-    out.set_item("bids", bids_list)?;
-    out.set_item("asks", asks_list)?;
+    out.set_item("price", price)?;
+    out.set_item("refills", refills)?;
+    out.set_item("is_iceberg", refills >= min_refills)?;
+    out.set_item("sizes", PyList::new(py, &sizes)?)?;
     Ok(out.unbind().into_any())
 }
 
+/// Annualized realized volatility of `(timestamp_ms, mid)` samples within the
+/// last `window_ms`: `sqrt(annualization_factor * mean(log_return^2))`, where the
+/// factor is the number of sampling intervals per year. `None` when the window
+/// holds fewer than two samples.
+#[pyfunction]
+pub fn realized_vol(
+    mut mids: Vec<(i64, f64)>,
+    window_ms: i64,
+    annualization_factor: f64,
+) -> PyResult<Option<f64>> {
+    if mids.iter().any(|&(_, mid)| mid <= 0.0) {
+        return Err(PyValueError::new_err("mids must be positive"));
+    }
+    mids.sort_by_key(|&(ts, _)| ts);
+    let Some(&(last_ts, _)) = mids.last() else {
+        return Ok(None);
+    };
+    let window: Vec<f64> = mids
+        .iter()
+        .filter(|&&(ts, _)| ts >= last_ts - window_ms)
+        .map(|&(_, mid)| mid)
+        .collect();
+    if window.len() < 2 {
+        return Ok(None);
+    }
+    let squared: Vec<f64> = window
+        .windows(2)
+        .map(|w| (w[1] / w[0]).ln().powi(2))
+        .collect();
+    let variance = squared.iter().sum::<f64>() / squared.len() as f64;
+    Ok(Some((variance * annualization_factor).sqrt()))
+}
+
+/// Pearson correlation of two `(timestamp_ms, mid)` series. Each sample of
+/// `mids_a` is paired with the nearest-timestamp sample of `mids_b` if that
+/// lies within `tolerance_ms`. `None` with fewer than two pairs or when either
+/// side is flat.
+#[pyfunction]
+#[pyo3(signature = (mids_a, mids_b, tolerance_ms=0))]
+pub fn mid_correlation(
+    mids_a: Vec<(i64, f64)>,
+    mut mids_b: Vec<(i64, f64)>,
+    tolerance_ms: i64,
+) -> PyResult<Option<f64>> {
+    if tolerance_ms < 0 {
+        return Err(PyValueError::new_err("tolerance_ms must be non-negative"));
+    }
+    mids_b.sort_by_key(|&(ts, _)| ts);
+    let pairs: Vec<(f64, f64)> = mids_a
+        .iter()
+        .filter_map(|&(ts, a)| {
+            let idx = mids_b.partition_point(|&(t, _)| t < ts);
+            let nearest = [idx.checked_sub(1), Some(idx)]
+                .into_iter()
+                .flatten()
+                .filter_map(|i| mids_b.get(i))
+                .min_by_key(|&&(t, _)| (t - ts).abs())?;
+            ((nearest.0 - ts).abs() <= tolerance_ms).then_some((a, nearest.1))
+        })
+        .collect();
+    if pairs.len() < 2 {
+        return Ok(None);
+    }
+    let n = pairs.len() as f64;
+    let mean_a = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_b = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for &(a, b) in &pairs {
+        cov += (a - mean_a) * (b - mean_b);
+        var_a += (a - mean_a).powi(2);
+        var_b += (b - mean_b).powi(2);
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        return Ok(None);
+    }
+    Ok(Some(cov / (var_a * var_b).sqrt()))
+}
+
+/// Probability that a resting order at `price` fills within `horizon`, using a
+/// queue-position model: size queued ahead of the order is consumed by opposing
+/// flow arriving at `arrival_rate` (size per unit time), giving
+/// `exp(-size_ahead / (arrival_rate * horizon))`. Marketable prices fill at 1.0.
+#[pyfunction]
+#[pyo3(signature = (book, side, price, arrival_rate, horizon=1.0))]
+pub fn fill_probability(
+    book: &Bound<'_, PyDict>,
+    side: &str,
+    price: i64,
+    arrival_rate: f64,
+    horizon: f64,
+) -> PyResult<f64> {
+    let side = Side::parse(side)?;
+    if arrival_rate < 0.0 || horizon < 0.0 {
+        return Err(PyValueError::new_err(
+            "arrival_rate and horizon must be non-negative",
+        ));
+    }
+    let levels = |key: &str| -> PyResult<Vec<(i64, i64)>> {
+        match book.get_item(key)? {
+            Some(v) => v.extract(),
+            None => Ok(Vec::new()),
+        }
+    };
+    let (own, opposite) = match side {
+        Side::Bid => (levels("bids")?, levels("asks")?),
+        Side::Ask => (levels("asks")?, levels("bids")?),
+    };
+    let at_or_better = |p: i64| match side {
+        Side::Bid => p >= price,
+        Side::Ask => p <= price,
+    };
+    let crosses = |p: i64| match side {
+        Side::Bid => p <= price,
+        Side::Ask => p >= price,
+    };
+    if opposite.iter().any(|&(p, _)| crosses(p)) {
+        return Ok(1.0);
+    }
+    // Orders at the same price were there first, so they queue ahead too
+    let size_ahead = own
+        .iter()
+        .filter(|&&(p, _)| at_or_better(p))
+        .try_fold(0i64, |sum, &(_, s)| sum.checked_add(s))
+        .ok_or_else(|| PyValueError::new_err("size ahead overflows"))?;
+    if size_ahead <= 0 {
+        return Ok(1.0);
+    }
+    let expected_flow = arrival_rate * horizon;
+    if expected_flow == 0.0 {
+        return Ok(0.0);
+    }
+    Ok((-(size_ahead as f64) / expected_flow).exp())
+}
+
+/// Volume-at-price histogram over recorded snapshots. Size from both sides is
+/// accumulated into `num_buckets` equal-width `[low, high)` price buckets; `poc`
+/// (point of control) is the single price with the most accumulated volume.
+#[pyfunction]
+pub fn volume_profile(
+    py: Python,
+    snapshots: &Bound<'_, PyList>,
+    num_buckets: usize,
+) -> PyResult<PyObject> {
+    if num_buckets == 0 {
+        return Err(PyValueError::new_err("num_buckets must be positive"));
+    }
+    let volume_overflow = || PyValueError::new_err("accumulated volume overflows");
+    let mut by_price: BTreeMap<i64, i64> = BTreeMap::new();
+    for any in snapshots.iter() {
+        let d: &Bound<PyDict> = any.downcast()?;
+        for side in ["bids", "asks"] {
+            if let Some(levels) = d.get_item(side)? {
+                for (p, s) in levels.extract::<Vec<(i64, i64)>>()? {
+                    let volume = by_price.entry(p).or_insert(0);
+                    *volume = volume.checked_add(s).ok_or_else(volume_overflow)?;
+                }
+            }
+        }
+    }
+
+    let out = PyDict::new(py);
+    let (Some((&min, _)), Some((&max, _))) =
+        (by_price.first_key_value(), by_price.last_key_value())
+    else {
+        out.set_item("buckets", PyList::empty(py))?;
+        out.set_item("poc", py.None())?;
+        return Ok(out.unbind().into_any());
+    };
+    let range_overflow = || PyValueError::new_err("price bucket bounds overflow");
+    let span = max.checked_sub(min).ok_or_else(range_overflow)?;
+    let width = (span / num_buckets as i64 + 1).max(1);
+    // Bucket `i` starts at `min + i * width`; there are `num_buckets + 1` bounds
+    let bound = |i: i64| {
+        i.checked_mul(width)
+            .and_then(|offset| min.checked_add(offset))
+            .ok_or_else(range_overflow)
+    };
+    let mut buckets: Vec<(i64, i64, i64)> = (0..num_buckets as i64)
+        .map(|i| Ok((bound(i)?, bound(i + 1)?, 0)))
+        .collect::<PyResult<_>>()?;
+    for (&p, &s) in &by_price {
+        let idx = (((p - min) / width) as usize).min(num_buckets - 1);
+        let volume = &mut buckets[idx].2;
+        *volume = volume.checked_add(s).ok_or_else(volume_overflow)?;
+    }
+    // Highest volume wins; ties go to the lower price
+    let poc = by_price
+        .iter()
+        .max_by_key(|&(p, s)| (*s, std::cmp::Reverse(*p)))
+        .map(|(p, _)| *p);
+
+    out.set_item("buckets", PyList::new(py, &buckets)?)?;
+    out.set_item("poc", poc)?;
+    Ok(out.unbind().into_any())
+}
+
+/// Kyle's lambda: OLS slope of mid-price changes on signed order flow across
+/// sequential snapshots. Each snapshot carries `bids`, `asks` and the
+/// `signed_volume` (buys positive) traded since the previous one; the first
+/// snapshot's flow is ignored. `None` when fewer than two changes are usable or
+/// the flow never varies.
+#[pyfunction]
+pub fn kyle_lambda(snapshots: &Bound<'_, PyList>) -> PyResult<Option<f64>> {
+    let mut samples: Vec<(Option<f64>, f64)> = Vec::new();
+    for any in snapshots.iter() {
+        let d: &Bound<PyDict> = any.downcast()?;
+        let best = |key: &str, bid: bool| -> PyResult<Option<i64>> {
+            let levels: Vec<(i64, i64)> = match d.get_item(key)? {
+                Some(v) => v.extract()?,
+                None => Vec::new(),
+            };
+            let prices = levels.into_iter().map(|(p, _)| p);
+            Ok(if bid { prices.max() } else { prices.min() })
+        };
+        let mid = match (best("bids", true)?, best("asks", false)?) {
+            // Widened so prices near i64::MAX cannot overflow the sum
+            (Some(bid), Some(ask)) => Some((i128::from(bid) + i128::from(ask)) as f64 / 2.0),
+            _ => None,
+        };
+        let flow: f64 = match d.get_item("signed_volume")? {
+            Some(v) => v.extract()?,
+            None => 0.0,
+        };
+        samples.push((mid, flow));
+    }
+
+    let points: Vec<(f64, f64)> = samples
+        .windows(2)
+        .filter_map(|w| Some((w[1].1, w[1].0? - w[0].0?)))
+        .collect();
+    if points.len() < 2 {
+        return Ok(None);
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let cov: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let var: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if var == 0.0 {
+        return Ok(None);
+    }
+    Ok(Some(cov / var))
+}
+
+/// Implied A/C book from an A/B and a B/C book (`bids`/`asks` of
+/// `(price, size)`, sizes in the base token). Selling A walks the A/B bids then
+/// the B/C bids, buying walks both asks; each composed level is priced at the
+/// product of the two legs and sized (in A) by whichever leg runs out first.
+#[pyfunction]
+pub fn synthetic_cross(
+    py: Python,
+    book_ab: &Bound<'_, PyDict>,
+    book_bc: &Bound<'_, PyDict>,
+) -> PyResult<PyObject> {
+    let levels =
+        |book: &Bound<'_, PyDict>, key: &str, descending: bool| -> PyResult<Vec<(f64, f64)>> {
+            let mut levels: Vec<(f64, f64)> = match book.get_item(key)? {
+                Some(v) => v.extract()?,
+                None => Vec::new(),
+            };
+            if levels.iter().any(|&(p, s)| p <= 0.0 || s < 0.0) {
+                return Err(PyValueError::new_err(
+                    "levels need positive prices and non-negative sizes",
+                ));
+            }
+            levels.sort_by(|a, b| a.0.total_cmp(&b.0));
+            if descending {
+                levels.reverse();
+            }
+            Ok(levels)
+        };
+    let bids = compose_levels(
+        &levels(book_ab, "bids", true)?,
+        &levels(book_bc, "bids", true)?,
+    );
+    let asks = compose_levels(
+        &levels(book_ab, "asks", false)?,
+        &levels(book_bc, "asks", false)?,
+    );
+
+    let out = PyDict::new(py);
+    out.set_item("bids", PyList::new(py, &bids)?)?;
+    out.set_item("asks", PyList::new(py, &asks)?)?;
+    Ok(out.unbind().into_any())
+}
+
+/// Walk two best-first legs in lockstep. `ab` sizes are in A, `bc` sizes in B.
+fn compose_levels(ab: &[(f64, f64)], bc: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    const EPS: f64 = 1e-12;
+    let mut out: Vec<(f64, f64)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut rem_a = ab.first().map_or(0.0, |l| l.1);
+    let mut rem_b = bc.first().map_or(0.0, |l| l.1);
+    while i < ab.len() && j < bc.len() {
+        let (p_ab, p_bc) = (ab[i].0, bc[j].0);
+        let fill_a = rem_a.min(rem_b / p_ab);
+        if fill_a > EPS {
+            let price = p_ab * p_bc;
+            match out.last_mut() {
+                Some(last) if last.0 == price => last.1 += fill_a,
+                _ => out.push((price, fill_a)),
+            }
+        }
+        rem_a -= fill_a;
+        rem_b -= fill_a * p_ab;
+        if rem_a <= EPS {
+            i += 1;
+            rem_a = ab.get(i).map_or(0.0, |l| l.1);
+        }
+        if rem_b <= EPS {
+            j += 1;
+            rem_b = bc.get(j).map_or(0.0, |l| l.1);
+        }
+    }
+    out
+}
+
+/// Market-making quotes around the micro-price (top-of-book mid weighted by the
+/// opposite side's size). The band keeps the current half spread and is
+/// shifted by `-inventory * risk_aversion`, so a long book quotes lower to
+/// sell and a short book higher to buy. Returns
+/// `{"fair", "reservation", "bid", "ask"}`, or `None` for a one-sided book.
+#[pyfunction]
+pub fn quote_band(
+    py: Python,
+    book: &Bound<'_, PyDict>,
+    inventory: f64,
+    risk_aversion: f64,
+) -> PyResult<PyObject> {
+    if risk_aversion < 0.0 {
+        return Err(PyValueError::new_err("risk_aversion must be non-negative"));
+    }
+    let levels = |key: &str| -> PyResult<Vec<(i64, i64)>> {
+        match book.get_item(key)? {
+            Some(v) => v.extract(),
+            None => Ok(Vec::new()),
+        }
+    };
+    let best_bid = levels("bids")?.into_iter().max_by_key(|&(p, _)| p);
+    let best_ask = levels("asks")?.into_iter().min_by_key(|&(p, _)| p);
+    let (Some((bid, bid_size)), Some((ask, ask_size))) = (best_bid, best_ask) else {
+        return Ok(py.None());
+    };
+    let (bid, ask) = (bid as f64, ask as f64);
+    let depth = (i128::from(bid_size) + i128::from(ask_size)) as f64;
+    let fair = if depth > 0.0 {
+        (bid * ask_size as f64 + ask * bid_size as f64) / depth
+    } else {
+        (bid + ask) / 2.0
+    };
+    let half_spread = (ask - bid).max(0.0) / 2.0;
+    let reservation = fair - inventory * risk_aversion;
+
+    let out = PyDict::new(py);
+    out.set_item("fair", fair)?;
+    out.set_item("reservation", reservation)?;
+    out.set_item("bid", reservation - half_spread)?;
+    out.set_item("ask", reservation + half_spread)?;
+    Ok(out.unbind().into_any())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Bid,
+    Ask,
+}
+
+impl Side {
+    fn parse(side: &str) -> PyResult<Self> {
+        match side {
+            "bid" => Ok(Side::Bid),
+            "ask" => Ok(Side::Ask),
+            _ => Err(PyTypeError::new_err("side must be 'bid' or 'ask'")),
+        }
+    }
+}
+
+struct L3Order {
+    side: Side,
+    price: i64,
+    size: i64,
+}
+
+/// Order-level (L3) book that tracks individual orders by id and collapses
+/// them into price levels on demand.
+#[pyclass]
+#[derive(Default)]
+pub struct L3Book {
+    orders: HashMap<String, L3Order>,
+    bids: BTreeMap<i64, i64>, // price -> total size
+    asks: BTreeMap<i64, i64>,
+}
+
+impl L3Book {
+    fn levels(&mut self, side: Side) -> &mut BTreeMap<i64, i64> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+
+    /// Add `delta` to the level's total; the book is left unchanged if the
+    /// total would overflow.
+    fn adjust_level(&mut self, side: Side, price: i64, delta: i64) -> PyResult<()> {
+        let levels = self.levels(side);
+        let size = levels
+            .get(&price)
+            .copied()
+            .unwrap_or(0)
+            .checked_add(delta)
+            .ok_or_else(|| PyValueError::new_err(format!("size at price {price} overflows")))?;
+        if size > 0 {
+            levels.insert(price, size);
+        } else {
+            levels.remove(&price);
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl L3Book {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn add_order(&mut self, id: String, side: &str, price: i64, size: i64) -> PyResult<()> {
+        let side = Side::parse(side)?;
+        if size <= 0 {
+            return Err(PyValueError::new_err("order size must be positive"));
+        }
+        if self.orders.contains_key(&id) {
+            return Err(PyValueError::new_err(format!(
+                "order '{id}' already exists"
+            )));
+        }
+        self.adjust_level(side, price, size)?;
+        self.orders.insert(id, L3Order { side, price, size });
+        Ok(())
+    }
+
+    fn cancel_order(&mut self, id: &str) -> PyResult<()> {
+        let order = self
+            .orders
+            .remove(id)
+            .ok_or_else(|| PyKeyError::new_err(format!("unknown order '{id}'")))?;
+        self.adjust_level(order.side, order.price, -order.size)
+    }
+
+    /// Change an order's remaining size; a size of zero cancels it.
+    fn modify_order(&mut self, id: &str, size: i64) -> PyResult<()> {
+        if size < 0 {
+            return Err(PyValueError::new_err("order size must not be negative"));
+        }
+        if size == 0 {
+            return self.cancel_order(id);
+        }
+        let order = self
+            .orders
+            .get(id)
+            .ok_or_else(|| PyKeyError::new_err(format!("unknown order '{id}'")))?;
+        let (side, price, delta) = (order.side, order.price, size - order.size);
+        self.adjust_level(side, price, delta)?;
+        if let Some(order) = self.orders.get_mut(id) {
+            order.size = size;
+        }
+        Ok(())
+    }
+
+    /// Collapsed L2 view, best levels first, optionally limited to `depth` per side.
+    #[pyo3(signature = (depth=None))]
+    fn to_l2(&self, py: Python, depth: Option<usize>) -> PyResult<PyObject> {
+        let depth = depth.unwrap_or(usize::MAX);
+        let bids: Vec<(i64, i64)> = self
+            .bids
+            .iter()
+            .rev()
+            .take(depth)
+            .map(|(p, s)| (*p, *s))
+            .collect();
+        let asks: Vec<(i64, i64)> = self
+            .asks
+            .iter()
+            .take(depth)
+            .map(|(p, s)| (*p, *s))
+            .collect();
+        let out = PyDict::new(py);
+        out.set_item("bids", PyList::new(py, &bids)?)?;
+        out.set_item("asks", PyList::new(py, &asks)?)?;
+        Ok(out.unbind().into_any())
+    }
+
+    fn __len__(&self) -> usize {
+        self.orders.len()
+    }
+}
+
+/// L2 book replayed from timestamped level updates with simulated feed
+/// latency: an update received at `ts_ms` only shows in snapshots from
+/// `ts_ms + latency` on. `latency_ms` is either a fixed delay or a list of
+/// delays sampled uniformly (seeded, so replays are reproducible). Updates
+/// never overtake each other, as on a real sequenced feed.
+#[pyclass]
+pub struct OrderBookReplay {
+    latencies: Vec<u64>,
+    rng_state: u64,
+    bids: BTreeMap<i64, i64>,
+    asks: BTreeMap<i64, i64>,
+    // (visible_at_ms, side, price, size), in visibility order
+    pending: VecDeque<(i64, Side, i64, i64)>,
+    last_visible_ms: i64,
+}
+
+impl OrderBookReplay {
+    fn next_latency(&mut self) -> i64 {
+        if self.latencies.len() == 1 {
+            return self.latencies[0] as i64;
+        }
+        // splitmix64
+        self.rng_state = self.rng_state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        self.latencies[(z % self.latencies.len() as u64) as usize] as i64
+    }
+
+    fn apply_due(&mut self, now_ms: i64) {
+        while let Some(&(visible_at, side, price, size)) = self.pending.front() {
+            if visible_at > now_ms {
+                break;
+            }
+            self.pending.pop_front();
+            let levels = match side {
+                Side::Bid => &mut self.bids,
+                Side::Ask => &mut self.asks,
+            };
+            if size == 0 {
+                levels.remove(&price);
+            } else {
+                levels.insert(price, size);
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl OrderBookReplay {
+    #[new]
+    #[pyo3(signature = (latency_ms=None, seed=0))]
+    fn new(latency_ms: Option<&Bound<'_, PyAny>>, seed: u64) -> PyResult<Self> {
+        let latencies: Vec<u64> = match latency_ms {
+            None => vec![0],
+            Some(v) if v.is_instance_of::<PyList>() => v.extract()?,
+            Some(v) => vec![v.extract()?],
+        };
+        if latencies.is_empty() {
+            return Err(PyValueError::new_err(
+                "latency_ms samples must not be empty",
+            ));
+        }
+        Ok(Self {
+            latencies,
+            rng_state: seed,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            pending: VecDeque::new(),
+            last_visible_ms: i64::MIN,
+        })
+    }
+
+    /// Record a level update received at `ts_ms`; a size of zero removes the level.
+    fn apply_update(&mut self, ts_ms: i64, side: &str, price: i64, size: i64) -> PyResult<()> {
+        let side = Side::parse(side)?;
+        if size < 0 {
+            return Err(PyValueError::new_err("level size must not be negative"));
+        }
+        let visible_at = (ts_ms + self.next_latency()).max(self.last_visible_ms);
+        self.last_visible_ms = visible_at;
+        self.pending.push_back((visible_at, side, price, size));
+        Ok(())
+    }
+
+    /// Book as seen at `now_ms`, best levels first, optionally limited to `depth`.
+    #[pyo3(signature = (now_ms, depth=None))]
+    fn snapshot(&mut self, py: Python, now_ms: i64, depth: Option<usize>) -> PyResult<PyObject> {
+        self.apply_due(now_ms);
+        let depth = depth.unwrap_or(usize::MAX);
+        let bids: Vec<(i64, i64)> = self
+            .bids
+            .iter()
+            .rev()
+            .take(depth)
+            .map(|(p, s)| (*p, *s))
+            .collect();
+        let asks: Vec<(i64, i64)> = self
+            .asks
+            .iter()
+            .take(depth)
+            .map(|(p, s)| (*p, *s))
+            .collect();
+        let out = PyDict::new(py);
+        out.set_item("bids", PyList::new(py, &bids)?)?;
+        out.set_item("asks", PyList::new(py, &asks)?)?;
+        Ok(out.unbind().into_any())
+    }
+
+    /// Updates received but not yet visible.
+    fn pending_updates(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Aggregated L2 book maintained from incremental level updates, so a tick
+/// does not require re-aggregating the whole book. Safe to update from one
+/// thread while others read snapshots.
+#[pyclass]
+#[derive(Default)]
+pub struct OrderBook {
+    levels: RwLock<L2Levels>,
+}
+
+#[derive(Default)]
+struct L2Levels {
+    bids: BTreeMap<i64, i64>,
+    asks: BTreeMap<i64, i64>,
+}
+
+#[pymethods]
+impl OrderBook {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set each `{side, price, size}` level to `size`; a size of zero removes
+    /// the level. The batch is validated first and applied all at once.
+    fn apply_delta(&self, levels: &Bound<'_, PyList>) -> PyResult<()> {
+        let mut updates: Vec<(Side, i64, i64)> = Vec::with_capacity(levels.len());
+        for (index, any) in levels.iter().enumerate() {
+            let d: &Bound<PyDict> = any.downcast()?;
+            let field = |key: &str| {
+                d.get_item(key)?
+                    .ok_or_else(|| PyKeyError::new_err(format!("missing '{key}'")))
+            };
+            let side = Side::parse(&field("side")?.extract::<String>()?)?;
+            let (price, size): (i64, i64) = (field("price")?.extract()?, field("size")?.extract()?);
+            if size < 0 {
+                return Err(PyValueError::new_err(format!(
+                    "level {index} has negative size {size}"
+                )));
+            }
+            updates.push((side, price, size));
+        }
+        let mut book = self
+            .levels
+            .write()
+            .map_err(|_| PyRuntimeError::new_err("order book lock poisoned"))?;
+        for (side, price, size) in updates {
+            let levels = match side {
+                Side::Bid => &mut book.bids,
+                Side::Ask => &mut book.asks,
+            };
+            if size == 0 {
+                levels.remove(&price);
+            } else {
+                levels.insert(price, size);
+            }
+        }
+        Ok(())
+    }
+
+    /// Current book, best levels first, optionally limited to `depth` per side.
+    #[pyo3(signature = (depth=None))]
+    fn snapshot(&self, py: Python, depth: Option<usize>) -> PyResult<PyObject> {
+        let depth = depth.unwrap_or(usize::MAX);
+        let book = self
+            .levels
+            .read()
+            .map_err(|_| PyRuntimeError::new_err("order book lock poisoned"))?;
+        let bids: Vec<(i64, i64)> = book
+            .bids
+            .iter()
+            .rev()
+            .take(depth)
+            .map(|(p, s)| (*p, *s))
+            .collect();
+        let asks: Vec<(i64, i64)> = book
+            .asks
+            .iter()
+            .take(depth)
+            .map(|(p, s)| (*p, *s))
+            .collect();
+        drop(book);
+        let out = PyDict::new(py);
+        out.set_item("bids", PyList::new(py, &bids)?)?;
+        out.set_item("asks", PyList::new(py, &asks)?)?;
+        Ok(out.unbind().into_any())
+    }
+}
+
 pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(aggregate_order_books, m)?)?;
+    m.add_function(wrap_pyfunction!(order_book_imbalance, m)?)?;
+    m.add_function(wrap_pyfunction!(build_candles, m)?)?;
+    m.add_function(wrap_pyfunction!(detect_icebergs, m)?)?;
+    m.add_function(wrap_pyfunction!(realized_vol, m)?)?;
+    m.add_function(wrap_pyfunction!(mid_correlation, m)?)?;
+    m.add_function(wrap_pyfunction!(fill_probability, m)?)?;
+    m.add_function(wrap_pyfunction!(volume_profile, m)?)?;
+    m.add_function(wrap_pyfunction!(kyle_lambda, m)?)?;
+    m.add_function(wrap_pyfunction!(synthetic_cross, m)?)?;
+    m.add_function(wrap_pyfunction!(quote_band, m)?)?;
+    m.add_class::<L3Book>()?;
+    m.add_class::<OrderBookReplay>()?;
+    m.add_class::<OrderBook>()?;
     Ok(())
 }
@@ -0,0 +1,157 @@
+// metrics.rs
+//! Process-wide counters and timing histograms for the hot paths, readable
+//! from Python with `metrics_snapshot()`. Everything is lock-free atomics so
+//! recording never contends with routing.
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+pub(crate) static METRICS: Metrics = Metrics::new();
+
+pub(crate) struct Metrics {
+    /// Route searches actually run (cache hits excluded).
+    pub(crate) routes_computed: AtomicU64,
+    pub(crate) route_cache_hits: AtomicU64,
+    /// Pools parsed into an engine by `update_pools`/`upsert_pool`.
+    pub(crate) pools_loaded: AtomicU64,
+    /// Raw transactions successfully parsed into an envelope.
+    pub(crate) transactions_decoded: AtomicU64,
+    pub(crate) route_compute_time: Histogram,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            routes_computed: AtomicU64::new(0),
+            route_cache_hits: AtomicU64::new(0),
+            pools_loaded: AtomicU64::new(0),
+            transactions_decoded: AtomicU64::new(0),
+            route_compute_time: Histogram::new(),
+        }
+    }
+
+    /// Count one route search and record how long it took.
+    pub(crate) fn record_route(&self, elapsed: Duration) {
+        self.routes_computed.fetch_add(1, Ordering::Relaxed);
+        self.route_compute_time.record(elapsed);
+    }
+}
+
+// Exact below this many microseconds, then this many buckets per power of two
+// (so a reported quantile is within 12.5% of the true value)
+const SUB_BUCKETS: u64 = 8;
+const BUCKETS: usize = (64 - 2) * SUB_BUCKETS as usize;
+
+/// Log-linear histogram of durations in microseconds.
+pub(crate) struct Histogram {
+    buckets: [AtomicU64; BUCKETS],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+    max_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; BUCKETS],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+            max_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, elapsed: Duration) {
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        self.buckets[bucket_index(micros)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    /// Upper bound (in microseconds) of the bucket holding quantile `q`, or
+    /// `None` before anything was recorded.
+    fn quantile_micros(counts: &[u64], q: f64) -> Option<u64> {
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((q * total as f64).ceil() as u64).clamp(1, total);
+        let mut seen = 0;
+        for (index, &count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(bucket_upper_bound(index));
+            }
+        }
+        None
+    }
+
+    /// `{count, mean, p50, p99, max}` in milliseconds; the timing keys are
+    /// None while the count is 0.
+    fn to_py<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        let count = self.count.load(Ordering::Relaxed);
+        let ms = |micros: u64| micros as f64 / 1000.0;
+        let dict = PyDict::new(py);
+        dict.set_item("count", count)?;
+        dict.set_item(
+            "mean",
+            (count > 0).then(|| ms(self.sum_micros.load(Ordering::Relaxed)) / count as f64),
+        )?;
+        dict.set_item("p50", Self::quantile_micros(&counts, 0.50).map(ms))?;
+        dict.set_item("p99", Self::quantile_micros(&counts, 0.99).map(ms))?;
+        dict.set_item(
+            "max",
+            (count > 0).then(|| ms(self.max_micros.load(Ordering::Relaxed))),
+        )?;
+        Ok(dict)
+    }
+}
+
+fn bucket_index(micros: u64) -> usize {
+    if micros < SUB_BUCKETS {
+        return micros as usize;
+    }
+    let octave = 63 - u64::from(micros.leading_zeros());
+    let sub = (micros >> (octave - 3)) & (SUB_BUCKETS - 1);
+    ((octave - 2) * SUB_BUCKETS + sub) as usize
+}
+
+fn bucket_upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index + 1;
+    }
+    let (octave, sub) = (index / SUB_BUCKETS + 2, index % SUB_BUCKETS);
+    (SUB_BUCKETS + sub + 1).saturating_mul(1 << (octave - 3))
+}
+
+/// Counters since process start plus route computation timings, e.g. for a
+/// Prometheus exporter: `routes_computed`, `route_cache_hits`,
+/// `pools_loaded`, `transactions_decoded` and `route_compute_ms`
+/// (`{count, mean, p50, p99, max}`; quantiles are bucket upper bounds).
+#[pyfunction]
+pub fn metrics_snapshot(py: Python) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    let counters = [
+        ("routes_computed", &METRICS.routes_computed),
+        ("route_cache_hits", &METRICS.route_cache_hits),
+        ("pools_loaded", &METRICS.pools_loaded),
+        ("transactions_decoded", &METRICS.transactions_decoded),
+    ];
+    for (name, counter) in counters {
+        dict.set_item(name, counter.load(Ordering::Relaxed))?;
+    }
+    dict.set_item("route_compute_ms", METRICS.route_compute_time.to_py(py)?)?;
+    Ok(dict.unbind().into_any())
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(metrics_snapshot, m)?)?;
+    Ok(())
+}
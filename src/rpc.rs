@@ -0,0 +1,201 @@
+// rpc.rs
+//! Minimal HTTP/1.1 JSON-RPC client over `http://` or `https://`, one
+//! request per connection.
+use once_cell::sync::OnceCell;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+use crate::url::{parse_url, Endpoint};
+
+// Larger responses are treated as an error rather than buffered
+const MAX_RESPONSE_BYTES: usize = 16 << 20;
+
+static TLS_CONNECTOR: OnceCell<TlsConnector> = OnceCell::new();
+
+/// Call `method(params)` and return its `result`, or the JSON-RPC `error`
+/// object as `Err(RpcError::Rejected)`. Connecting and reading the response
+/// are each bounded by `time_limit`, so an unresponsive node fails the call
+/// rather than hanging it.
+pub(crate) async fn call(
+    url: &str,
+    method: &str,
+    params: Value,
+    time_limit: Duration,
+) -> Result<Value, RpcError> {
+    let request = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+    let body = post(url, request.to_string().as_bytes(), time_limit).await?;
+    let response: Value = serde_json::from_slice(&body)
+        .map_err(|e| RpcError::Transport(format!("invalid JSON-RPC response: {e}")))?;
+    if let Some(error) = response.get("error") {
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .map_or_else(|| error.to_string(), str::to_string);
+        return Err(RpcError::Rejected(message));
+    }
+    response
+        .get("result")
+        .cloned()
+        .ok_or_else(|| RpcError::Transport("response has neither result nor error".into()))
+}
+
+pub(crate) enum RpcError {
+    /// The node answered with a JSON-RPC error.
+    Rejected(String),
+    /// The request never got a JSON-RPC answer.
+    Transport(String),
+}
+
+async fn post(url: &str, body: &[u8], time_limit: Duration) -> Result<Vec<u8>, RpcError> {
+    let transport = RpcError::Transport;
+    let Endpoint {
+        tls,
+        host,
+        port,
+        path,
+    } = parse_endpoint(url).map_err(transport)?;
+    let stream = timeout(time_limit, TcpStream::connect((host.as_str(), port)))
+        .await
+        .map_err(|_| transport(format!("connect to {host}:{port} timed out")))?
+        .map_err(|e| transport(format!("connect to {host}:{port} failed: {e}")))?;
+    let head = format!(
+        "POST {path} HTTP/1.1\r\nHost: {host}:{port}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    if !tls {
+        return exchange(stream, &head, body, time_limit).await;
+    }
+    let server_name = ServerName::try_from(host.clone())
+        .map_err(|e| transport(format!("invalid TLS server name {host:?}: {e}")))?;
+    // The handshake gets its own `time_limit`, like connecting
+    let stream = timeout(time_limit, tls_connector().connect(server_name, stream))
+        .await
+        .map_err(|_| transport(format!("TLS handshake with {host}:{port} timed out")))?
+        .map_err(|e| transport(format!("TLS handshake with {host}:{port} failed: {e}")))?;
+    exchange(stream, &head, body, time_limit).await
+}
+
+/// Client config trusting the bundled Mozilla roots, built on first use.
+fn tls_connector() -> &'static TlsConnector {
+    TLS_CONNECTOR.get_or_init(|| {
+        let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .expect("ring supports the default protocol versions")
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    })
+}
+
+/// Send the request over an open connection and read the response body.
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    head: &str,
+    body: &[u8],
+    time_limit: Duration,
+) -> Result<Vec<u8>, RpcError> {
+    let transport = RpcError::Transport;
+    let write_err = |e: std::io::Error| transport(format!("write failed: {e}"));
+    stream.write_all(head.as_bytes()).await.map_err(write_err)?;
+    stream.write_all(body).await.map_err(write_err)?;
+    stream.flush().await.map_err(write_err)?;
+
+    timeout(time_limit, read_response(BufReader::new(stream)))
+        .await
+        .map_err(|_| transport("timed out waiting for the response".into()))?
+}
+
+async fn read_response<S: AsyncRead + Unpin>(
+    mut reader: BufReader<S>,
+) -> Result<Vec<u8>, RpcError> {
+    let transport = RpcError::Transport;
+    let read_err = |e: std::io::Error| transport(format!("read failed: {e}"));
+    let mut status = String::new();
+    reader.read_line(&mut status).await.map_err(read_err)?;
+    let code = status
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+    let (mut content_length, mut chunked) = (None, false);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.map_err(read_err)? == 0 {
+            return Err(transport("connection closed inside the headers".into()));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let (name, value) = (name.trim(), value.trim());
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse::<usize>().ok();
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            }
+        }
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            let mut size_line = String::new();
+            reader.read_line(&mut size_line).await.map_err(read_err)?;
+            let size_hex = size_line.trim().split(';').next().unwrap_or_default();
+            let size = usize::from_str_radix(size_hex, 16)
+                .map_err(|_| transport(format!("invalid chunk size {size_hex:?}")))?;
+            if size == 0 {
+                break;
+            }
+            if body
+                .len()
+                .checked_add(size)
+                .is_none_or(|total| total > MAX_RESPONSE_BYTES)
+            {
+                return Err(transport("response exceeds the size limit".into()));
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader
+                .read_exact(&mut body[start..])
+                .await
+                .map_err(read_err)?;
+            let mut crlf = [0u8; 2];
+            reader.read_exact(&mut crlf).await.map_err(read_err)?;
+        }
+    } else if let Some(length) = content_length {
+        if length > MAX_RESPONSE_BYTES {
+            return Err(transport("response exceeds the size limit".into()));
+        }
+        body.resize(length, 0);
+        reader.read_exact(&mut body).await.map_err(read_err)?;
+    } else {
+        reader
+            .take(MAX_RESPONSE_BYTES as u64)
+            .read_to_end(&mut body)
+            .await
+            .map_err(read_err)?;
+    }
+    // Nodes report JSON-RPC errors with 200, but some proxies use 4xx/5xx
+    // with a JSON-RPC body; only fail outright when there is nothing to parse
+    if !code.starts_with('2') && serde_json::from_slice::<Value>(&body).is_err() {
+        return Err(transport(format!("HTTP {}", status.trim())));
+    }
+    Ok(body)
+}
+
+/// Parse a JSON-RPC endpoint, `http://` or `https://`.
+pub(crate) fn parse_endpoint(url: &str) -> Result<Endpoint, String> {
+    parse_url(url, "http")
+}
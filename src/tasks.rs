@@ -0,0 +1,127 @@
+// tasks.rs
+use parking_lot::Mutex;
+use pyo3::prelude::*;
+use std::sync::Arc;
+
+use crate::runtime_handle;
+
+/// Cancellation state shared between a `TaskHandle` and the thread driving
+/// its coroutine.
+#[derive(Default)]
+struct TaskControl {
+    cancel_requested: bool,
+    // (event loop, asyncio.Task) while the coroutine is running
+    running: Option<(PyObject, PyObject)>,
+}
+
+/// Handle to a coroutine running on the shared runtime. Await it (or call
+/// `result()`) for the coroutine's return value; `cancel()` raises
+/// `asyncio.CancelledError` inside the coroutine.
+#[pyclass]
+pub struct TaskHandle {
+    // concurrent.futures.Future resolved by the driver thread
+    future: PyObject,
+    control: Arc<Mutex<TaskControl>>,
+}
+
+#[pymethods]
+impl TaskHandle {
+    /// Request cancellation. Returns False if the task had already finished.
+    fn cancel(&self, py: Python) -> PyResult<bool> {
+        if self.done(py)? {
+            return Ok(false);
+        }
+        let running = {
+            let mut control = self.control.lock();
+            control.cancel_requested = true;
+            control
+                .running
+                .as_ref()
+                .map(|(event_loop, task)| (event_loop.clone_ref(py), task.clone_ref(py)))
+        };
+        // Scheduling may release the GIL, so the lock must not be held here
+        if let Some((event_loop, task)) = running {
+            let scheduled =
+                event_loop.call_method1(py, "call_soon_threadsafe", (task.getattr(py, "cancel")?,));
+            // The loop closes once the task finishes; that is not an error
+            if let Err(e) = scheduled {
+                if !self.done(py)? && !event_loop.call_method0(py, "is_closed")?.extract(py)? {
+                    return Err(e);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn done(&self, py: Python) -> PyResult<bool> {
+        self.future.call_method0(py, "done")?.extract(py)
+    }
+
+    /// Block until the task finishes and return its result (or raise its error).
+    #[pyo3(signature = (timeout=None))]
+    fn result(&self, py: Python, timeout: Option<f64>) -> PyResult<PyObject> {
+        self.future.call_method1(py, "result", (timeout,))
+    }
+
+    fn __await__(&self, py: Python) -> PyResult<PyObject> {
+        let asyncio = py.import("asyncio")?;
+        let wrapped = asyncio.call_method1("wrap_future", (self.future.clone_ref(py),))?;
+        Ok(wrapped.call_method0("__await__")?.unbind())
+    }
+}
+
+/// Run `coro` on its own event loop in a `GLOBAL_RUNTIME` blocking thread,
+/// so custom async jobs can reuse the crate's runtime.
+#[pyfunction]
+pub fn spawn_task(py: Python, coro: PyObject) -> PyResult<TaskHandle> {
+    let future = py
+        .import("concurrent.futures")?
+        .getattr("Future")?
+        .call0()?
+        .unbind();
+    let control = Arc::new(Mutex::new(TaskControl::default()));
+
+    let (driver_future, driver_control) = (future.clone_ref(py), control.clone());
+    runtime_handle()?.spawn_blocking(move || {
+        Python::with_gil(|py| {
+            if let Err(e) = drive(py, coro, &driver_future, &driver_control) {
+                // Never leave the handle pending, even if setup failed
+                let _ = driver_future.call_method1(py, "set_exception", (e.into_value(py),));
+            }
+        })
+    });
+    Ok(TaskHandle { future, control })
+}
+
+fn drive(
+    py: Python,
+    coro: PyObject,
+    future: &PyObject,
+    control: &Mutex<TaskControl>,
+) -> PyResult<()> {
+    let asyncio = py.import("asyncio")?;
+    let event_loop = asyncio.call_method0("new_event_loop")?;
+    let task = event_loop.call_method1("create_task", (coro,))?;
+    {
+        let mut control = control.lock();
+        if control.cancel_requested {
+            task.call_method0("cancel")?;
+        }
+        control.running = Some((event_loop.clone().unbind(), task.clone().unbind()));
+    }
+    let outcome = event_loop.call_method1("run_until_complete", (&task,));
+    // Cleared before closing so later cancel() calls skip the loop
+    control.lock().running = None;
+    event_loop.call_method0("close")?;
+    match outcome {
+        Ok(value) => future.call_method1(py, "set_result", (value,))?,
+        Err(e) => future.call_method1(py, "set_exception", (e.into_value(py),))?,
+    };
+    Ok(())
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<TaskHandle>()?;
+    m.add_function(wrap_pyfunction!(spawn_task, m)?)?;
+    Ok(())
+}
@@ -1,33 +1,601 @@
 // transaction.rs
+use alloy_consensus::transaction::SignerRecoverable;
+use alloy_consensus::Transaction;
+use alloy_primitives::{keccak256, Address, B256, U256};
+use futures::stream::{self, StreamExt};
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::PyList;
+use pyo3::types::{PyDict, PyList};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use crate::chain_monitor::{checksum, decode_envelope, decode_envelope_raw};
+use crate::rpc::{self, RpcError};
+use crate::{runtime_handle, LoopFuture};
 
 #[pyclass]
 pub struct TransactionBatcher {
     max_batch_size: usize,
+    // Relay limit on a batch's summed raw (hex-decoded) size
+    max_batch_bytes: Option<usize>,
+    dedupe: bool,
+}
+
+impl TransactionBatcher {
+    /// With `dedupe`, drop transactions whose raw bytes hash the same as an
+    /// earlier one, keeping first-seen order. Also returns how many were dropped.
+    fn deduplicated(&self, transactions: Vec<String>) -> PyResult<(Vec<String>, usize)> {
+        if !self.dedupe {
+            return Ok((transactions, 0));
+        }
+        let total = transactions.len();
+        let mut seen: HashSet<B256> = HashSet::with_capacity(total);
+        let mut unique = Vec::with_capacity(total);
+        for (index, tx) in transactions.into_iter().enumerate() {
+            let raw = hex::decode(tx.trim_start_matches("0x")).map_err(|e| {
+                PyValueError::new_err(format!("transaction {index}: invalid hex: {e}"))
+            })?;
+            if seen.insert(keccak256(&raw)) {
+                unique.push(tx);
+            }
+        }
+        let dropped = total - unique.len();
+        Ok((unique, dropped))
+    }
+
+    /// The batches, paired with the duplicate count as `(batches, dropped)`
+    /// when deduplicating.
+    fn batches_to_py(
+        &self,
+        py: Python,
+        chunks: Vec<Vec<String>>,
+        dropped: usize,
+    ) -> PyResult<PyObject> {
+        let batches = PyList::new(py, chunks)?;
+        if self.dedupe {
+            Ok((batches, dropped).into_pyobject(py)?.unbind().into_any())
+        } else {
+            Ok(batches.unbind().into_any())
+        }
+    }
 }
 
 #[pymethods]
 impl TransactionBatcher {
     #[new]
-    #[pyo3(signature = (max_batch_size=None))]
-    fn new(max_batch_size: Option<usize>) -> Self {
-        Self {
+    #[pyo3(signature = (max_batch_size=None, max_batch_bytes=None, dedupe=false))]
+    fn new(
+        max_batch_size: Option<usize>,
+        max_batch_bytes: Option<usize>,
+        dedupe: bool,
+    ) -> PyResult<Self> {
+        if max_batch_size == Some(0) {
+            return Err(PyValueError::new_err("max_batch_size must be at least 1"));
+        }
+        Ok(Self {
             max_batch_size: max_batch_size.unwrap_or(100),
+            max_batch_bytes,
+            dedupe,
+        })
+    }
+
+    /// Split transactions into batches of at most `max_batch_size`, also
+    /// closing a batch before its raw size would exceed `max_batch_bytes` when
+    /// set. A transaction over the byte limit by itself gets its own batch.
+    /// With `dedupe=True` exact duplicates (by keccak256 of the raw bytes) are
+    /// dropped first and `(batches, duplicates_dropped)` is returned.
+    ///
+    /// `sort_by="gas_price_desc"` (or `"gas_price_asc"`) first orders the
+    /// transactions by decoded effective gas price; ties keep input order and
+    /// undecodable ones go last. Dynamic-fee types are priced at
+    /// `min(max_fee_per_gas, base_fee + max_priority_fee_per_gas)` given a
+    /// `base_fee`, or at their fee cap without one.
+    #[pyo3(signature = (transactions, sort_by=None, base_fee=None))]
+    fn batch_transactions(
+        &self,
+        py: Python,
+        transactions: Vec<String>,
+        sort_by: Option<&str>,
+        base_fee: Option<u64>,
+    ) -> PyResult<PyObject> {
+        if base_fee.is_some() && sort_by.is_none() {
+            return Err(PyValueError::new_err("base_fee only applies with sort_by"));
+        }
+        let (mut transactions, dropped) = self.deduplicated(transactions)?;
+        if let Some(sort_by) = sort_by {
+            let descending = match sort_by {
+                "gas_price_desc" => true,
+                "gas_price_asc" => false,
+                other => {
+                    return Err(PyValueError::new_err(format!(
+                        "unknown sort_by {other:?}; expected 'gas_price_desc' or 'gas_price_asc'"
+                    )))
+                }
+            };
+            sort_by_gas_price(&mut transactions, descending, base_fee);
+        }
+        let Some(max_bytes) = self.max_batch_bytes else {
+            let chunks: Vec<Vec<String>> = transactions
+                .chunks(self.max_batch_size)
+                .map(|c| c.to_vec())
+                .collect();
+            return self.batches_to_py(py, chunks, dropped);
+        };
+        let mut chunks: Vec<Vec<String>> = Vec::new();
+        let mut current: Vec<String> = Vec::new();
+        let mut current_bytes = 0usize;
+        for (index, tx) in transactions.into_iter().enumerate() {
+            let bytes = hex::decode(tx.trim_start_matches("0x"))
+                .map_err(|e| {
+                    PyValueError::new_err(format!("transaction {index}: invalid hex: {e}"))
+                })?
+                .len();
+            if bytes > max_bytes {
+                tracing::warn!(
+                    index,
+                    bytes,
+                    max_bytes,
+                    "transaction exceeds max_batch_bytes; batching it alone"
+                );
+            }
+            let full = current.len() >= self.max_batch_size || current_bytes + bytes > max_bytes;
+            if !current.is_empty() && full {
+                chunks.push(std::mem::take(&mut current));
+                current_bytes = 0;
+            }
+            current.push(tx);
+            current_bytes += bytes;
+        }
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+        self.batches_to_py(py, chunks, dropped)
+    }
+
+    /// Pair each transaction (raw or an unsigned call, passed through as-is)
+    /// with the nonce it should be signed with: `starting_nonce` for the first,
+    /// incrementing in input order. Returns `[{"transaction", "nonce"}]`.
+    fn assign_nonces(
+        &self,
+        py: Python,
+        transactions: Vec<PyObject>,
+        starting_nonce: u64,
+    ) -> PyResult<PyObject> {
+        let out = PyList::empty(py);
+        for (offset, tx) in transactions.into_iter().enumerate() {
+            let nonce = starting_nonce
+                .checked_add(offset as u64)
+                .ok_or_else(|| PyValueError::new_err("nonce overflow"))?;
+            let entry = PyDict::new(py);
+            entry.set_item("transaction", tx)?;
+            entry.set_item("nonce", nonce)?;
+            out.append(entry)?;
+        }
+        Ok(out.unbind().into_any())
+    }
+
+    /// Send every raw transaction in `batch` to `rpc_url` (`http://` or
+    /// `https://`) with `eth_sendRawTransaction`, at most `max_concurrency` in
+    /// flight.
+    /// Awaiting the result gives one `{"hash", "status", "error"}` dict per
+    /// transaction in input order; `status` is `"submitted"`, `"rejected"`
+    /// (the node returned an error, or the hex is invalid) or `"failed"` (no
+    /// JSON-RPC answer, including a node that does not connect or answer
+    /// within `timeout_secs`). One bad transaction never fails the whole batch.
+    #[pyo3(signature = (rpc_url, batch, max_concurrency=8, timeout_secs=30.0))]
+    fn submit_batch_async(
+        &self,
+        py: Python,
+        rpc_url: String,
+        batch: Vec<String>,
+        max_concurrency: usize,
+        timeout_secs: f64,
+    ) -> PyResult<PyObject> {
+        if max_concurrency == 0 {
+            return Err(PyValueError::new_err("max_concurrency must be positive"));
+        }
+        if !timeout_secs.is_finite() || timeout_secs <= 0.0 {
+            return Err(PyValueError::new_err(format!(
+                "timeout_secs must be a positive number, got {timeout_secs}"
+            )));
+        }
+        let time_limit = Duration::from_secs_f64(timeout_secs);
+        rpc::parse_endpoint(&rpc_url).map_err(PyValueError::new_err)?;
+        let bridge = LoopFuture::new(py)?;
+        let awaitable = bridge.awaitable(py);
+
+        runtime_handle()?.spawn(async move {
+            let results: Vec<Submission> = stream::iter(batch)
+                .map(|tx| submit_raw(&rpc_url, tx, time_limit))
+                .buffered(max_concurrency)
+                .collect()
+                .await;
+            let _ = tokio::task::spawn_blocking(move || {
+                bridge.resolve(move |py| {
+                    let results = results
+                        .iter()
+                        .map(|result| result.to_py(py))
+                        .collect::<PyResult<Vec<_>>>()?;
+                    Ok(PyList::new(py, results)?.unbind().into_any())
+                })
+            })
+            .await;
+        });
+        Ok(awaitable)
+    }
+
+    /// Greedily pack transactions into batches whose summed `cost_fn(tx)` stays
+    /// within `max_cost`. A transaction costing more than `max_cost` on its own
+    /// gets a batch to itself. Costs and `max_cost` must be finite and
+    /// non-negative. Deduplicates like `batch_transactions`.
+    fn batch_by_cost(
+        &self,
+        py: Python,
+        transactions: Vec<String>,
+        cost_fn: &Bound<'_, PyAny>,
+        max_cost: f64,
+    ) -> PyResult<PyObject> {
+        if !max_cost.is_finite() || max_cost < 0.0 {
+            return Err(PyValueError::new_err(format!(
+                "max_cost must be finite and non-negative, got {max_cost}"
+            )));
+        }
+        let mut chunks: Vec<Vec<String>> = Vec::new();
+        let mut current: Vec<String> = Vec::new();
+        let mut current_cost = 0.0;
+        let (transactions, dropped) = self.deduplicated(transactions)?;
+        for tx in transactions {
+            let cost: f64 = cost_fn.call1((tx.as_str(),))?.extract()?;
+            if !cost.is_finite() || cost < 0.0 {
+                return Err(PyValueError::new_err(format!(
+                    "cost_fn must return a finite, non-negative cost, got {cost}"
+                )));
+            }
+            let full = current.len() >= self.max_batch_size || current_cost + cost > max_cost;
+            if !current.is_empty() && full {
+                chunks.push(std::mem::take(&mut current));
+                current_cost = 0.0;
+            }
+            current.push(tx);
+            current_cost += cost;
+        }
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+        self.batches_to_py(py, chunks, dropped)
+    }
+}
+
+/// Stable sort by effective gas price at `base_fee`; transactions that fail to
+/// decode sink to the end in their original order.
+fn sort_by_gas_price(transactions: &mut [String], descending: bool, base_fee: Option<u64>) {
+    let price = |tx: &String| {
+        decode_envelope_raw(tx)
+            .map(|(_, envelope)| envelope.effective_gas_price(base_fee))
+            .ok()
+    };
+    let mut keyed: Vec<(Option<u128>, String)> = transactions
+        .iter_mut()
+        .map(|tx| (price(tx), std::mem::take(tx)))
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(a),
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    for (slot, (_, tx)) in transactions.iter_mut().zip(keyed) {
+        *slot = tx;
+    }
+}
+
+/// Outcome of one `eth_sendRawTransaction` in `submit_batch_async`.
+struct Submission {
+    hash: Option<String>,
+    status: &'static str,
+    error: Option<String>,
+}
+
+impl Submission {
+    fn to_py<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("hash", &self.hash)?;
+        dict.set_item("status", self.status)?;
+        dict.set_item("error", &self.error)?;
+        Ok(dict)
+    }
+}
+
+/// The hash is computed locally so rejected transactions can still be matched
+/// up; a node-reported hash takes precedence when the node accepts.
+async fn submit_raw(rpc_url: &str, tx: String, time_limit: Duration) -> Submission {
+    let raw = match hex::decode(tx.trim_start_matches("0x")) {
+        Ok(raw) => raw,
+        Err(e) => {
+            return Submission {
+                hash: None,
+                status: "rejected",
+                error: Some(format!("invalid hex: {e}")),
+            }
+        }
+    };
+    let local_hash = format!("{:#x}", keccak256(&raw));
+    let params = json!([format!("0x{}", hex::encode(&raw))]);
+    match rpc::call(rpc_url, "eth_sendRawTransaction", params, time_limit).await {
+        Ok(result) => Submission {
+            hash: Some(result.as_str().map_or(local_hash, str::to_string)),
+            status: "submitted",
+            error: None,
+        },
+        Err(RpcError::Rejected(message)) => Submission {
+            hash: Some(local_hash),
+            status: "rejected",
+            error: Some(message),
+        },
+        Err(RpcError::Transport(message)) => Submission {
+            hash: Some(local_hash),
+            status: "failed",
+            error: Some(message),
+        },
+    }
+}
+
+/// Total gas limit and worst-case fee (`gas_limit * max_fee_per_gas`) for a
+/// flat list of raw transactions, or one totals dict per batch when given a
+/// list of batches. Wide values are strings, matching `decode_transaction`.
+#[pyfunction]
+pub fn batch_totals(py: Python, transactions: &Bound<'_, PyList>) -> PyResult<PyObject> {
+    let nested = transactions
+        .iter()
+        .next()
+        .is_some_and(|first| first.downcast::<PyList>().is_ok());
+    if nested {
+        let per_batch = PyList::empty(py);
+        for batch in transactions.iter() {
+            per_batch.append(totals_dict(py, &batch.extract::<Vec<String>>()?)?)?;
+        }
+        return Ok(per_batch.unbind().into_any());
+    }
+    Ok(totals_dict(py, &transactions.extract::<Vec<String>>()?)?.into_any())
+}
+
+fn totals_dict(py: Python, transactions: &[String]) -> PyResult<Py<PyDict>> {
+    let mut total_gas = U256::ZERO;
+    let mut total_max_fee = U256::ZERO;
+    for tx_hex in transactions {
+        let (_, envelope) = decode_envelope(tx_hex)?;
+        let gas = U256::from(envelope.gas_limit());
+        total_gas += gas;
+        total_max_fee += gas * U256::from(envelope.max_fee_per_gas());
+    }
+    let out = PyDict::new(py);
+    out.set_item("total_gas", total_gas.to_string())?;
+    out.set_item("total_max_fee_wei", total_max_fee.to_string())?;
+    out.set_item("count", transactions.len())?;
+    Ok(out.unbind())
+}
+
+/// Assign batches to `num_workers` so each worker's total transaction count is
+/// balanced: largest batches first, each to the currently least-loaded worker.
+#[pyfunction]
+pub fn partition_batches(
+    py: Python,
+    batches: Vec<Vec<String>>,
+    num_workers: usize,
+) -> PyResult<PyObject> {
+    if num_workers == 0 {
+        return Err(PyValueError::new_err("num_workers must be positive"));
+    }
+    let mut order: Vec<usize> = (0..batches.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(batches[i].len()));
+
+    let mut workers: Vec<Vec<usize>> = vec![Vec::new(); num_workers];
+    let mut loads = vec![0usize; num_workers];
+    for i in order {
+        let (worker, _) = loads
+            .iter()
+            .enumerate()
+            .min_by_key(|&(w, load)| (*load, w))
+            .expect("num_workers > 0");
+        loads[worker] += batches[i].len();
+        workers[worker].push(i);
+    }
+
+    let assigned: Vec<Vec<Vec<String>>> = workers
+        .into_iter()
+        .map(|indices| indices.into_iter().map(|i| batches[i].clone()).collect())
+        .collect();
+    Ok(PyList::new(py, assigned)?.unbind().into_any())
+}
+
+/// Split raw transactions by recovered sender: `accepted` keeps those signed by
+/// an address in `allowlist` (in any case), `rejected` the rest, including
+/// transactions whose signer does not recover. Order within each list follows
+/// the input.
+#[pyfunction]
+pub fn filter_by_sender(
+    py: Python,
+    transactions: Vec<String>,
+    allowlist: Vec<String>,
+) -> PyResult<PyObject> {
+    let allowed = allowlist
+        .iter()
+        .map(|entry| {
+            entry.parse::<Address>().map_err(|e| {
+                PyValueError::new_err(format!("invalid allowlist address {entry:?}: {e}"))
+            })
+        })
+        .collect::<PyResult<HashSet<Address>>>()?;
+    let mut accepted = Vec::new();
+    let mut rejected = Vec::new();
+    for tx_hex in transactions {
+        let (_, envelope) = decode_envelope(&tx_hex)?;
+        let sender = envelope.recover_signer().ok();
+        if sender.is_some_and(|sender| allowed.contains(&sender)) {
+            accepted.push(tx_hex);
+        } else {
+            rejected.push(tx_hex);
+        }
+    }
+    let out = PyDict::new(py);
+    out.set_item("accepted", accepted)?;
+    out.set_item("rejected", rejected)?;
+    Ok(out.unbind().into_any())
+}
+
+/// Check that a bundle can execute in the given order: each sender's nonces
+/// must increase by exactly one from transaction to transaction. Returns
+/// `{"valid", "issues"}`; each issue names its `kind` ("duplicate_nonce",
+/// "nonce_gap" or "out_of_order"), `sender`, `nonce` and bundle `index`.
+#[pyfunction]
+pub fn validate_bundle(py: Python, transactions: Vec<String>) -> PyResult<PyObject> {
+    let issues = PyList::empty(py);
+    let mut seen: std::collections::HashSet<(String, u64)> = std::collections::HashSet::new();
+    let mut last_nonce: HashMap<String, u64> = HashMap::new();
+    for (index, tx_hex) in transactions.iter().enumerate() {
+        let (_, envelope) = decode_envelope(tx_hex)?;
+        let sender = checksum(
+            &envelope
+                .recover_signer()
+                .map_err(|e| PyValueError::new_err(format!("failed to recover signer: {e}")))?,
+        );
+        let nonce = envelope.nonce();
+        let kind = if !seen.insert((sender.clone(), nonce)) {
+            Some("duplicate_nonce")
+        } else {
+            match last_nonce.get(&sender) {
+                Some(&last) if nonce < last => Some("out_of_order"),
+                Some(&last) if nonce > last + 1 => Some("nonce_gap"),
+                _ => None,
+            }
+        };
+        if let Some(kind) = kind {
+            let issue = PyDict::new(py);
+            issue.set_item("kind", kind)?;
+            issue.set_item("sender", &sender)?;
+            issue.set_item("nonce", nonce)?;
+            issue.set_item("index", index)?;
+            issues.append(issue)?;
+        }
+        let last = last_nonce.entry(sender).or_insert(nonce);
+        *last = (*last).max(nonce);
+    }
+    let out = PyDict::new(py);
+    out.set_item("valid", issues.is_empty())?;
+    out.set_item("issues", issues)?;
+    Ok(out.unbind().into_any())
+}
+
+/// Keccak Merkle root over the batch's transaction hashes. Leaves are hashed
+/// as `keccak256(0x00 || tx_hash)` and internal nodes as
+/// `keccak256(0x01 || lo || hi)`, so an internal node cannot pass as a leaf.
+/// Pairs are hashed in sorted order so a proof is just the list of siblings;
+/// an unpaired node is carried up unchanged. With `include_proofs` each leaf's
+/// proof is returned too.
+#[pyfunction]
+#[pyo3(signature = (transactions, include_proofs=false))]
+pub fn batch_merkle_root(
+    py: Python,
+    transactions: Vec<String>,
+    include_proofs: bool,
+) -> PyResult<PyObject> {
+    if transactions.is_empty() {
+        return Err(PyValueError::new_err(
+            "cannot build a Merkle root of an empty batch",
+        ));
+    }
+    let leaves = transactions
+        .iter()
+        .map(|tx_hex| Ok(*decode_envelope(tx_hex)?.1.tx_hash()))
+        .collect::<PyResult<Vec<B256>>>()?;
+
+    let mut proofs: Vec<Vec<B256>> = vec![Vec::new(); leaves.len()];
+    // Position of each leaf within the current level
+    let mut positions: Vec<usize> = (0..leaves.len()).collect();
+    let mut level: Vec<B256> = leaves.iter().map(hash_leaf).collect();
+    while level.len() > 1 {
+        if include_proofs {
+            for (proof, pos) in proofs.iter_mut().zip(&positions) {
+                if let Some(sibling) = level.get(*pos ^ 1) {
+                    proof.push(*sibling);
+                }
+            }
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => hash_pair(a, b),
+                [a] => *a,
+                _ => unreachable!(),
+            })
+            .collect();
+        for pos in positions.iter_mut() {
+            *pos /= 2;
         }
     }
 
-    fn batch_transactions(&self, py: Python, transactions: Vec<String>) -> PyResult<PyObject> {
-        let chunks: Vec<Vec<String>> = transactions
-            .chunks(self.max_batch_size)
-            .map(|c| c.to_vec())
+    let out = PyDict::new(py);
+    out.set_item("root", format!("{:#x}", level[0]))?;
+    out.set_item(
+        "leaves",
+        leaves.iter().map(|h| format!("{h:#x}")).collect::<Vec<_>>(),
+    )?;
+    if include_proofs {
+        let proofs: Vec<Vec<String>> = proofs
+            .iter()
+            .map(|proof| proof.iter().map(|h| format!("{h:#x}")).collect())
             .collect();
-        let py_list = PyList::new(py, chunks)?;
-        Ok(py_list.unbind().into_any())
+        out.set_item("proofs", proofs)?;
     }
+    Ok(out.unbind().into_any())
+}
+
+/// Check a proof produced by `batch_merkle_root` for `leaf`, a transaction
+/// hash from its `leaves`, against `root`.
+#[pyfunction]
+pub fn verify_merkle_proof(leaf: &str, proof: Vec<String>, root: &str) -> PyResult<bool> {
+    let mut acc = hash_leaf(&parse_b256(leaf)?);
+    for sibling in &proof {
+        acc = hash_pair(&acc, &parse_b256(sibling)?);
+    }
+    Ok(acc == parse_b256(root)?)
+}
+
+// Domain prefixes keeping leaf and internal node hashes apart
+const MERKLE_LEAF: u8 = 0x00;
+const MERKLE_NODE: u8 = 0x01;
+
+fn hash_leaf(tx_hash: &B256) -> B256 {
+    let mut buf = [0u8; 33];
+    buf[0] = MERKLE_LEAF;
+    buf[1..].copy_from_slice(tx_hash.as_slice());
+    keccak256(buf)
+}
+
+fn hash_pair(a: &B256, b: &B256) -> B256 {
+    let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+    let mut buf = [0u8; 65];
+    buf[0] = MERKLE_NODE;
+    buf[1..33].copy_from_slice(lo.as_slice());
+    buf[33..].copy_from_slice(hi.as_slice());
+    keccak256(buf)
+}
+
+fn parse_b256(value: &str) -> PyResult<B256> {
+    value
+        .parse()
+        .map_err(|e| PyValueError::new_err(format!("invalid 32-byte hash {value:?}: {e}")))
 }
 
 pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<TransactionBatcher>()?;
+    m.add_function(wrap_pyfunction!(batch_totals, m)?)?;
+    m.add_function(wrap_pyfunction!(partition_batches, m)?)?;
+    m.add_function(wrap_pyfunction!(filter_by_sender, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_bundle, m)?)?;
+    m.add_function(wrap_pyfunction!(batch_merkle_root, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_merkle_proof, m)?)?;
     Ok(())
 }
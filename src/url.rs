@@ -0,0 +1,44 @@
+// url.rs
+//! Endpoint URL parsing shared by the HTTP and websocket clients.
+
+pub(crate) struct Endpoint {
+    /// The `https`/`wss` variant of the scheme was used.
+    pub(crate) tls: bool,
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) path: String,
+}
+
+/// Parse a `<scheme>://host[:port][/path]` URL, or its TLS variant
+/// `<scheme>s://`, for a plain scheme such as `http` or `ws`. The port
+/// defaults to 80, or 443 with TLS.
+pub(crate) fn parse_url(url: &str, scheme: &str) -> Result<Endpoint, String> {
+    let (tls, rest) = url
+        .strip_prefix(scheme)
+        .and_then(|rest| match rest.strip_prefix("s://") {
+            Some(rest) => Some((true, rest)),
+            None => rest.strip_prefix("://").map(|rest| (false, rest)),
+        })
+        .ok_or_else(|| format!("expected a {scheme}:// or {scheme}s:// URL, got {url:?}"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .map_err(|_| format!("invalid port in {url:?}"))?,
+        ),
+        None => (authority, if tls { 443 } else { 80 }),
+    };
+    if host.is_empty() {
+        return Err(format!("missing host in {url:?}"));
+    }
+    Ok(Endpoint {
+        tls,
+        host: host.to_string(),
+        port,
+        path: path.to_string(),
+    })
+}
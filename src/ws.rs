@@ -0,0 +1,79 @@
+// ws.rs
+//! Websocket client for JSON-RPC subscriptions over `ws://` or `wss://`,
+//! with a ping keepalive so half-open connections are noticed.
+use futures::{SinkExt, StreamExt};
+use std::sync::Once;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async_with_config, MaybeTlsStream, WebSocketStream};
+
+// Larger messages are treated as a protocol error rather than buffered
+const MAX_MESSAGE_BYTES: usize = 16 << 20;
+
+static TLS_PROVIDER: Once = Once::new();
+
+pub(crate) struct WsStream {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    idle_timeout: Duration,
+}
+
+impl WsStream {
+    /// Connect and complete the opening handshake, both within
+    /// `idle_timeout`. A connection that then stays silent for `idle_timeout`
+    /// is pinged, and dropped if the silence lasts another `idle_timeout`.
+    pub(crate) async fn connect(url: &str, idle_timeout: Duration) -> Result<Self, String> {
+        TLS_PROVIDER.call_once(|| {
+            // Fails only if the process already installed a provider
+            let _ = rustls::crypto::ring::default_provider().install_default();
+        });
+        let config = WebSocketConfig::default().max_message_size(Some(MAX_MESSAGE_BYTES));
+        let (stream, _) = timeout(
+            idle_timeout,
+            connect_async_with_config(url, Some(config), true),
+        )
+        .await
+        .map_err(|_| format!("connect to {url} timed out"))?
+        .map_err(|e| format!("connect to {url} failed: {e}"))?;
+        Ok(Self {
+            stream,
+            idle_timeout,
+        })
+    }
+
+    pub(crate) async fn send_text(&mut self, text: &str) -> Result<(), String> {
+        self.stream
+            .send(Message::text(text))
+            .await
+            .map_err(|e| format!("write failed: {e}"))
+    }
+
+    /// Next text message; pings are answered on the way. `None` once the
+    /// server closes the connection.
+    pub(crate) async fn next_text(&mut self) -> Result<Option<String>, String> {
+        let mut pinged = false;
+        loop {
+            let message = match timeout(self.idle_timeout, self.stream.next()).await {
+                Ok(Some(message)) => message.map_err(|e| format!("read failed: {e}"))?,
+                Ok(None) => return Ok(None),
+                Err(_) if !pinged => {
+                    self.stream
+                        .send(Message::Ping(Default::default()))
+                        .await
+                        .map_err(|e| format!("write failed: {e}"))?;
+                    pinged = true;
+                    continue;
+                }
+                Err(_) => return Err("connection went silent; no answer to ping".into()),
+            };
+            pinged = false;
+            match message {
+                Message::Text(text) => return Ok(Some(text.to_string())),
+                Message::Close(_) => return Ok(None),
+                Message::Binary(_) | Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,13 @@
+"""Builders shared by the platform_rust tests."""
+
+
+def make_pool(address, token0, token1, reserve0, reserve1, fee=3000):
+    return {
+        "address": address,
+        "token0": {"address": token0, "symbol": token0.upper()},
+        "token1": {"address": token1, "symbol": token1.upper()},
+        "fee": fee,
+        "liquidity": reserve0 + reserve1,
+        "reserve0": reserve0,
+        "reserve1": reserve1,
+    }
@@ -2,6 +2,7 @@ import os
 import sys
 import pytest
 import importlib.util
+from decimal import Decimal
 
 # Import rust_bindings from the canonical package
 from platform_py.rust_bindings import decode_transaction, optimize_route, aggregate_order_books
@@ -50,3 +51,52 @@ def test_aggregate_order_books_fallback():
     assert isinstance(result, dict)
     assert 'bids' in result
     assert 'asks' in result
+
+
+def test_aggregate_order_books_fallback_matches_native():
+    platform_rust = pytest.importorskip("platform_rust")
+    from platform_py.rust_bindings import _fallback_aggregate_order_books
+
+    untagged = [
+        {"side": "bid", "price": 99, "size": 4},
+        {"side": "ask", "price": 103, "size": 1},
+        {"side": "bid", "price": 101, "size": 2},
+        {"side": "ask", "price": 102, "size": 5},
+        {"side": "bid", "price": 99, "size": 3},
+        {"side": "ask", "price": 102, "size": 0},
+        {"side": "bid", "price": 100, "size": 6},
+    ]
+    fractional = [
+        {"side": "bid", "price": 0.015, "size": 1.5},
+        {"side": "bid", "price": 0.0151, "size": 2},
+        {"side": "ask", "price": 0.016, "size": 0.25},
+        {"side": "bid", "price": 0.015, "size": 0.5},
+    ]
+    exact = [
+        {"side": "bid", "price": Decimal("1.10"), "size": Decimal("3")},
+        {"side": "ask", "price": Decimal("1.25"), "size": Decimal("0.5")},
+        {"side": "ask", "price": Decimal("1.2"), "size": Decimal("1")},
+    ]
+    tagged = [
+        {"side": "bid", "price": 100, "size": 2, "source": "b"},
+        {"side": "bid", "price": 100, "size": 3, "source": "a"},
+        {"side": "bid", "price": 100, "size": 1, "source": None},
+        {"side": "ask", "price": 101, "size": 4, "source": "a"},
+        {"side": "bid", "price": 98, "size": 7, "source": None},
+    ]
+    def shape(result):
+        # Types too, since 1 == 1.0 would hide an int/float mismatch
+        flat = [x for key in ("bids", "asks") for level in result[key] for x in level]
+        flat += [result[k] for k in ("best_bid", "best_ask", "mid_price", "spread")]
+        return [(x, type(x)) for x in flat]
+
+    for entries in (untagged, fractional, exact, tagged, []):
+        for depth in (None, 0, 1, 2, 10):
+            native = platform_rust.aggregate_order_books(entries, depth=depth)
+            fallback = _fallback_aggregate_order_books(entries, depth)
+            assert shape(fallback) == shape(native), (entries, depth)
+            for key in ("bid_vwap", "ask_vwap"):
+                if native[key] is None:
+                    assert fallback[key] is None
+                else:
+                    assert fallback[key] == pytest.approx(native[key])
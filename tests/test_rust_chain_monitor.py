@@ -0,0 +1,623 @@
+import asyncio
+import base64
+import hashlib
+import json
+import time
+
+import pytest
+
+platform_rust = pytest.importorskip("platform_rust")
+
+# EIP-155 reference transaction, signed by 0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f
+LEGACY_TX = (
+    "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939"
+    "bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1"
+    "966a3b6d83"
+)
+# Same sender, signed without EIP-155 replay protection
+LEGACY_PRE155_TX = (
+    "0xf86c808504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000801ca0ae4ec29e5426"
+    "516043e3bbfd7950486f0301c1b640f1173c05928ac063e1bcdba03006963cacd3ec33bfc3c37a8f56187a33a333b01a7e11e405"
+    "424aaedcdbc3f4"
+)
+EIP1559_TX = (
+    "0x02f86d010284773594008509502f900082ea6094353535353535353535353535353535353535353582303980c080a0dc2f233fc7"
+    "26c8efb5cf06927527dd45ac7845eacdd3da822033644bd677201ca04a55be67abceccf8b2b76054f5f18085f07948611361c4c6d9"
+    "a92b391b7e1518"
+)
+# Same sender; the EIP-2930 transaction carries a one-entry access list
+EIP2930_TX = (
+    "0x01f8c201018506fc23ac0082c3509435353535353535353535353535353535353535350180f85bf85994111111111111111111"
+    "1111111111111111111111f842a00000000000000000000000000000000000000000000000000000000000000000a00000000000"
+    "00000000000000000000000000000000000000000000000000000101a080e82d9c6c176009fa43eba12afdb7663d8b0b4f0ed78f"
+    "961c90cc6ed45f0706a02cc305f8f8efceec03361eed1f5bb4375294a6d2af95f7a004e7fb8ce9c8c4db"
+)
+EIP4844_TX = (
+    "0x03f8b60103843b9aca00850ba43b7400830186a09435353535353535353535353535353535353535358080c08501a13b8600f8"
+    "42a00100000000000000000000000000000000000000000000000000000000000001a00100000000000000000000000000000000"
+    "00000000000000000000000000000280a0b423f10adae8e53b6a289b461c3d421b7bedc9745fef20b9b67b0125cdd8dccea0601a"
+    "7ad795fce1f060d5cb0b5b32cfaac0a07c2a05b7b16fac6348c9e2f5c11e"
+)
+EIP7702_TX = (
+    "0x04f8ca010484b2d05e00850a7a358200830138809435353535353535353535353535353535353535358080c0f85cf85a019422"
+    "222222222222222222222222222222222222220780a08d14c7d7e06b67c43a147f14c63523ef36d6eb34deb105b7b1f7c594c7f9"
+    "1818a002a7f2524299517a73b25a159174a75c09e7e9039a45a481840083871675519580a0afe217685a3eea6359c42884328503"
+    "b92cd847297984bb11c1a27d9d9d650e3aa07ae6308ecf773a4f4f03e741d8626e99fe890b5024843d2a7baaa00a7e710a43"
+)
+
+# Nonces 0, 1 and 3, signed by 0x19e7e376e7c213b7e7e7e46cc70a5dd086daff2a
+SENDER2_NONCE0_TX = (
+    "0x02f86d0180843b9aca008502540be40082ea6094353535353535353535353535353535353535353582303980c080a0f73474f2"
+    "fa9958a02492d018313376691cb86421511ed72e2be3244d20f8f2d7a0329e8af62c513dc0e223b10b4f211bd8594a679270a9bb"
+    "9b57c0a534c8534e21"
+)
+SENDER2_NONCE1_TX = (
+    "0x02f86d0101843b9aca00850df847580082ea6094353535353535353535353535353535353535353582303980c001a0412c94cf"
+    "1c1ccabd5c79df097aee210d2e839cf2215355f3f54b612dcbeea392a0637f8b1efc4808e3f9ec7f8e491a3e2ccd6499e908223c"
+    "3749769cfd78ca3951"
+)
+SENDER2_NONCE3_TX = (
+    "0x02f86d0103843b9aca008504a817c80082ea6094353535353535353535353535353535353535353582303980c001a06427e48e"
+    "9cfbe52fe9cdec7328d11962bfaa844ee2a2966e50fa92ac744537f2a040766d989f3713709dabfa55d712c9f13a58eb8282fa81"
+    "09fa73f40294f548be"
+)
+
+# Calls to 0x3535... with ERC-20 transfer, approve, router swap and unrecognized selectors
+ERC20_TRANSFER_TX = (
+    "0x02f8b2010684773594008509502f900082ea60943535353535353535353535353535353535353535823039b844a9059cbb0000"
+    "00000000000000000000111111111111111111111111111111111111111100000000000000000000000000000000000000000000"
+    "00000000000000000064c080a0cee76a4119aa978d6e0da3df380e5d8ac277091e911069061953eb2285941d8fa04c937d7df623"
+    "36966eecaf7bb5945991dadd1e311db5bf166d09fd45348dec2f"
+)
+APPROVE_TX = (
+    "0x02f8b2010784773594008509502f900082ea60943535353535353535353535353535353535353535823039b844095ea7b30000"
+    "000000000000000000001111111111111111111111111111111111111111ffffffffffffffffffffffffffffffffffffffffffff"
+    "ffffffffffffffffffffc001a06f62c03eb6df530cb1d2f5b8854571943059370439e2b8a290c8c9ae4f608c01a06a343d70853f"
+    "9bfc745332130e7a682cd6e9a54ade996ff43be1424c7d9fc29c"
+)
+SWAP_TX = (
+    "0x02f891010884773594008509502f900082ea60943535353535353535353535353535353535353535823039a438ed1739000000"
+    "0000000000000000000000000000000000000000000000000000000064c001a0436b276510e6e44210d0bef01f638052e7772b85"
+    "a4decf1640f55d512c226f11a07d3083a6ee2f03b6477bc237ae914cdbd5cb565651c79acec311710ce6f5869e"
+)
+UNKNOWN_CALL_TX = (
+    "0x02f871010984773594008509502f900082ea6094353535353535353535353535353535353535353582303984deadbeefc080a0"
+    "760cfa9884b55ba40ab7327cfe9348692f1b2c7c2768ae898118499d7ff4ca4ba02b5f332f526cf6362fdacfc10c68faf846cdef"
+    "c17fbde28ff2cc56b65d4e00fa"
+)
+# Contract creation
+CREATE_TX = (
+    "0x02f85e010584773594008509502f900082ea6080823039856080604052c001a0637b0a7fd4301b4709ed49aeaef4d6468ad2c6"
+    "978c8b367690f238d6b29d8e71a03d3a12ae45298fa98de3f99a7d1912999a820edfdd380d996e777d62965289df"
+)
+
+
+def raw(tx_hex):
+    return bytes.fromhex(tx_hex[2:])
+
+
+def test_field_offsets_slice_to_field_encodings():
+    decoded = platform_rust.decode_transaction(LEGACY_TX, include_offsets=True)
+    data = raw(LEGACY_TX)
+
+    def field(name):
+        start, length = decoded["field_offsets"][name]
+        return data[start : start + length]
+
+    assert field("nonce") == b"\x09"
+    assert field("gas_limit") == bytes.fromhex("825208")
+    assert field("to") == b"\x94" + b"\x35" * 20
+    assert field("value") == bytes.fromhex("880de0b6b3a7640000")
+    assert field("input") == b"\x80"
+    assert field("v") == b"\x25"
+
+    typed = platform_rust.decode_transaction(EIP1559_TX, include_offsets=True)["field_offsets"]
+    start, length = typed["chain_id"]
+    assert raw(EIP1559_TX)[start : start + length] == b"\x01"
+    assert "field_offsets" not in platform_rust.decode_transaction(EIP1559_TX)
+
+
+def test_blob_base_fee_matches_spec_reference_values():
+    # Values from the EIP-4844 `fake_exponential` reference implementation
+    assert platform_rust.blob_base_fee(0) == 1
+    assert platform_rust.blob_base_fee(393_216) == 1
+    assert platform_rust.blob_base_fee(3_338_477) == 2
+    assert platform_rust.blob_base_fee(10_000_000) == 19
+    assert platform_rust.blob_base_fee(50_000_000) == 3_194_333
+
+
+def test_blob_base_fee_large_excess_raises_promptly():
+    def fake_exponential(factor, numerator, denominator):
+        i, output, accum = 1, 0, factor * denominator
+        while accum > 0:
+            output += accum
+            accum = accum * numerator // (denominator * i)
+            i += 1
+        return output // denominator
+
+    # Near the top of the u128 range the fee still matches the reference
+    assert platform_rust.blob_base_fee(290_000_000) == fake_exponential(1, 290_000_000, 3_338_477)
+    started = time.monotonic()
+    for excess in (300_000_000, 10**14, 2**64 - 1):
+        with pytest.raises(ValueError, match="exceeds u128"):
+            platform_rust.blob_base_fee(excess)
+    assert time.monotonic() - started < 0.5
+
+
+def rlp_length_prefix(length, offset):
+    if length < 56:
+        return bytes([offset + length])
+    encoded = length.to_bytes((length.bit_length() + 7) // 8, "big")
+    return bytes([offset + 55 + len(encoded)]) + encoded
+
+
+def rlp_list(*encoded_items):
+    payload = b"".join(encoded_items)
+    return rlp_length_prefix(len(payload), 0xC0) + payload
+
+
+def rlp_bytes(data):
+    if len(data) == 1 and data[0] < 0x80:
+        return data
+    return rlp_length_prefix(len(data), 0x80) + data
+
+
+def test_decode_block_transactions_handles_legacy_and_typed():
+    header = rlp_list(rlp_bytes(b"\x11" * 32), rlp_bytes(b"\x01"))
+    # Legacy transactions are embedded as lists, typed ones as byte strings
+    transactions = rlp_list(raw(LEGACY_TX), rlp_bytes(raw(EIP1559_TX)))
+    block = rlp_list(header, transactions, rlp_list())
+
+    decoded = platform_rust.decode_block_transactions("0x" + block.hex())
+
+    assert [tx["hash"] for tx in decoded] == [
+        platform_rust.decode_transaction(LEGACY_TX)["hash"],
+        platform_rust.decode_transaction(EIP1559_TX)["hash"],
+    ]
+    assert {tx["from"] for tx in decoded} == {"0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"}
+
+
+def test_decode_block_transactions_rejects_items_past_their_list():
+    header = rlp_list(rlp_bytes(b"\x11" * 32), rlp_bytes(b"\x01"))
+    transactions = rlp_list(raw(LEGACY_TX), rlp_bytes(raw(EIP1559_TX)))
+    for block in (
+        # A one-byte transaction list whose transaction runs on into the uncles
+        rlp_list(header, b"\xc1" + raw(LEGACY_TX), rlp_list()),
+        # A block list that ends after the header, followed by transactions
+        rlp_list(header) + transactions + rlp_list(),
+        rlp_list(header, transactions, rlp_list())[:-20],
+    ):
+        with pytest.raises(ValueError):
+            platform_rust.decode_block_transactions("0x" + block.hex())
+
+
+def test_decoded_tx_attributes_match_dict():
+    for tx_hex, tx_type in ((LEGACY_TX, 0), (EIP1559_TX, 2)):
+        tx = platform_rust.DecodedTx(tx_hex)
+        as_dict = tx.to_dict()
+
+        assert as_dict == platform_rust.decode_transaction(tx_hex)
+        assert tx.tx_type == tx_type
+        assert tx.from_ == as_dict["from"]
+        assert tx.to == as_dict["to"]
+        assert tx.nonce == as_dict["nonce"]
+        assert tx.value == int(as_dict["value"])
+        assert tx.gas_limit == int(as_dict["gas"])
+        for fee in ("gas_price", "max_fee_per_gas", "max_priority_fee_per_gas"):
+            expected = as_dict[fee]
+            assert getattr(tx, fee) == (None if expected is None else int(expected))
+        assert tx.hash == as_dict["hash"]
+        assert tx.access_list == []
+
+    legacy = platform_rust.DecodedTx(LEGACY_TX)
+    assert legacy.chain_id == 1
+    assert legacy.gas_price == 20_000_000_000
+    assert legacy.max_fee_per_gas is None
+    assert legacy.max_priority_fee_per_gas is None
+    assert platform_rust.DecodedTx(EIP2930_TX).max_fee_per_gas is None
+    assert platform_rust.DecodedTx(EIP1559_TX).max_priority_fee_per_gas == 2_000_000_000
+
+
+def test_decode_transaction_human_fields():
+    legacy = platform_rust.decode_transaction(LEGACY_TX, human=True)
+    assert legacy["value_eth"] == pytest.approx(1.0)
+    assert legacy["gas_price_gwei"] == pytest.approx(20.0)
+    assert legacy["value"] == str(10**18)
+
+    dynamic = platform_rust.decode_transaction(EIP1559_TX, human=True)
+    assert dynamic["value_eth"] == pytest.approx(12345 / 1e18)
+    assert dynamic["gas_price_gwei"] == pytest.approx(40.0)
+    assert "value_eth" not in platform_rust.decode_transaction(EIP1559_TX)
+
+
+def test_next_nonce_from_pending_sender_transactions():
+    pending = [SENDER2_NONCE1_TX, LEGACY_TX, SENDER2_NONCE3_TX, SENDER2_NONCE0_TX]
+
+    assert platform_rust.next_nonce(pending, "0x19E7E376E7C213B7E7E7E46CC70A5DD086DAFF2A") == 4
+    assert platform_rust.next_nonce(pending, "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f") == 10
+    assert platform_rust.next_nonce(pending, "0x" + "00" * 20) is None
+
+
+def test_decode_transaction_labels_action_by_selector():
+    def action(tx_hex):
+        return platform_rust.decode_transaction(tx_hex)["action"]
+
+    assert action(EIP1559_TX) == "eth_transfer"
+    assert action(ERC20_TRANSFER_TX) == "erc20_transfer"
+    assert action(APPROVE_TX) == "approve"
+    assert action(SWAP_TX) == "swap"
+    assert action(UNKNOWN_CALL_TX) == "unknown"
+    assert action(CREATE_TX) == "contract_creation"
+
+
+def test_decode_transactions_keeps_partial_results():
+    decoded = platform_rust.decode_transactions([LEGACY_TX, "0xzz", SWAP_TX, "0x01"])
+
+    assert len(decoded) == 4
+    assert decoded[0] == platform_rust.decode_transaction(LEGACY_TX)
+    assert decoded[2] == platform_rust.decode_transaction(SWAP_TX)
+    assert "invalid hex" in decoded[1]["error"]
+    assert set(decoded[3]) == {"error"}
+    assert platform_rust.decode_transactions([]) == []
+
+
+def test_decode_transaction_reports_tx_type():
+    cases = [
+        (LEGACY_TX, 0, "legacy"),
+        (EIP2930_TX, 1, "eip2930"),
+        (EIP1559_TX, 2, "eip1559"),
+        (EIP4844_TX, 3, "eip4844"),
+        (EIP7702_TX, 4, "eip7702"),
+    ]
+    for tx_hex, tx_type, name in cases:
+        decoded = platform_rust.decode_transaction(tx_hex)
+        assert (decoded["tx_type"], decoded["tx_type_name"]) == (tx_type, name)
+        assert platform_rust.DecodedTx(tx_hex).tx_type == tx_type
+
+
+def test_decode_transaction_returns_access_list():
+    decoded = platform_rust.decode_transaction(EIP2930_TX)
+
+    assert decoded["access_list"] == [
+        {
+            "address": "0x1111111111111111111111111111111111111111",
+            "storage_keys": ["0x" + "00" * 32, "0x" + "00" * 31 + "01"],
+        }
+    ]
+    assert platform_rust.DecodedTx(EIP2930_TX).access_list == decoded["access_list"]
+    assert platform_rust.decode_transaction(LEGACY_TX)["access_list"] == []
+    assert platform_rust.decode_transaction(EIP1559_TX)["access_list"] == []
+
+
+def test_dynamic_fee_transactions_report_both_fee_caps():
+    legacy = platform_rust.decode_transaction(LEGACY_TX)
+    assert legacy["gas_price"] == "20000000000"
+    assert legacy["max_fee_per_gas"] is None
+    assert legacy["max_priority_fee_per_gas"] is None
+
+    dynamic = platform_rust.decode_transaction(EIP1559_TX)
+    assert dynamic["gas_price"] is None
+    assert dynamic["max_fee_per_gas"] == "40000000000"
+    assert dynamic["max_priority_fee_per_gas"] == "2000000000"
+    for tx_hex in (EIP4844_TX, EIP7702_TX):
+        decoded = platform_rust.decode_transaction(tx_hex)
+        assert decoded["gas_price"] is None
+        assert int(decoded["max_fee_per_gas"]) >= int(decoded["max_priority_fee_per_gas"])
+
+
+def test_blob_fields_only_on_eip4844_transactions():
+    decoded = platform_rust.decode_transaction(EIP4844_TX)
+
+    assert decoded["blob_versioned_hashes"] == ["0x01" + "00" * 30 + "01", "0x01" + "00" * 30 + "02"]
+    assert decoded["max_fee_per_blob_gas"] == "7000000000"
+    for tx_hex in (LEGACY_TX, EIP1559_TX, EIP7702_TX):
+        other = platform_rust.decode_transaction(tx_hex)
+        assert "blob_versioned_hashes" not in other
+        assert "max_fee_per_blob_gas" not in other
+
+
+def test_decode_transaction_returns_eip7702_authorizations():
+    decoded = platform_rust.decode_transaction(EIP7702_TX)
+
+    [auth] = decoded["authorization_list"]
+    assert auth["chain_id"] == 1
+    assert auth["address"] == "0x" + "22" * 20
+    assert auth["nonce"] == 7
+    assert auth["y_parity"] in (0, 1)
+    assert len(auth["r"]) == len(auth["s"]) == 66
+    assert auth["authority"] == "0x19E7E376E7C213B7E7e7e46cc70A5dD086DAff2A"
+    assert "authorization_list" not in platform_rust.decode_transaction(EIP1559_TX)
+
+
+def test_decode_transaction_reports_chain_id_and_signature():
+    legacy = platform_rust.decode_transaction(LEGACY_TX)
+    assert legacy["chain_id"] == 1
+    assert legacy["signature"] == {
+        "r": "0x28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276",
+        "s": "0x67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        "y_parity": 0,
+        "v": 37,
+    }
+
+    pre155 = platform_rust.decode_transaction(LEGACY_PRE155_TX)
+    assert pre155["chain_id"] is None
+    assert pre155["signature"]["v"] == 28
+
+    dynamic = platform_rust.decode_transaction(EIP1559_TX)
+    assert dynamic["chain_id"] == 1
+    assert dynamic["signature"] == {
+        "r": "0xdc2f233fc726c8efb5cf06927527dd45ac7845eacdd3da822033644bd677201c",
+        "s": "0x4a55be67abceccf8b2b76054f5f18085f07948611361c4c6d9a92b391b7e1518",
+        "y_parity": 0,
+    }
+
+
+def test_decode_transaction_survives_unrecoverable_signature():
+    # EIP1559_TX with r above the curve order, which no key can produce
+    r = "dc2f233fc726c8efb5cf06927527dd45ac7845eacdd3da822033644bd677201c"
+    corrupted = EIP1559_TX.replace(r, "ff" * 32)
+    expected = platform_rust.decode_transaction(EIP1559_TX)
+
+    decoded = platform_rust.decode_transaction(corrupted)
+
+    assert decoded["from"] is None
+    for key in ("to", "nonce", "gas", "value", "input", "max_fee_per_gas"):
+        assert decoded[key] == expected[key]
+    unrecovered = platform_rust.decode_transaction(EIP1559_TX, recover_sender=False)
+    assert unrecovered["from"] is None
+    assert unrecovered["hash"] == expected["hash"]
+
+
+def test_decode_calldata_erc20_transfer():
+    input_hex = platform_rust.decode_transaction(ERC20_TRANSFER_TX)["input"]
+
+    recipient, amount = platform_rust.decode_calldata(input_hex, "transfer(address,uint256)")
+
+    assert recipient == "0x" + "11" * 20
+    assert amount == 100
+    with pytest.raises(ValueError, match="selector mismatch"):
+        platform_rust.decode_calldata(input_hex, "approve(address,uint256)")
+
+
+def test_decode_calldata_dynamic_parameters():
+    # f(uint256[],string,bool) with ([1, 2], "hi", True); head holds two offsets and the bool
+    words = [0x60, 0xC0, 1, 2, 1, 2, 2]
+    body = b"".join(w.to_bytes(32, "big") for w in words) + b"hi".ljust(32, b"\0")
+
+    values = platform_rust.decode_calldata("0x893f2ed5" + body.hex(), "f(uint[],string,bool)")
+
+    assert values == [[1, 2], "hi", True]
+
+
+def test_decode_calldata_rejects_oversized_fixed_arrays():
+    with pytest.raises(ValueError, match="exceeds the calldata"):
+        platform_rust.decode_calldata("0xf03f19e5" + "00" * 32, "f(uint256[1000000000000000000])")
+    # An empty outer array decodes, but the head size of its elements overflows
+    with pytest.raises(ValueError, match="too large"):
+        platform_rust.decode_calldata("0xf31d8d80" + "00" * 32, "f(uint256[576460752303423488][0])")
+    assert platform_rust.decode_calldata("0xe9b69258" + "00" * 31 + "01" + "00" * 32, "f(bool[2])") == [[True, False]]
+
+
+def test_checksum_address_matches_eip55_vectors():
+    vectors = [
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ]
+    for expected in vectors:
+        assert platform_rust.checksum_address(expected.lower()) == expected
+        assert platform_rust.checksum_address(expected[2:].upper()) == expected
+    with pytest.raises(ValueError):
+        platform_rust.checksum_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA")
+
+    decoded = platform_rust.decode_transaction(LEGACY_TX)
+    assert decoded["from"] == "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"
+
+
+def test_contract_creation_reports_created_address():
+    decoded = platform_rust.decode_transaction(CREATE_TX)
+
+    assert decoded["to"] is None
+    # keccak256(rlp([0x9d8a...5a4f, 5]))[12:]
+    assert decoded["created_contract"] == "0x856ae2B3580e976b1dD6dBB78a6cB12a8DA28c29"
+    assert "created_contract" not in platform_rust.decode_transaction(EIP1559_TX)
+
+
+async def read_client_frame(reader):
+    header = await reader.readexactly(2)
+    length = header[1] & 0x7F
+    if length == 126:
+        length = int.from_bytes(await reader.readexactly(2), "big")
+    elif length == 127:
+        length = int.from_bytes(await reader.readexactly(8), "big")
+    mask = await reader.readexactly(4)
+    payload = await reader.readexactly(length)
+    return bytes(b ^ mask[i % 4] for i, b in enumerate(payload)).decode()
+
+
+def server_text_frame(message):
+    payload = json.dumps(message).encode()
+    if len(payload) < 126:
+        return bytes([0x81, len(payload)]) + payload
+    return bytes([0x81, 126]) + len(payload).to_bytes(2, "big") + payload
+
+
+async def accept_websocket(reader, writer):
+    request = (await reader.readuntil(b"\r\n\r\n")).decode()
+    key = next(
+        line.split(":", 1)[1].strip()
+        for line in request.split("\r\n")
+        if line.lower().startswith("sec-websocket-key")
+    )
+    accept = base64.b64encode(
+        hashlib.sha1((key + "258EAFA5-E914-47DA-95CA-C5AB0DC85B11").encode()).digest()
+    ).decode()
+    writer.write(
+        (
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n"
+            f"Connection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+        ).encode()
+    )
+
+
+async def test_subscribe_new_heads_reconnects_after_disconnect():
+    connections = []
+
+    async def node(reader, writer):
+        connections.append(writer)
+        await accept_websocket(reader, writer)
+        subscribe = json.loads(await read_client_frame(reader))
+        assert subscribe["method"] == "eth_subscribe" and subscribe["params"] == ["newHeads"]
+        writer.write(server_text_frame({"jsonrpc": "2.0", "id": subscribe["id"], "result": "0x1"}))
+        if len(connections) > 2:
+            await writer.drain()
+            return
+        header = {
+            "number": hex(len(connections)),
+            "hash": "0x" + "ab" * 32,
+            "miner": "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed",
+            "baseFeePerGas": "0x3b9aca00",
+        }
+        params = {"subscription": "0x1", "result": header}
+        writer.write(server_text_frame({"jsonrpc": "2.0", "method": "eth_subscription", "params": params}))
+        await writer.drain()
+        # Drop the connection without a close frame
+        writer.close()
+
+    server = await asyncio.start_server(node, "127.0.0.1", 0)
+    port = server.sockets[0].getsockname()[1]
+    subscription = platform_rust.subscribe_new_heads(f"ws://127.0.0.1:{port}")
+
+    first = await asyncio.wait_for(subscription.__anext__(), 5)
+    second = await asyncio.wait_for(subscription.__anext__(), 5)
+
+    assert (first["number"], second["number"]) == (1, 2)
+    assert first["base_fee_per_gas"] == 10**9
+    assert first["miner"] == "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+    assert first["gas_used"] is None
+    subscription.stop()
+    with pytest.raises(StopAsyncIteration):
+        await asyncio.wait_for(subscription.__anext__(), 5)
+    server.close()
+    with pytest.raises(ValueError):
+        platform_rust.subscribe_new_heads("https://127.0.0.1")
+    with pytest.raises(ValueError):
+        platform_rust.subscribe_new_heads(f"ws://127.0.0.1:{port}", idle_timeout_secs=0.0)
+    # TLS endpoints are accepted
+    platform_rust.subscribe_new_heads("wss://127.0.0.1:1").stop()
+
+
+def new_heads_notification(number):
+    params = {"subscription": "0x1", "result": {"number": hex(number), "hash": "0x" + "ab" * 32}}
+    return server_text_frame({"jsonrpc": "2.0", "method": "eth_subscription", "params": params})
+
+
+async def test_cancelled_anext_keeps_the_header_for_the_next_one():
+    release = asyncio.Event()
+
+    async def node(reader, writer):
+        await accept_websocket(reader, writer)
+        subscribe = json.loads(await read_client_frame(reader))
+        writer.write(server_text_frame({"jsonrpc": "2.0", "id": subscribe["id"], "result": "0x1"}))
+        await release.wait()
+        writer.write(new_heads_notification(1) + new_heads_notification(2))
+        await writer.drain()
+        await asyncio.sleep(10)
+
+    server = await asyncio.start_server(node, "127.0.0.1", 0)
+    port = server.sockets[0].getsockname()[1]
+    subscription = platform_rust.subscribe_new_heads(f"ws://127.0.0.1:{port}")
+
+    with pytest.raises(asyncio.TimeoutError):
+        await asyncio.wait_for(subscription.__anext__(), 0.2)
+    release.set()
+    first = await asyncio.wait_for(subscription.__anext__(), 5)
+    second = await asyncio.wait_for(subscription.__anext__(), 5)
+
+    assert (first["number"], second["number"]) == (1, 2)
+    subscription.stop()
+    server.close()
+
+
+async def test_silent_connection_is_dropped_and_reconnected():
+    connections = []
+
+    async def node(reader, writer):
+        connections.append(writer)
+        await accept_websocket(reader, writer)
+        subscribe = json.loads(await read_client_frame(reader))
+        writer.write(server_text_frame({"jsonrpc": "2.0", "id": subscribe["id"], "result": "0x1"}))
+        if len(connections) > 1:
+            writer.write(new_heads_notification(len(connections)))
+        await writer.drain()
+        # Half-open: keep the socket but never answer pings
+        await asyncio.sleep(10)
+
+    server = await asyncio.start_server(node, "127.0.0.1", 0)
+    port = server.sockets[0].getsockname()[1]
+    subscription = platform_rust.subscribe_new_heads(f"ws://127.0.0.1:{port}", idle_timeout_secs=0.2)
+
+    header = await asyncio.wait_for(subscription.__anext__(), 5)
+
+    assert header["number"] == 2
+    subscription.stop()
+    server.close()
+
+
+async def test_pending_transactions_are_filtered_before_the_callback():
+    raw_by_hash = {"0x01": EIP1559_TX, "0x02": LEGACY_TX, "0x03": ERC20_TRANSFER_TX, "0x04": None}
+    disconnected = asyncio.Event()
+
+    async def node(reader, writer):
+        await accept_websocket(reader, writer)
+        subscribe = json.loads(await read_client_frame(reader))
+        assert subscribe["params"] == ["newPendingTransactions"]
+        writer.write(server_text_frame({"jsonrpc": "2.0", "id": subscribe["id"], "result": "0x9"}))
+        for tx_hash in raw_by_hash:
+            params = {"subscription": "0x9", "result": tx_hash}
+            writer.write(server_text_frame({"jsonrpc": "2.0", "method": "eth_subscription", "params": params}))
+        await writer.drain()
+        while True:
+            try:
+                request = json.loads(await read_client_frame(reader))
+            except asyncio.IncompleteReadError:
+                disconnected.set()
+                return
+            assert request["method"] == "eth_getRawTransactionByHash"
+            result = raw_by_hash[request["params"][0]]
+            writer.write(server_text_frame({"jsonrpc": "2.0", "id": request["id"], "result": result}))
+            await writer.drain()
+
+    server = await asyncio.start_server(node, "127.0.0.1", 0)
+    port = server.sockets[0].getsockname()[1]
+    received = []
+    subscription = platform_rust.subscribe_pending_transactions(
+        f"ws://127.0.0.1:{port}",
+        received.append,
+        {"to": ["0x" + "35" * 20], "min_value": 10**17},
+    )
+
+    for _ in range(500):
+        if received:
+            break
+        await asyncio.sleep(0.01)
+    # Give the remaining transactions time to be (wrongly) delivered
+    await asyncio.sleep(0.2)
+
+    assert [tx["hash"] for tx in received] == [platform_rust.decode_transaction(LEGACY_TX)["hash"]]
+    assert received[0]["value"] == str(10**18)
+    assert subscription.is_running()
+    subscription.stop()
+    assert not subscription.is_running()
+    await asyncio.wait_for(disconnected.wait(), 5)
+    server.close()
+
+
+def test_decoded_tx_survives_unrecoverable_signature():
+    r = "dc2f233fc726c8efb5cf06927527dd45ac7845eacdd3da822033644bd677201c"
+    tx = platform_rust.DecodedTx(EIP1559_TX.replace(r, "ff" * 32))
+
+    assert tx.from_ is None
+    assert tx.nonce == platform_rust.DecodedTx(EIP1559_TX).nonce
+    assert tx.to_dict()["from"] is None
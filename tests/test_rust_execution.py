@@ -0,0 +1,1035 @@
+import asyncio
+import json
+import os
+import subprocess
+import sys
+import time
+
+import pytest
+
+from rust_helpers import make_pool
+
+platform_rust = pytest.importorskip("platform_rust")
+
+
+def make_engine(pools):
+    engine = platform_rust.ExecutionEngine()
+    engine.update_pools(pools)
+    return engine
+
+
+def test_constant_product_output_matches_known_values():
+    def expected(amount_in, reserve_in, reserve_out, fee=3000):
+        with_fee = amount_in * (1_000_000 - fee)
+        return reserve_out * with_fee // (reserve_in * 1_000_000 + with_fee)
+
+    engine = make_engine([make_pool("p1", "a", "b", 1_000_000, 2_000_000)])
+    forward = engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": 1_000})
+    backward = engine.optimize_route({"token_in": "b", "token_out": "a", "amount_in": 1_000})
+    assert int(forward["output_amount"]) == expected(1_000, 1_000_000, 2_000_000) == 1_992
+    assert int(backward["output_amount"]) == expected(1_000, 2_000_000, 1_000_000) == 498
+
+    # Products of these exceed u128 and must not overflow
+    whale = 10**36
+    engine = make_engine([make_pool("p1", "a", "b", whale, whale)])
+    route = engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": 10**30})
+    assert int(route["output_amount"]) == expected(10**30, whale, whale)
+
+    # Amounts and reserves beyond u128, as ints or decimal strings
+    wide = 10**45
+    assert wide > 2**128
+    engine = make_engine([{**make_pool("p1", "a", "b", wide, 2 * wide), "reserve1": str(2 * wide)}])
+    for amount_in in (10**40, str(10**40)):
+        route = engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": amount_in})
+        assert route["output_amount"] == str(expected(10**40, wide, 2 * wide))
+    assert engine.get_pool("p1")["reserve1"] == str(2 * wide)
+    with pytest.raises(ValueError, match="non-negative"):
+        engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": -1})
+    with pytest.raises(ValueError, match="invalid amount"):
+        engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": "1e18"})
+
+
+def test_pools_without_reserves_fall_back_to_liquidity(capfd):
+    platform_rust.initialize_rust_runtime()
+    legacy = make_pool("p1", "a", "b", 10**6, 10**6)
+    del legacy["reserve0"], legacy["reserve1"]
+
+    fallback = make_engine([legacy])
+    assert "splitting liquidity evenly" in "".join(capfd.readouterr())
+    explicit = make_engine([make_pool("p1", "a", "b", 10**6, 10**6)])
+    assert "splitting liquidity evenly" not in "".join(capfd.readouterr())
+
+    params = {"token_in": "a", "token_out": "b", "amount_in": 1_000}
+    assert fallback.optimize_route(params)["output_amount"] == explicit.optimize_route(params)["output_amount"]
+
+
+def test_reserves_override_changes_quote_without_persisting():
+    engine = make_engine([make_pool("p1", "a", "b", 1_000_000, 1_000_000)])
+    params = {"token_in": "a", "token_out": "b", "amount_in": 1_000}
+
+    baseline = engine.optimize_route(params)
+    overridden = engine.optimize_route({**params, "reserves_override": {"p1": (1_000_000, 4_000_000)}})
+    after = engine.optimize_route(params)
+
+    assert int(overridden["output_amount"]) > int(baseline["output_amount"])
+    assert after["output_amount"] == baseline["output_amount"]
+
+
+def test_twap_schedule_sums_to_total_and_spaces_timestamps():
+    engine = make_engine([make_pool("p1", "a", "b", 10_000_000, 10_000_000)])
+
+    for profile in ("even", "front", "back"):
+        slices = engine.twap_schedule(1_001, 4, 1_000, 250, profile=profile)
+        assert sum(int(s["amount"]) for s in slices) == 1_001
+        assert [s["ts"] for s in slices] == [1_000, 1_250, 1_500, 1_750]
+
+    front = engine.twap_schedule(1_000, 4, 0, 1, profile="front")
+    assert int(front[0]["amount"]) > int(front[-1]["amount"])
+
+    quoted = engine.twap_schedule(1_000, 2, 0, 1, token_in="a", token_out="b")
+    assert all(0 < int(s["expected_output"]) <= int(s["amount"]) for s in quoted)
+
+
+def test_twap_schedule_rejects_overflowing_timestamps():
+    engine = make_engine([])
+
+    assert engine.twap_schedule(2, 2, 2**64 - 2, 1)[-1]["ts"] == 2**64 - 1
+    with pytest.raises(ValueError):
+        engine.twap_schedule(3, 3, 2**64 - 2, 1)
+    with pytest.raises(ValueError):
+        engine.twap_schedule(3, 3, 0, 2**63)
+
+
+def test_precompute_hubs_keeps_routes_correct():
+    pools = [
+        make_pool("p1", "hub", "a", 1_000_000, 1_000_000),
+        make_pool("p2", "a", "b", 1_000_000, 1_000_000),
+        make_pool("p3", "x", "y", 1_000_000, 1_000_000),
+    ]
+    engine = make_engine(pools)
+    params = {"token_in": "hub", "token_out": "b", "amount_in": 1_000}
+    before = engine.optimize_route(params)
+
+    engine.precompute_hubs(["hub"])
+
+    assert engine.optimize_route(params) == before
+    assert before["path"] == ["hub", "a", "b"]
+    assert engine.optimize_route({**params, "token_out": "y"}) is None
+
+    # Pool changes invalidate the hop table, so newly connected tokens route again
+    engine.update_pools(pools + [make_pool("p4", "b", "x", 1_000_000, 1_000_000)])
+    assert engine.optimize_route({**params, "token_out": "y"})["path"] == ["hub", "a", "b", "x", "y"]
+
+    # A hub given by symbol prunes with the same matching the search uses
+    engine.precompute_hubs(["HUB"])
+    by_symbol = engine.optimize_route({**params, "token_in": "HUB"})
+    assert by_symbol["path"] == ["HUB", "a", "b"]
+    assert by_symbol["output_amount"] == before["output_amount"]
+
+
+def test_equal_parallel_pools_pick_smallest_address():
+    for order in (["0xbb", "0xaa", "0xcc"], ["0xcc", "0xbb", "0xaa"]):
+        engine = make_engine([make_pool(addr, "a", "b", 1_000_000, 1_000_000) for addr in order])
+        for _ in range(5):
+            route = engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": 1_000})
+            assert route["pools"] == ["0xaa"]
+
+
+async def test_best_route_across_picks_higher_output_engine():
+    thin = make_engine([make_pool("p1", "a", "b", 1_000_000, 1_000_000)])
+    deep = make_engine([make_pool("p2", "a", "b", 1_000_000, 3_000_000)])
+    params = {"token_in": "a", "token_out": "b", "amount_in": 1_000, "min_output_amount": 2_000}
+
+    best, unreachable = await asyncio.gather(
+        platform_rust.best_route_across([thin, deep], params),
+        platform_rust.best_route_across([thin], {**params, "token_out": "missing"}),
+    )
+
+    assert best.pop("engine_id") == deep.engine_id
+    assert best == deep.optimize_route(params)
+    assert best["meets_minimum"] and best["hops"]
+    assert unreachable is None
+
+
+def test_rebalance_quote_moves_weights_toward_target():
+    engine = make_engine([make_pool("p1", "eth", "usd", 1_000_000_000, 2_000_000_000_000, fee=0)])
+
+    def eth_weight(holdings):
+        eth_value = int(holdings.get("eth", 0)) * 2_000
+        return eth_value / (eth_value + int(holdings.get("usd", 0)))
+
+    current = {"eth": 1_000_000, "usd": 0}
+    quote = engine.rebalance_quote(current, {"eth": 0.5, "usd": 0.5}, "usd")
+
+    assert [(s["token_in"], s["token_out"]) for s in quote["swaps"]] == [("eth", "usd")]
+    assert abs(eth_weight(quote["holdings"]) - 0.5) < abs(eth_weight(current) - 0.5)
+    assert eth_weight(quote["holdings"]) == pytest.approx(0.5, abs=0.01)
+
+
+def test_rebalance_quote_zero_weight_sells_the_whole_holding():
+    engine = make_engine([
+        make_pool("p1", "eth", "usd", 10**9, 2 * 10**12, fee=0),
+        make_pool("p2", "btc", "usd", 10**9, 3 * 10**13, fee=0),
+    ])
+
+    quote = engine.rebalance_quote({"eth": 52_319_253, "usd": 464_680_097}, {"eth": 0.0, "usd": 1.0}, "usd")
+
+    assert [(s["token_in"], s["amount_in"]) for s in quote["swaps"]] == [("eth", "52319253")]
+    assert quote["holdings"]["eth"] == "0"
+
+    # Selling everything into the base token leaves nothing else behind
+    current = {"eth": 123_457, "btc": 7_654_321}
+    everything = engine.rebalance_quote(current, {"usd": 1.0}, "usd")
+    sold = {s["token_in"]: int(s["amount_in"]) for s in everything["swaps"]}
+    assert sold == current
+    assert everything["holdings"]["eth"] == everything["holdings"]["btc"] == "0"
+    proceeds = sum(int(s["route"]["output_amount"]) for s in everything["swaps"])
+    assert everything["holdings"]["usd"] == str(proceeds)
+
+    with pytest.raises(ValueError):
+        engine.rebalance_quote(current, {"usd": float("nan")}, "usd")
+
+
+def test_slow_route_threshold_logs_only_slow_routes(capfd):
+    platform_rust.initialize_rust_runtime()
+    engine = make_engine([make_pool("p1", "a", "b", 1_000_000, 1_000_000)])
+    params = {"token_in": "a", "token_out": "b", "amount_in": 1_000}
+
+    engine.slow_route_threshold_ms = 60_000
+    engine.optimize_route(params)
+    assert "slow route computation" not in "".join(capfd.readouterr())
+
+    # Any measurable compute time exceeds a zero threshold (new amount: not cached)
+    engine.slow_route_threshold_ms = 0
+    engine.optimize_route({**params, "amount_in": 2_000})
+    assert "slow route computation" in "".join(capfd.readouterr())
+
+
+def test_route_confidence_tracks_liquidity_consumed():
+    deep = make_engine([
+        make_pool("p1", "a", "b", 10**12, 10**12),
+        make_pool("p2", "b", "c", 10**12, 10**12),
+    ])
+    thin = make_engine([
+        make_pool("p1", "a", "b", 10_000, 10_000),
+        make_pool("p2", "b", "c", 10_000, 10_000),
+    ])
+
+    small = deep.optimize_route({"token_in": "a", "token_out": "c", "amount_in": 1_000})
+    large = thin.optimize_route({"token_in": "a", "token_out": "c", "amount_in": 50_000})
+
+    assert small["confidence"] > 0.99
+    assert large["confidence"] < 0.2
+
+
+def test_rebated_pool_is_preferred_over_identical_pool():
+    plain = make_pool("0xaa", "a", "b", 1_000_000, 1_000_000)
+    rebated = {**make_pool("0xbb", "a", "b", 1_000_000, 1_000_000), "rebate_bps": 10}
+    engine = make_engine([plain, rebated])
+
+    route = engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": 10_000})
+
+    assert route["pools"] == ["0xbb"]
+    with pytest.raises(ValueError):
+        engine.update_pools([{**plain, "rebate_bps": 31}])
+
+
+def test_via_list_forces_hubs_in_order():
+    engine = make_engine([
+        make_pool("direct", "a", "d", 10**9, 10**9),
+        make_pool("p1", "a", "h1", 10**6, 10**6),
+        make_pool("p2", "h1", "h2", 10**6, 10**6),
+        make_pool("p3", "h2", "d", 10**6, 10**6),
+    ])
+    params = {"token_in": "a", "token_out": "d", "amount_in": 1_000}
+
+    assert engine.optimize_route(params)["path"] == ["a", "d"]
+    assert engine.optimize_route({**params, "via": ["h1", "h2"]})["path"] == ["a", "h1", "h2", "d"]
+    assert engine.optimize_route({**params, "via": ["h1", "missing"]}) is None
+
+
+def test_max_pool_age_excludes_stale_pools():
+    now_ms = int(time.time() * 1000)
+    engine = make_engine([
+        {**make_pool("fresh", "a", "b", 1_000_000, 1_000_000), "last_updated": now_ms},
+        {**make_pool("stale", "b", "c", 1_000_000, 1_000_000), "last_updated": now_ms - 60_000},
+    ])
+    params = {"token_in": "a", "token_out": "c", "amount_in": 1_000}
+
+    assert engine.optimize_route(params)["pools"] == ["fresh", "stale"]
+    assert engine.optimize_route({**params, "max_pool_age_ms": 120_000}) is not None
+    assert engine.optimize_route({**params, "max_pool_age_ms": 30_000}) is None
+
+
+def test_gas_priced_in_input_token_nets_output():
+    engine = make_engine([make_pool("p1", "usdc", "dai", 10**12, 10**12, fee=0)])
+    params = {"token_in": "usdc", "token_out": "dai", "amount_in": 1_000_000}
+
+    # 5e12 wei of gas at 1e7 wei per raw usdc unit costs 500_000 usdc units
+    route = engine.optimize_route({**params, "gas_cost_wei": 5 * 10**12, "eth_per_input_token": 1e7})
+    half = engine.optimize_route({**params, "amount_in": 500_000})
+
+    assert route["gas_cost_input"] == "500000"
+    assert route["net_output"] == half["output_amount"]
+    assert int(route["net_output"]) < int(route["output_amount"])
+    assert "net_output" not in engine.optimize_route(params)
+
+
+def test_best_hub_picks_highest_output_hub():
+    engine = make_engine([
+        make_pool("p1", "a", "weth", 10**6, 10**6),
+        make_pool("p2", "weth", "d", 10**6, 10**6),
+        make_pool("p3", "a", "usdc", 10**9, 10**9),
+        make_pool("p4", "usdc", "d", 10**9, 10**9),
+    ])
+
+    best = engine.best_hub("a", "d", 1_000, ["weth", "usdc", "missing"])
+
+    assert best["hub"] == "usdc"
+    assert best["route"]["path"] == ["a", "usdc", "d"]
+    assert engine.best_hub("a", "d", 1_000, ["missing"]) is None
+
+
+def test_is_routable_respects_hop_limit():
+    engine = make_engine([
+        make_pool("p1", "a", "b", 10**6, 10**6),
+        make_pool("p2", "b", "c", 10**6, 10**6),
+        make_pool("p3", "c", "d", 10**6, 10**6),
+        make_pool("p4", "x", "y", 10**6, 10**6),
+    ])
+
+    assert engine.is_routable("a", "d", 3)
+    assert not engine.is_routable("a", "d", 2)
+    assert not engine.is_routable("a", "x", 10)
+
+    engine.precompute_hubs(["a"])
+    assert engine.is_routable("a", "c", 2)
+    assert not engine.is_routable("a", "d", 2)
+
+    # Symbols resolve like they do for optimize_route, with or without a hop table
+    assert engine.is_routable("B", "d", 2)
+    assert not engine.is_routable("B", "d", 1)
+    engine.precompute_hubs(["B"])
+    assert engine.is_routable("B", "d", 2)
+    assert engine.is_routable("B", "a", 1)
+
+
+def test_route_cache_export_import_respects_generation():
+    params = {"token_in": "a", "token_out": "b", "amount_in": 1_000}
+    source = make_engine([make_pool("p1", "a", "b", 10**6, 10**6)])
+    cached = source.optimize_route(params)
+    data = source.export_route_cache()
+    assert isinstance(data, bytes)
+
+    # Same generation, different reserves: the imported route is served as a hit
+    warm = make_engine([make_pool("p1", "a", "b", 10**6, 2 * 10**6)])
+    assert warm.pool_generation == source.pool_generation
+    assert warm.import_route_cache(data, source.pool_generation)
+    assert warm.optimize_route(params)["output_amount"] == cached["output_amount"]
+
+    stale = make_engine([make_pool("p1", "a", "b", 10**6, 2 * 10**6)])
+    stale.update_pools([make_pool("p1", "a", "b", 10**6, 2 * 10**6)])
+    assert not stale.import_route_cache(data, source.pool_generation)
+    assert int(stale.optimize_route(params)["output_amount"]) > int(cached["output_amount"])
+
+
+
+def test_route_cache_hits_until_pools_change_and_evicts_lru():
+    pools = [make_pool("p1", "a", "b", 10**6, 10**6)]
+    engine = platform_rust.ExecutionEngine(route_cache_capacity=2)
+    engine.update_pools(pools)
+    params = {"token_in": "a", "token_out": "b", "amount_in": 1_000}
+
+    engine.optimize_route(params)
+    engine.optimize_route(params)
+    assert engine.cache_stats() == {"hits": 1, "misses": 1, "size": 1, "capacity": 2}
+
+    engine.update_pools(pools)
+    engine.optimize_route(params)
+    assert engine.cache_stats()["misses"] == 2
+
+    # Refreshing 1_000 makes 2_000 the least recently used entry
+    engine.optimize_route({**params, "amount_in": 2_000})
+    engine.optimize_route(params)
+    engine.optimize_route({**params, "amount_in": 3_000})
+    engine.optimize_route(params)
+    engine.optimize_route({**params, "amount_in": 2_000})
+    assert engine.cache_stats() == {"hits": 3, "misses": 5, "size": 2, "capacity": 2}
+
+    engine.clear_cache()
+    assert engine.cache_stats()["size"] == 0
+    disabled = platform_rust.ExecutionEngine(route_cache_capacity=0)
+    disabled.update_pools(pools)
+    disabled.optimize_route(params)
+    disabled.optimize_route(params)
+    assert disabled.cache_stats() == {"hits": 0, "misses": 0, "size": 0, "capacity": 0}
+
+
+def test_optimize_route_without_pools_raises_instead_of_returning_none():
+    engine = platform_rust.ExecutionEngine()
+    params = {"token_in": "a", "token_out": "b", "amount_in": 1_000}
+
+    with pytest.raises(RuntimeError, match="no pools loaded"):
+        engine.optimize_route(params)
+    for call in (
+        lambda: engine.optimize_split_route(params),
+        lambda: engine.route_tradeoff(params),
+        lambda: engine.best_hub("a", "b", 1_000, ["c"]),
+        lambda: engine.is_routable("a", "b", 3),
+        lambda: engine.max_size_for_slippage("a", "b", 50.0),
+        lambda: platform_rust.best_route_across([engine], params),
+    ):
+        with pytest.raises(RuntimeError, match="no pools loaded"):
+            call()
+    # Loaded but unreachable is still a plain None
+    engine.update_pools([make_pool("p1", "a", "c", 10**6, 10**6)])
+    assert engine.optimize_route(params) is None
+
+
+def test_token_addresses_are_validated_and_case_normalized():
+    weth, usdc = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"
+    pool = make_pool("p1", weth, usdc, 10**6, 10**6)
+    pool["token0"]["symbol"] = "WETH"
+    engine = make_engine([pool])
+
+    # Any casing of the same address matches, and routes report lowercase
+    route = engine.optimize_route({"token_in": weth.lower(), "token_out": usdc.upper()[2:], "amount_in": 1_000})
+    assert route["path"] == [weth.lower(), usdc.lower()]
+    assert engine.spot_price(weth, usdc.lower()) == pytest.approx(1.0)
+
+    short = "0x" + "ab" * 19
+    with pytest.raises(ValueError, match="malformed token address"):
+        engine.optimize_route({"token_in": short, "token_out": usdc, "amount_in": 1_000})
+    # A rejected refresh leaves the previous pools in place
+    with pytest.raises(ValueError, match="malformed token address"):
+        engine.update_pools([pool, make_pool("p2", short, usdc, 10**6, 10**6)])
+    assert engine.pool_count() == 1
+    # Non-address identifiers still match by symbol
+    by_symbol = engine.optimize_route({"token_in": "WETH", "token_out": usdc, "amount_in": 1_000})
+    assert by_symbol["output_amount"] == route["output_amount"]
+
+def test_token_caps_force_alternate_intermediate():
+    engine = make_engine([
+        make_pool("p1", "a", "weth", 10**9, 10**9),
+        make_pool("p2", "weth", "d", 10**9, 10**9),
+        make_pool("p3", "a", "usdc", 10**6, 10**6),
+        make_pool("p4", "usdc", "d", 10**6, 10**6),
+    ])
+    params = {"token_in": "a", "token_out": "d", "amount_in": 10_000}
+
+    assert engine.optimize_route(params)["path"] == ["a", "weth", "d"]
+    capped = engine.optimize_route({**params, "token_caps": {"weth": 5_000}})
+    assert capped["path"] == ["a", "usdc", "d"]
+    assert engine.optimize_route({**params, "token_caps": {"weth": 5_000, "usdc": 5_000}}) is None
+
+
+def test_route_canonical_bytes_are_deterministic():
+    engine = make_engine([
+        make_pool("p1", "a", "b", 10**6, 10**6),
+        make_pool("p2", "b", "c", 10**6, 10**6),
+    ])
+    route = engine.optimize_route({"token_in": "a", "token_out": "c", "amount_in": 1_000})
+
+    data, digest = engine.route_canonical_bytes(route)
+    reordered = {key: route[key] for key in reversed(list(route))}
+
+    assert engine.route_canonical_bytes(route) == (data, digest)
+    assert engine.route_canonical_bytes(reordered) == (data, digest)
+    assert data[0] == 1
+    assert data[-64:] == (1_000).to_bytes(32, "big") + int(route["output_amount"]).to_bytes(32, "big")
+    assert digest.startswith("0x") and len(digest) == 66
+    changed = {**route, "output_amount": int(route["output_amount"]) - 1}
+    assert engine.route_canonical_bytes(changed)[1] != digest
+    assert engine.route_canonical_bytes({**route, "amount_in": 999})[1] != digest
+    # Legacy int amounts commit to the same bytes
+    assert engine.route_canonical_bytes({**route, "output_amount": int(route["output_amount"])}) == (data, digest)
+    # Amounts past u128 keep every byte of the U256 word
+    wide = engine.route_canonical_bytes({**route, "amount_in": 2**200 + 1, "output_amount": 2**128})[0]
+    assert wide[-64:] == (2**200 + 1).to_bytes(32, "big") + (2**128).to_bytes(32, "big")
+    with pytest.raises(ValueError):
+        engine.route_canonical_bytes({**route, "output_amount": 2**256})
+
+
+def test_price_after_trade_moves_further_with_size():
+    engine = make_engine([make_pool("p1", "eth", "usd", 10**6, 2 * 10**9)])
+
+    spot = engine.price_after_trade("p1", "eth", 0)
+    small = engine.price_after_trade("p1", "eth", 1_000)
+    large = engine.price_after_trade("p1", "eth", 100_000)
+
+    assert spot == pytest.approx(2_000.0)
+    assert large < small < spot
+    assert engine.price_after_trade("p1", "eth", 0) == spot
+    with pytest.raises(KeyError):
+        engine.price_after_trade("missing", "eth", 1)
+
+
+def test_route_tradeoff_returns_long_and_short_routes():
+    engine = make_engine([
+        make_pool("direct", "a", "d", 10**6, 10**6),
+        make_pool("p1", "a", "b", 10**9, 10**9),
+        make_pool("p2", "b", "c", 10**9, 10**9),
+        make_pool("p3", "c", "d", 10**9, 10**9),
+    ])
+    params = {"token_in": "a", "token_out": "d", "amount_in": 10_000}
+
+    tradeoff = engine.route_tradeoff(params)
+
+    assert len(tradeoff["price_optimal"]["hops"]) == 3
+    assert len(tradeoff["gas_optimal"]["hops"]) == 1
+    assert tradeoff["gas_optimal"]["pools"] == ["direct"]
+    assert int(tradeoff["price_optimal"]["net_output"]) > int(tradeoff["gas_optimal"]["net_output"])
+
+    priced = engine.route_tradeoff({**params, "gas_per_hop": 200, "token_price_in_gas": 1.0})
+    assert priced["price_optimal"]["pools"] == tradeoff["price_optimal"]["pools"]
+    assert (priced["price_optimal"]["gas_cost_input"], priced["gas_optimal"]["gas_cost_input"]) == ("600", "200")
+    assert int(priced["price_optimal"]["net_output"]) < int(priced["gas_optimal"]["net_output"])
+
+
+def test_route_tradeoff_nets_per_hop_gas_like_optimize_route():
+    engine = make_engine([
+        make_pool("direct", "a", "d", 10**6, 10**6),
+        make_pool("p1", "a", "b", 10**9, 10**9),
+        make_pool("p2", "b", "c", 10**9, 10**9),
+        make_pool("p3", "c", "d", 10**9, 10**9),
+    ])
+    params = {
+        "token_in": "a",
+        "token_out": "d",
+        "amount_in": 10_000,
+        "gas_per_hop": 1_000,
+        "token_price_in_gas": 1.0,
+        "min_output_amount": 9_000,
+    }
+
+    tradeoff = engine.route_tradeoff(params)
+    routed = engine.optimize_route(params)
+
+    gas_optimal = tradeoff["gas_optimal"]
+    assert gas_optimal["pools"] == routed["pools"] == ["direct"]
+    for key in ("output_amount", "gas_cost_input", "net_output", "meets_minimum", "hops"):
+        assert gas_optimal[key] == routed[key]
+    # 1_000 gas per hop at one gas per input unit is withheld from the input
+    ungassed = {"token_in": "a", "token_out": "d", "max_hops": 1, "amount_in": 9_000}
+    assert gas_optimal["net_output"] == engine.optimize_route(ungassed)["output_amount"]
+    assert tradeoff["price_optimal"]["gas_cost_input"] == "3000"
+    assert int(tradeoff["price_optimal"]["net_output"]) < int(gas_optimal["net_output"])
+
+
+def test_max_size_for_slippage_stops_at_the_cap():
+    reserve = 10**9
+    engine = make_engine([make_pool("p1", "a", "b", reserve, reserve)])
+
+    def impact_bps(amount):
+        # Constant product: execution vs fee-adjusted spot is reserve / (reserve + amount * (1 - fee))
+        return (1 - reserve / (reserve + amount * 0.997)) * 10_000
+
+    result = engine.max_size_for_slippage("a", "b", 50.0)
+    size = int(result["amount_in"])
+
+    assert result["route"]["path"] == ["a", "b"]
+    assert result["impact_bps"] <= 50.0
+    assert impact_bps(size) == pytest.approx(50.0, rel=1e-3)
+    assert impact_bps(size * 1.01) > 50.0
+    assert int(engine.max_size_for_slippage("a", "b", 100.0)["amount_in"]) > size
+    assert engine.max_size_for_slippage("a", "missing", 50.0) is None
+
+def test_max_hops_bounds_path_length():
+    chain = [
+        make_pool("p1", "a", "b", 10**9, 10**9),
+        make_pool("p2", "b", "c", 10**9, 10**9),
+        make_pool("p3", "c", "d", 10**9, 10**9),
+    ]
+    params = {"token_in": "a", "token_out": "d", "amount_in": 10_000}
+
+    engine = make_engine(chain)
+    assert engine.optimize_route({**params, "max_hops": 2}) is None
+    assert engine.optimize_route({**params, "max_hops": 3})["path"] == ["a", "b", "c", "d"]
+
+    engine = make_engine(chain + [make_pool("p4", "a", "c", 10**6, 10**6)])
+    assert engine.optimize_route(params)["path"] == ["a", "b", "c", "d"]
+    assert engine.optimize_route({**params, "max_hops": 2})["path"] == ["a", "c", "d"]
+
+
+def test_optimize_route_top_k_routes_sorted_by_output():
+    engine = make_engine([
+        make_pool("p1", "a", "b", 10**9, 10**9),
+        make_pool("p2", "b", "d", 10**9, 10**9),
+        make_pool("p3", "a", "c", 10**8, 10**8),
+        make_pool("p4", "c", "d", 10**8, 10**8),
+        make_pool("p5", "a", "d", 10**7, 10**7),
+    ])
+    params = {"token_in": "a", "token_out": "d", "amount_in": 100_000}
+
+    routes = engine.optimize_route({**params, "k": 3})
+
+    assert [r["pools"] for r in routes] == [["p1", "p2"], ["p3", "p4"], ["p5"]]
+    outputs = [int(r["output_amount"]) for r in routes]
+    assert outputs == sorted(outputs, reverse=True)
+    assert routes[0] == engine.optimize_route(params)
+    assert engine.optimize_route({**params, "k": 1}) == routes[0]
+    assert len(engine.optimize_route({**params, "k": 10})) == 3
+    assert engine.optimize_route({**params, "token_out": "missing", "k": 2}) == []
+
+
+def test_route_reconstruction_survives_predecessor_cycles():
+    # c -> b through "p0" ties the a -> b output exactly, and "p0" sorts first, so
+    # the tie-break would point b's predecessor back at its own successor c
+    tie = make_engine([
+        make_pool("pa", "a", "b", 1_000, 1_000, fee=0),
+        make_pool("pb", "b", "c", 500, 500, fee=0),
+        make_pool("p0", "c", "b", 250, 1_000, fee=0),
+        make_pool("pd", "b", "d", 10**6, 10**6, fee=0),
+    ])
+    route = tie.optimize_route({"token_in": "a", "token_out": "d", "amount_in": 1_000})
+    assert route["path"] == ["a", "b", "d"]
+
+    # Mismatched reserves form a profitable loop a -> usdc -> d -> weth -> a
+    arbitrage = make_engine([
+        make_pool("p1", "a", "weth", 10**6, 10**6),
+        make_pool("p2", "weth", "d", 10**6, 10**6),
+        make_pool("p3", "a", "usdc", 10**6, 3 * 10**6),
+        make_pool("p4", "usdc", "d", 10**6, 10**6),
+    ])
+    route = arbitrage.optimize_route({"token_in": "a", "token_out": "weth", "amount_in": 1_000})
+    assert route["path"][0] == "a" and route["path"][-1] == "weth"
+    assert len(set(route["path"])) == len(route["path"])
+
+
+def test_route_reports_compounded_price_impact_and_effective_price():
+    engine = make_engine([
+        make_pool("p1", "a", "b", 10**6, 2 * 10**6),
+        make_pool("p2", "b", "c", 4 * 10**6, 10**6),
+    ])
+    amount = 10_000
+    kept = 0.997
+
+    single = engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": amount})
+    assert single["price_impact_bps"] == pytest.approx((1 - 10**6 / (10**6 + amount * kept)) * 10_000)
+    assert single["effective_price"] == pytest.approx(int(single["output_amount"]) / amount)
+
+    double = engine.optimize_route({"token_in": "a", "token_out": "c", "amount_in": amount})
+    mid = 2 * 10**6 * amount * kept / (10**6 + amount * kept)
+    shortfall = (10**6 / (10**6 + amount * kept)) * (4 * 10**6 / (4 * 10**6 + mid * kept))
+    assert double["price_impact_bps"] == pytest.approx((1 - shortfall) * 10_000)
+    assert double["price_impact_bps"] > single["price_impact_bps"]
+
+
+def test_upsert_and_remove_pool_touch_only_one_pool():
+    engine = make_engine(
+        [make_pool("p1", "a", "b", 1_000_000, 1_000_000), make_pool("p2", "b", "c", 1_000_000, 1_000_000)]
+    )
+    params = {"token_in": "a", "token_out": "b", "amount_in": 1_000}
+    baseline = engine.optimize_route(params)
+    generation = engine.pool_generation
+
+    engine.upsert_pool(make_pool("p1", "a", "b", 1_000_000, 4_000_000))
+    assert engine.pool_generation == generation + 1
+    assert int(engine.optimize_route(params)["output_amount"]) > int(baseline["output_amount"])
+    assert engine.optimize_route({"token_in": "b", "token_out": "c", "amount_in": 1_000})["pools"] == ["p2"]
+
+    assert engine.remove_pool("p1")
+    assert not engine.remove_pool("p1")
+    assert engine.optimize_route(params) is None
+
+
+def test_route_hops_break_down_each_pool():
+    engine = make_engine([
+        make_pool("p1", "a", "b", 10**6, 2 * 10**6),
+        make_pool("p2", "b", "c", 4 * 10**6, 10**6, fee=500),
+    ])
+    first = engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": 10_000})
+    route = engine.optimize_route({"token_in": "a", "token_out": "c", "amount_in": 10_000})
+
+    assert [(h["pool_address"], h["token_in"], h["token_out"], h["fee"]) for h in route["hops"]] == [
+        ("p1", "a", "b", 3000),
+        ("p2", "b", "c", 500),
+    ]
+    assert route["hops"][0]["amount_in"] == "10000"
+    assert route["hops"][0]["amount_out"] == first["output_amount"] == route["hops"][1]["amount_in"]
+    assert route["hops"][1]["amount_out"] == route["output_amount"]
+
+
+def test_get_pool_reflects_latest_update():
+    pool = make_pool("p1", "a", "b", 1_000_000, 2_000_000)
+    engine = make_engine([pool])
+
+    assert engine.pool_count() == 1
+    assert engine.get_pool("missing") is None
+    loaded = engine.get_pool("p1")
+    amounts = ("liquidity", "reserve0", "reserve1")
+    assert {k: loaded[k] for k in pool} == {**pool, **{k: str(pool[k]) for k in amounts}}
+    assert loaded["rebate_bps"] == 0
+    # The returned shape loads back unchanged
+    engine.upsert_pool(loaded)
+    assert engine.get_pool("p1") == loaded
+
+    engine.upsert_pool({**pool, "reserve0": 5})
+    assert engine.get_pool("p1")["reserve0"] == "5"
+    engine.upsert_pool(make_pool("p2", "b", "c", 1, 1))
+    assert engine.pool_count() == 2
+
+
+async def test_optimize_route_async_matches_sync_routes():
+    engine = make_engine(
+        [make_pool("p1", "a", "b", 1_000_000, 1_000_000), make_pool("p2", "b", "c", 1_000_000, 1_000_000)]
+    )
+    requests = [{"token_in": "a", "token_out": "c", "amount_in": amount} for amount in (100, 1_000, 10_000)]
+
+    routes = await asyncio.gather(*(engine.optimize_route_async(params) for params in requests))
+
+    assert routes == [engine.optimize_route(params) for params in requests]
+    assert await engine.optimize_route_async({"token_in": "a", "token_out": "z", "amount_in": 1}) is None
+    with pytest.raises(ValueError):
+        await engine.optimize_route_async({"token_in": "a", "token_out": "c", "amount_in": 1, "k": 0})
+
+
+def test_optimize_route_async_exits_cleanly():
+    # Exiting right after the last route resolves used to crash as runtime
+    # threads kept touching Python during interpreter finalization
+    script = (
+        "import asyncio, platform_rust\n"
+        "pool = {'token0': {'address': 'a', 'symbol': 'A'}, 'token1': {'address': 'b', 'symbol': 'B'},\n"
+        "        'fee': 3000, 'liquidity': 2_000_000, 'reserve0': 1_000_000, 'reserve1': 1_000_000}\n"
+        "engine = platform_rust.ExecutionEngine()\n"
+        "engine.update_pools([{**pool, 'address': f'p{i}'} for i in range(50)])\n"
+        "async def main():\n"
+        "    params = [{'token_in': 'a', 'token_out': 'b', 'amount_in': 1_000 + i} for i in range(50)]\n"
+        "    await asyncio.gather(*(engine.optimize_route_async(p) for p in params))\n"
+        "asyncio.run(main())\n"
+    )
+    env = dict(os.environ, PYTHONPATH=os.path.dirname(platform_rust.__file__))
+    for _ in range(5):
+        done = subprocess.run([sys.executable, "-c", script], env=env, capture_output=True, text=True, timeout=30)
+        assert done.returncode == 0, done.stderr
+
+
+def make_v3_pool(address, token0, token1, liquidity, sqrt_price_x96, tick, tick_spacing=60, fee=3000):
+    return {
+        "address": address,
+        "kind": "v3",
+        "token0": {"address": token0, "symbol": token0.upper()},
+        "token1": {"address": token1, "symbol": token1.upper()},
+        "fee": fee,
+        "liquidity": liquidity,
+        "sqrt_price_x96": sqrt_price_x96,
+        "tick": tick,
+        "tick_spacing": tick_spacing,
+    }
+
+
+def test_v3_pool_matches_sqrt_price_math_quote():
+    q96 = 2**96
+    liquidity, sqrt_price = 10**18, q96
+    engine = make_engine([make_v3_pool("v3", "a", "b", liquidity, sqrt_price, tick=0)])
+
+    amount_in = 10**15
+    amount = amount_in * 997_000 // 1_000_000
+    # SqrtPriceMath.getNextSqrtPriceFromAmount0RoundingUp, then getAmount1Delta rounding down
+    next_price = -(-liquidity * q96 * sqrt_price // (liquidity * q96 + amount * sqrt_price))
+    expected = liquidity * (sqrt_price - next_price) // q96
+
+    route = engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": amount_in})
+    assert int(route["output_amount"]) == expected
+    reverse = engine.optimize_route({"token_in": "b", "token_out": "a", "amount_in": amount_in})
+    assert 0 < int(reverse["output_amount"]) < amount
+    assert engine.get_pool("v3")["sqrt_price_x96"] == sqrt_price
+
+    # Past the far edge of the adjacent range (tick -60) further input is not filled
+    edge = liquidity * (sqrt_price - int(1.0001**-30 * q96)) // q96
+    huge = engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": 10**20})
+    assert abs(int(huge["output_amount"]) - edge) <= edge // 10**9
+
+
+def test_stable_pool_prices_near_par_and_beats_constant_product():
+    stable = {
+        "address": "curve",
+        "kind": "stable",
+        "token0": {"address": "usdc", "symbol": "USDC"},
+        "token1": {"address": "usdt", "symbol": "USDT"},
+        "fee": 400,
+        "liquidity": 2 * 10**12,
+        "amplification": 100,
+        "balances": [10**12, 10**12],
+    }
+    engine = make_engine([stable, make_pool("cp", "usdc", "usdt", 10**12, 10**12, fee=400)])
+    amount_in = 10**10
+
+    route = engine.optimize_route({"token_in": "usdc", "token_out": "usdt", "amount_in": amount_in})
+
+    assert route["pools"] == ["curve"]
+    net_of_fee = amount_in * (1_000_000 - 400) // 1_000_000
+    assert net_of_fee * 0.9999 < int(route["output_amount"]) <= net_of_fee
+    assert engine.get_pool("curve")["balances"] == [str(10**12), str(10**12)]
+
+    with pytest.raises(ValueError):
+        make_engine([{**stable, "balances": [1, 2, 3]}])
+    with pytest.raises(ValueError):
+        make_engine([{**stable, "amplification": 0}])
+
+
+def test_stable_pool_spot_impact_and_sizing_follow_swap_output():
+    def stable_pool(b0, b1):
+        return {
+            "address": "curve",
+            "kind": "stable",
+            "token0": {"address": "usdc", "symbol": "USDC"},
+            "token1": {"address": "usdt", "symbol": "USDT"},
+            "fee": 400,
+            "liquidity": b0 + b1,
+            "amplification": 100,
+            "balances": [b0, b1],
+        }
+
+    fee_kept = 1 - 400 / 1_000_000
+    for b0, b1 in [(10**12, 10**12), (3 * 10**12, 10**12)]:
+        engine = make_engine([stable_pool(b0, b1)])
+
+        def quote(amount_in):
+            return engine.optimize_route({"token_in": "usdc", "token_out": "usdt", "amount_in": amount_in})
+
+        # A small swap's rate is the marginal price, not the reserve ratio
+        spot = engine.spot_price("usdc", "usdt")
+        assert int(quote(10**6)["output_amount"]) / (10**6 * fee_kept) == pytest.approx(spot, rel=1e-5)
+        assert spot > 0.98
+
+        # Impact of a 1% swap is the shortfall of the actual output from spot
+        amount_in = b0 // 100
+        route = quote(amount_in)
+        output = int(route["output_amount"])
+        actual_bps = (1 - output / (amount_in * spot * fee_kept)) * 10_000
+        assert route["price_impact_bps"] == pytest.approx(actual_bps, abs=0.01)
+        assert route["price_impact_bps"] < 10
+
+        after = make_engine([stable_pool(b0 + amount_in, b1 - output)])
+        assert engine.price_after_trade("curve", "usdc", amount_in) == pytest.approx(after.spot_price("usdc", "usdt"))
+
+        sized = engine.max_size_for_slippage("usdc", "usdt", 10.0)
+        assert sized["impact_bps"] <= 10.0
+        assert quote(2 * int(sized["amount_in"]))["price_impact_bps"] > 10.0
+
+
+def test_min_output_amount_flags_routes_that_fall_short():
+    engine = make_engine([make_pool("p1", "a", "b", 1_000_000, 1_000_000)])
+    params = {"token_in": "a", "token_out": "b", "amount_in": 1_000}
+    output = engine.optimize_route(params)["output_amount"]
+
+    assert "meets_minimum" not in engine.optimize_route(params)
+    assert engine.optimize_route({**params, "min_output_amount": output})["meets_minimum"]
+    short = engine.optimize_route({**params, "min_output_amount": int(output) + 1})
+    assert short["output_amount"] == output
+    assert not short["meets_minimum"]
+
+
+def test_gas_per_hop_prefers_shorter_route_net_of_gas():
+    engine = make_engine(
+        [
+            make_pool("direct", "a", "b", 1_000_000, 1_000_000),
+            make_pool("h1", "a", "x", 10**9, 10**9, fee=500),
+            make_pool("h2", "x", "y", 10**9, 10**9, fee=500),
+            make_pool("h3", "y", "b", 10**9, 10**9, fee=500),
+        ]
+    )
+    params = {"token_in": "a", "token_out": "b", "amount_in": 10_000}
+
+    assert engine.optimize_route(params)["pools"] == ["h1", "h2", "h3"]
+
+    # 100 input units of gas per hop outweighs the long route's better price
+    priced = engine.optimize_route({**params, "gas_per_hop": 200_000, "token_price_in_gas": 2_000})
+    assert priced["pools"] == ["direct"]
+    assert priced["gas_cost_input"] == "100"
+    direct = engine.optimize_route({**params, "amount_in": 9_900, "max_hops": 1})
+    assert priced["net_output"] == direct["output_amount"]
+
+    with pytest.raises(ValueError):
+        engine.optimize_route({**params, "gas_per_hop": 200_000})
+
+
+def test_exclude_pools_and_tokens_route_around_them():
+    engine = make_engine(
+        [
+            make_pool("best", "a", "b", 10_000_000, 10_000_000),
+            make_pool("backup", "a", "b", 1_000_000, 1_000_000),
+            make_pool("p3", "a", "x", 10_000_000, 10_000_000),
+            make_pool("p4", "x", "c", 10_000_000, 10_000_000),
+        ]
+    )
+    params = {"token_in": "a", "token_out": "b", "amount_in": 100_000}
+
+    assert engine.optimize_route(params)["pools"] == ["best"]
+    assert engine.optimize_route({**params, "exclude_pools": ["best"]})["pools"] == ["backup"]
+    assert engine.optimize_route({**params, "exclude_pools": ["best", "backup"]}) is None
+
+    via_x = {"token_in": "a", "token_out": "c", "amount_in": 1_000}
+    assert engine.optimize_route(via_x)["path"] == ["a", "x", "c"]
+    assert engine.optimize_route({**via_x, "exclude_tokens": ["x"]}) is None
+    assert engine.optimize_route(params)["pools"] == ["best"]
+
+
+def test_drained_and_shallow_pools_are_skipped():
+    engine = make_engine(
+        [
+            make_pool("p1", "a", "b", 10**6, 10**6),
+            make_pool("drained", "b", "c", 10**6, 0),
+        ]
+    )
+    params = {"token_in": "a", "token_out": "c", "amount_in": 1_000}
+    assert engine.optimize_route(params) is None
+
+    engine.upsert_pool(make_pool("drained", "b", "c", 10**6, 5_000))
+    assert engine.optimize_route(params)["pools"] == ["p1", "drained"]
+    assert engine.optimize_route({**params, "min_liquidity": 10_000}) is None
+    assert engine.optimize_route({**params, "min_liquidity": str(5_000)}) is not None
+
+
+def test_split_route_beats_single_path_on_deep_order():
+    engine = make_engine(
+        [
+            make_pool("direct", "a", "b", 1_000_000, 1_000_000),
+            make_pool("p2", "a", "x", 5_000_000, 5_000_000),
+            make_pool("p3", "x", "b", 5_000_000, 5_000_000),
+        ]
+    )
+    params = {"token_in": "a", "token_out": "b", "amount_in": 500_000}
+    single = engine.optimize_route(params)
+
+    splits = engine.optimize_split_route(params, max_splits=2)
+
+    assert len(splits) == 2
+    assert sum(int(s["amount_in"]) for s in splits) == 500_000
+    assert sum(s["fraction"] for s in splits) == pytest.approx(1.0)
+    assert sum(int(s["output_amount"]) for s in splits) > int(single["output_amount"])
+    assert engine.optimize_split_route(params, max_splits=1)[0]["output_amount"] == single["output_amount"]
+
+
+def test_directional_fees_price_each_way_differently():
+    pool = {
+        **make_pool("p1", "a", "b", 1_000_000, 1_000_000, fee=3000),
+        "fee_token0_to_token1": 500,
+        "fee_token1_to_token0": 10_000,
+    }
+    engine = make_engine([pool])
+    symmetric = make_engine([make_pool("p1", "a", "b", 1_000_000, 1_000_000, fee=3000)])
+
+    forward = engine.optimize_route({"token_in": "a", "token_out": "b", "amount_in": 10_000})
+    backward = engine.optimize_route({"token_in": "b", "token_out": "a", "amount_in": 10_000})
+    baseline = symmetric.optimize_route({"token_in": "a", "token_out": "b", "amount_in": 10_000})
+
+    assert int(forward["output_amount"]) > int(baseline["output_amount"]) > int(backward["output_amount"])
+    assert engine.get_pool("p1")["fee_token1_to_token0"] == 10_000
+
+
+def test_spot_price_weights_direct_pools_by_liquidity():
+    engine = make_engine(
+        [
+            make_pool("p1", "a", "b", 1_000_000, 2_000_000),
+            make_pool("p2", "b", "a", 1_500_000, 500_000),
+            make_pool("p3", "b", "c", 1_000_000, 1_000_000),
+        ]
+    )
+
+    # p1 quotes 2.0 with liquidity 3M, p2 quotes 3.0 with liquidity 2M
+    assert engine.spot_price("a", "b") == pytest.approx((2.0 * 3 + 3.0 * 2) / 5)
+    assert engine.spot_price("c", "b") == pytest.approx(1.0)
+    assert engine.spot_price("a", "c") is None
+
+
+def test_initialize_rust_runtime_rejects_conflicting_reconfiguration():
+    # A fresh interpreter, since this one's runtime is already built
+    script = (
+        "import platform_rust\n"
+        "platform_rust.initialize_rust_runtime(worker_threads=2, max_blocking_threads=8)\n"
+        "platform_rust.initialize_rust_runtime()\n"
+        "platform_rust.initialize_rust_runtime(worker_threads=2)\n"
+        "try:\n"
+        "    platform_rust.initialize_rust_runtime(worker_threads=4)\n"
+        "except RuntimeError as e:\n"
+        "    print(e)\n"
+    )
+    env = dict(os.environ, PYTHONPATH=os.path.dirname(platform_rust.__file__))
+    done = subprocess.run([sys.executable, "-c", script], env=env, capture_output=True, text=True, timeout=30)
+
+    assert done.returncode == 0, done.stderr
+    assert "already initialized with worker_threads=2, max_blocking_threads=8" in done.stdout
+    with pytest.raises(ValueError):
+        platform_rust.initialize_rust_runtime(worker_threads=0)
+
+
+def test_shutdown_rust_runtime_stops_background_tasks():
+    script = (
+        "import socket, time, platform_rust\n"
+        "probe = socket.socket(); probe.bind(('127.0.0.1', 0)); port = probe.getsockname()[1]; probe.close()\n"
+        "platform_rust.initialize_rust_runtime()\n"
+        "# Reconnects forever against the closed port until the runtime stops\n"
+        "platform_rust.subscribe_new_heads(f'ws://127.0.0.1:{port}')\n"
+        "started = time.monotonic()\n"
+        "platform_rust.shutdown_rust_runtime(2.0)\n"
+        "print(time.monotonic() - started)\n"
+        "platform_rust.shutdown_rust_runtime(2.0)\n"
+        "for call in (platform_rust.initialize_rust_runtime, lambda: platform_rust.subscribe_new_heads('ws://127.0.0.1:1')):\n"
+        "    try:\n"
+        "        call()\n"
+        "    except RuntimeError as e:\n"
+        "        print(e)\n"
+    )
+    env = dict(os.environ, PYTHONPATH=os.path.dirname(platform_rust.__file__))
+    done = subprocess.run([sys.executable, "-c", script], env=env, capture_output=True, text=True, timeout=30)
+
+    assert done.returncode == 0, done.stderr
+    elapsed, *errors = done.stdout.splitlines()
+    assert float(elapsed) < 2.0
+    assert errors == ["tokio runtime has been shut down"] * 2
+    with pytest.raises(ValueError):
+        platform_rust.shutdown_rust_runtime(-1.0)
+
+
+def test_initialize_rust_runtime_log_level_applies_on_first_call():
+    script = (
+        "import sys, platform_rust\n"
+        "first = platform_rust.initialize_rust_runtime(log_level=sys.argv[1] or None)\n"
+        "print(first, platform_rust.initialize_rust_runtime(log_level='trace'))\n"
+        "# Warns about the oversized transaction\n"
+        "platform_rust.TransactionBatcher(max_batch_bytes=1).batch_transactions(['0xdeadbeef'])\n"
+    )
+
+    def run(log_level, rust_log=None):
+        env = dict(os.environ, PYTHONPATH=os.path.dirname(platform_rust.__file__))
+        env.pop("RUST_LOG", None)
+        if rust_log is not None:
+            env["RUST_LOG"] = rust_log
+        done = subprocess.run(
+            [sys.executable, "-c", script, log_level], env=env, capture_output=True, text=True, timeout=30
+        )
+        assert done.returncode == 0, done.stderr
+        return done.stdout
+
+    quiet = run("ERROR", rust_log="trace")
+    assert quiet.splitlines()[0] == "error error"
+    assert "exceeds max_batch_bytes" not in quiet
+    from_env = run("", rust_log="warn")
+    assert from_env.splitlines()[0] == "warn warn"
+    assert "exceeds max_batch_bytes" in from_env
+    assert run("").splitlines()[0] == "info info"
+    with pytest.raises(ValueError):
+        platform_rust.initialize_rust_runtime(log_level="verbose")
+
+
+def test_initialize_rust_runtime_json_log_format_emits_json_lines():
+    script = (
+        "import platform_rust\n"
+        "platform_rust.initialize_rust_runtime(log_format='json')\n"
+        "platform_rust.TransactionBatcher(max_batch_bytes=1).batch_transactions(['0xdeadbeef', '0xdeadbeef'])\n"
+    )
+    env = dict(os.environ, PYTHONPATH=os.path.dirname(platform_rust.__file__))
+    env.pop("RUST_LOG", None)
+    done = subprocess.run([sys.executable, "-c", script], env=env, capture_output=True, text=True, timeout=30)
+
+    assert done.returncode == 0, done.stderr
+    events = [json.loads(line) for line in done.stdout.splitlines()]
+    assert len(events) == 2
+    assert events[0]["level"] == "WARN" and events[0]["target"] == "platform_rust::transaction"
+    assert events[0]["fields"]["message"] == "transaction exceeds max_batch_bytes; batching it alone"
+    assert events[1]["fields"]["index"] == 1 and events[1]["fields"]["max_bytes"] == 1
+    assert events[0]["timestamp"].endswith("Z")
+    with pytest.raises(ValueError):
+        platform_rust.initialize_rust_runtime(log_format="yaml")
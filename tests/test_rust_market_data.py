@@ -0,0 +1,400 @@
+import math
+from decimal import Decimal
+
+import pytest
+
+platform_rust = pytest.importorskip("platform_rust")
+
+
+def test_detect_icebergs_flags_repeated_refills():
+    # The 100 ask is hit down and refilled back to 50 four times
+    sizes = [50, 10, 50, 5, 50, 20, 50, 0, 50]
+    snapshots = [{"bids": [(99, 30)], "asks": [(100, s), (101, 40)]} for s in sizes]
+
+    result = platform_rust.detect_icebergs(snapshots, 100)
+
+    assert result["refills"] == 4
+    assert result["is_iceberg"] is True
+    assert result["sizes"] == sizes
+
+    steady = platform_rust.detect_icebergs(snapshots, 101)
+    assert steady["refills"] == 0
+    assert steady["is_iceberg"] is False
+
+    with pytest.raises(ValueError):
+        platform_rust.detect_icebergs([{"bids": [(100, 2**63 - 1)], "asks": [(100, 1)]}], 100)
+
+
+def test_realized_vol_matches_known_variance():
+    # Log returns alternate +/-1%, so mean squared return is exactly 0.01^2
+    mids, mid = [], 100.0
+    for i in range(11):
+        mids.append((i * 1_000, mid))
+        mid *= math.exp(0.01 if i % 2 == 0 else -0.01)
+
+    vol = platform_rust.realized_vol(mids, 60_000, 365.0)
+
+    assert vol == pytest.approx(0.01 * math.sqrt(365.0))
+    assert platform_rust.realized_vol(mids[:1], 60_000, 365.0) is None
+
+
+def test_l3_book_collapses_orders_into_levels():
+    book = platform_rust.L3Book()
+    book.add_order("b1", "bid", 99, 5)
+    book.add_order("b2", "bid", 99, 3)
+    book.add_order("b3", "bid", 98, 7)
+    book.add_order("a1", "ask", 101, 4)
+    book.add_order("a2", "ask", 102, 6)
+
+    assert book.to_l2() == {"bids": [(99, 8), (98, 7)], "asks": [(101, 4), (102, 6)]}
+
+    book.cancel_order("b1")
+    book.modify_order("a1", 1)
+    book.cancel_order("a2")
+
+    assert book.to_l2(depth=1) == {"bids": [(99, 3)], "asks": [(101, 1)]}
+    assert len(book) == 3
+    with pytest.raises(KeyError):
+        book.cancel_order("missing")
+
+
+def test_l3_book_rejects_level_overflow_without_changing_the_book():
+    book = platform_rust.L3Book()
+    book.add_order("b1", "bid", 99, 2**63 - 10)
+    book.add_order("b2", "bid", 99, 5)
+
+    with pytest.raises(ValueError):
+        book.add_order("b3", "bid", 99, 10)
+    with pytest.raises(ValueError):
+        book.modify_order("b2", 20)
+
+    assert book.to_l2() == {"bids": [(99, 2**63 - 5)], "asks": []}
+    assert len(book) == 2
+    book.cancel_order("b2")
+    assert book.to_l2() == {"bids": [(99, 2**63 - 10)], "asks": []}
+
+
+def test_fill_probability_decreases_deeper_in_book():
+    book = {"bids": [(100, 10), (99, 20), (98, 30)], "asks": [(101, 10), (102, 20)]}
+
+    near = platform_rust.fill_probability(book, "bid", 100, 25.0)
+    deep = platform_rust.fill_probability(book, "bid", 98, 25.0)
+
+    assert 0.0 < deep < near < 1.0
+    assert platform_rust.fill_probability(book, "bid", 101, 25.0) == 1.0
+    assert platform_rust.fill_probability(book, "ask", 102, 25.0) < platform_rust.fill_probability(book, "ask", 101, 25.0)
+
+    with pytest.raises(ValueError):
+        platform_rust.fill_probability({"bids": [(100, 2**63 - 1), (99, 1)]}, "bid", 99, 25.0)
+
+
+def test_volume_profile_point_of_control():
+    snapshots = [
+        {"bids": [(98, 5), (99, 40)], "asks": [(101, 5), (110, 2)]},
+        {"bids": [(99, 60)], "asks": [(102, 3)]},
+        {"bids": [(97, 1), (99, 50)], "asks": [(101, 4)]},
+    ]
+
+    profile = platform_rust.volume_profile(snapshots, 4)
+
+    assert profile["poc"] == 99
+    assert sum(volume for _, _, volume in profile["buckets"]) == 170
+    assert len(profile["buckets"]) == 4
+    low, high, volume = max(profile["buckets"], key=lambda b: b[2])
+    assert low <= 99 < high
+
+
+def test_volume_profile_rejects_overflowing_prices_and_volume():
+    i64_max = 2**63 - 1
+    for snapshots in (
+        [{"bids": [(-i64_max, 1)], "asks": [(i64_max, 1)]}],
+        [{"bids": [(0, 1)], "asks": [(i64_max, 1)]}],
+        [{"bids": [(100, i64_max)], "asks": [(100, 1)]}],
+    ):
+        with pytest.raises(ValueError):
+            platform_rust.volume_profile(snapshots, 4)
+
+
+def test_mid_correlation_of_linked_series():
+    mids_a = [(i * 1_000, 100.0 + math.sin(i)) for i in range(50)]
+    same = [(ts + 3, 2 * mid + 5) for ts, mid in mids_a]
+    opposite = [(ts - 4, 300.0 - mid) for ts, mid in mids_a]
+
+    assert platform_rust.mid_correlation(mids_a, same, 5) == pytest.approx(1.0)
+    assert platform_rust.mid_correlation(mids_a, opposite, 5) == pytest.approx(-1.0)
+    assert platform_rust.mid_correlation(mids_a, same, 1) is None
+
+
+def test_kyle_lambda_recovers_linear_impact():
+    flows = [0, 40, -20, 60, -80, 10, 30, -50]
+    snapshots = []
+    mid = 1_000.0
+    for flow in flows:
+        mid += 0.5 * flow
+        snapshots.append({"bids": [(int(mid) - 1, 10)], "asks": [(int(mid) + 1, 10)], "signed_volume": flow})
+
+    assert platform_rust.kyle_lambda(snapshots) == pytest.approx(0.5)
+    assert platform_rust.kyle_lambda(snapshots[:2]) is None
+
+    # Prices above i64::MAX / 2, where bid + ask would overflow i64
+    high, mid = [], 3 * 2**61
+    for flow in [0, 10, -20, 30]:
+        mid += flow * 4_096 // 10
+        high.append({"bids": [(mid - 1, 1)], "asks": [(mid + 1, 1)], "signed_volume": flow})
+    assert platform_rust.kyle_lambda(high) == pytest.approx(409.6)
+
+
+def test_synthetic_cross_top_of_book_is_product_of_legs():
+    book_ab = {"bids": [(2.0, 10), (1.9, 10)], "asks": [(2.1, 5), (2.2, 10)]}
+    book_bc = {"bids": [(3.0, 8), (2.9, 100)], "asks": [(3.1, 100)]}
+
+    cross = platform_rust.synthetic_cross(book_ab, book_bc)
+
+    assert cross["bids"][0] == pytest.approx((6.0, 4.0))
+    assert cross["bids"][1] == pytest.approx((5.8, 6.0))
+    assert cross["asks"][0] == pytest.approx((2.1 * 3.1, 5.0))
+    assert sum(size for _, size in cross["bids"]) == pytest.approx(20.0)
+
+
+def test_order_book_replay_lags_updates_by_latency():
+    replay = platform_rust.OrderBookReplay(latency_ms=50)
+    replay.apply_update(1_000, "bid", 100, 5)
+    replay.apply_update(1_010, "ask", 101, 7)
+
+    assert replay.snapshot(1_000) == {"bids": [], "asks": []}
+    assert replay.snapshot(1_055) == {"bids": [(100, 5)], "asks": []}
+    assert replay.pending_updates() == 1
+    assert replay.snapshot(1_060) == {"bids": [(100, 5)], "asks": [(101, 7)]}
+
+    sampled = platform_rust.OrderBookReplay(latency_ms=[10, 20, 30], seed=7)
+    sampled.apply_update(0, "bid", 100, 1)
+    assert sampled.snapshot(0)["bids"] == []
+    assert sampled.snapshot(30)["bids"] == [(100, 1)]
+
+
+def test_quote_band_skews_with_inventory():
+    book = {"bids": [(100, 30), (99, 50)], "asks": [(102, 10), (103, 40)]}
+
+    flat = platform_rust.quote_band(book, 0.0, 0.1)
+    long = platform_rust.quote_band(book, 5.0, 0.1)
+    short = platform_rust.quote_band(book, -5.0, 0.1)
+
+    assert flat["fair"] == pytest.approx((100 * 10 + 102 * 30) / 40)
+    assert flat["ask"] - flat["bid"] == pytest.approx(2.0)
+    assert long["bid"] < flat["bid"] and long["ask"] < flat["ask"]
+    assert short["bid"] > flat["bid"] and short["ask"] > flat["ask"]
+    assert platform_rust.quote_band({"bids": [(100, 1)]}, 0.0, 0.1) is None
+
+    # Sizes whose sum overflows i64 still weight the micro-price
+    deep = platform_rust.quote_band({"bids": [(100, 2**63 - 1)], "asks": [(102, 2**63 - 1)]}, 0.0, 0.1)
+    assert deep["fair"] == pytest.approx(101.0)
+
+
+def test_aggregate_order_books_reports_side_vwap():
+    books = [
+        {"side": "bid", "price": 100, "size": 10},
+        {"side": "bid", "price": 98, "size": 30},
+        {"side": "bid", "price": 100, "size": 10},
+    ]
+
+    result = platform_rust.aggregate_order_books(books)
+
+    assert result["bids"] == [(98, 30), (100, 20)]
+    # (100 * 20 + 98 * 30) / 50
+    assert result["bid_vwap"] == pytest.approx(98.8)
+    assert result["ask_vwap"] is None
+
+
+def test_aggregate_order_books_keeps_fractional_levels_exact():
+    books = [
+        {"side": "ask", "price": 0.015, "size": 0.1},
+        {"side": "ask", "price": 0.0151, "size": 0.2},
+        {"side": "ask", "price": 0.015, "size": 0.2},
+    ]
+
+    result = platform_rust.aggregate_order_books(books)
+
+    # 0.1 + 0.2 would be 0.30000000000000004 with float summation
+    assert result["asks"] == [(0.015, 0.3), (0.0151, 0.2)]
+    assert result["ask_vwap"] == pytest.approx((0.015 * 0.3 + 0.0151 * 0.2) / 0.5)
+
+    decimal_books = [
+        {"side": "bid", "price": "0.0149", "size": "1.10"},
+        {"side": "bid", "price": Decimal("0.01490"), "size": Decimal("2.2")},
+    ]
+    assert platform_rust.aggregate_order_books(decimal_books)["bids"] == [("0.0149", "3.3")]
+    with pytest.raises(ValueError):
+        platform_rust.aggregate_order_books([{"side": "bid", "price": "1e-19", "size": 1}])
+
+
+def test_aggregate_order_books_reports_top_of_book():
+    books = [
+        {"side": "bid", "price": 99, "size": 5},
+        {"side": "bid", "price": 100, "size": 5},
+        {"side": "ask", "price": 103, "size": 5},
+        {"side": "ask", "price": 102, "size": 5},
+    ]
+
+    result = platform_rust.aggregate_order_books(books)
+
+    assert (result["best_bid"], result["best_ask"]) == (100, 102)
+    assert result["spread"] == 2
+    assert result["mid_price"] == 101.0
+
+    crossed = platform_rust.aggregate_order_books(
+        [{"side": "bid", "price": "1.02", "size": 1}, {"side": "ask", "price": "1.01", "size": 1}]
+    )
+    assert (crossed["best_bid"], crossed["best_ask"]) == ("1.02", "1.01")
+    assert crossed["spread"] == "-0.01"
+    assert crossed["mid_price"] == "1.015"
+
+    one_sided = platform_rust.aggregate_order_books(books[:2])
+    assert one_sided["best_bid"] == 100
+    assert one_sided["best_ask"] is None
+    assert one_sided["mid_price"] is None and one_sided["spread"] is None
+
+
+def test_aggregate_order_books_trims_output_to_depth():
+    bids = [{"side": "bid", "price": p, "size": 1} for p in (97, 98, 99, 100)]
+    asks = [{"side": "ask", "price": p, "size": 1} for p in (101, 102, 103, 104)]
+
+    result = platform_rust.aggregate_order_books(bids + asks, depth=2)
+
+    assert result["bids"] == [(99, 1), (100, 1)]
+    assert result["asks"] == [(101, 1), (102, 1)]
+    # Derived fields still see every level
+    assert result["bid_vwap"] == pytest.approx(98.5)
+    assert len(platform_rust.aggregate_order_books(bids + asks)["bids"]) == 4
+
+
+def test_order_book_imbalance_over_top_levels():
+    books = [
+        {"side": "bid", "price": 100, "size": 30},
+        {"side": "bid", "price": 99, "size": 10},
+        {"side": "bid", "price": 98, "size": 500},
+        {"side": "ask", "price": 101, "size": 10},
+        {"side": "ask", "price": 102, "size": 10},
+    ]
+
+    result = platform_rust.order_book_imbalance(books, 2)
+
+    assert (result["bid_volume"], result["ask_volume"]) == (40, 20)
+    assert result["imbalance"] == pytest.approx(1 / 3)
+    assert result["cumulative_bids"] == [(100, 30), (99, 40)]
+    assert result["cumulative_asks"] == [(101, 10), (102, 20)]
+
+    empty = platform_rust.order_book_imbalance([], 5)
+    assert empty["imbalance"] == 0.0
+    assert empty["cumulative_bids"] == []
+
+
+def test_aggregate_order_books_tags_levels_with_sources():
+    books = [
+        {"side": "bid", "price": 100, "size": 4, "source": "binance"},
+        {"side": "bid", "price": 100, "size": 6, "source": "coinbase"},
+        {"side": "bid", "price": 100, "size": 1, "source": "binance"},
+        {"side": "ask", "price": 101, "size": 3},
+    ]
+
+    result = platform_rust.aggregate_order_books(books)
+
+    assert result["bids"] == [(100, 11, {"binance": 5, "coinbase": 6})]
+    assert result["asks"] == [(101, 3, {None: 3})]
+
+
+def test_build_candles_buckets_unsorted_trades():
+    trades = [
+        {"timestamp": 65, "price": 11.0, "size": 1.0},
+        {"timestamp": 10, "price": 10.0, "size": 2.0},
+        {"timestamp": 59, "price": 9.5, "size": 1.0},
+        {"timestamp": 30, "price": 12.0, "size": 0.5},
+        {"timestamp": 300, "price": 13.0, "size": 1.0},
+    ]
+
+    candles = platform_rust.build_candles(trades, 60)
+
+    assert candles == [
+        {"open_time": 0, "open": 10.0, "high": 12.0, "low": 9.5, "close": 9.5, "volume": 3.5},
+        {"open_time": 60, "open": 11.0, "high": 11.0, "low": 11.0, "close": 11.0, "volume": 1.0},
+        # Nothing is synthesized for the empty minutes in between
+        {"open_time": 300, "open": 13.0, "high": 13.0, "low": 13.0, "close": 13.0, "volume": 1.0},
+    ]
+    with pytest.raises(ValueError):
+        platform_rust.build_candles(trades, 0)
+
+
+def test_aggregate_order_books_rejects_negative_sizes():
+    books = [
+        {"side": "bid", "price": 100, "size": 5},
+        {"side": "bid", "price": 99, "size": 0},
+        {"side": "ask", "price": 101, "size": -2},
+    ]
+
+    with pytest.raises(ValueError, match="entry 2"):
+        platform_rust.aggregate_order_books(books)
+
+    result = platform_rust.aggregate_order_books(books[:2])
+    assert result["bids"] == [(100, 5)]
+
+
+def test_aggregate_order_books_nets_negative_deltas():
+    books = [
+        {"side": "bid", "price": 100, "size": 5},
+        {"side": "bid", "price": 100, "size": -2},
+        {"side": "ask", "price": 101, "size": 3},
+        {"side": "ask", "price": 101, "size": -3},
+        {"side": "ask", "price": 102, "size": -1},
+        {"side": "ask", "price": 103, "size": 4},
+    ]
+
+    result = platform_rust.aggregate_order_books(books, allow_negative=True)
+
+    assert result["bids"] == [(100, 3)]
+    assert result["asks"] == [(103, 4)]
+    assert result["best_ask"] == 103
+
+
+def test_order_book_applies_incremental_deltas():
+    book = platform_rust.OrderBook()
+    book.apply_delta(
+        [
+            {"side": "bid", "price": 100, "size": 5},
+            {"side": "bid", "price": 99, "size": 7},
+            {"side": "bid", "price": 98, "size": 9},
+            {"side": "ask", "price": 101, "size": 4},
+        ]
+    )
+    book.apply_delta(
+        [
+            {"side": "bid", "price": 100, "size": 0},
+            {"side": "bid", "price": 99, "size": 8},
+            {"side": "ask", "price": 102, "size": 6},
+        ]
+    )
+
+    assert book.snapshot() == {"bids": [(99, 8), (98, 9)], "asks": [(101, 4), (102, 6)]}
+    assert book.snapshot(depth=1) == {"bids": [(99, 8)], "asks": [(101, 4)]}
+    # A bad level rejects the whole batch
+    with pytest.raises(ValueError, match="level 1"):
+        book.apply_delta(
+            [{"side": "ask", "price": 103, "size": 1}, {"side": "ask", "price": 104, "size": -1}]
+        )
+    assert book.snapshot()["asks"] == [(101, 4), (102, 6)]
+
+
+def test_order_book_reads_while_another_thread_updates():
+    import threading
+
+    book = platform_rust.OrderBook()
+
+    def writer():
+        for i in range(1, 500):
+            book.apply_delta([{"side": "bid", "price": 100, "size": i}])
+
+    thread = threading.Thread(target=writer)
+    thread.start()
+    while thread.is_alive():
+        for price, size in book.snapshot()["bids"]:
+            assert price == 100 and size > 0
+    thread.join()
+    assert book.snapshot()["bids"] == [(100, 499)]
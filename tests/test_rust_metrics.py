@@ -0,0 +1,34 @@
+import pytest
+
+from rust_helpers import make_pool
+
+platform_rust = pytest.importorskip("platform_rust")
+
+LEGACY_TX = (
+    "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd9"
+    "39bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b29"
+    "7fb1966a3b6d83"
+)
+
+
+def test_metrics_snapshot_counts_hot_path_work():
+    # Counters are process-wide, so compare deltas
+    before = platform_rust.metrics_snapshot()
+    engine = platform_rust.ExecutionEngine()
+    engine.update_pools([make_pool("p1", "a", "b", 10**6, 10**6), make_pool("p2", "b", "c", 10**6, 10**6)])
+    engine.upsert_pool(make_pool("p3", "a", "c", 10**6, 10**6))
+    params = {"token_in": "a", "token_out": "c", "amount_in": 1_000}
+    engine.optimize_route(params)
+    engine.optimize_route(params)
+    engine.optimize_route({**params, "amount_in": 2_000})
+    platform_rust.decode_transaction(LEGACY_TX)
+    with pytest.raises(ValueError):
+        platform_rust.decode_transaction("0xdeadbeef")
+
+    after = platform_rust.metrics_snapshot()
+    delta = {key: after[key] - before[key] for key in before if key != "route_compute_ms"}
+    assert delta == {"routes_computed": 2, "route_cache_hits": 1, "pools_loaded": 3, "transactions_decoded": 1}
+    timings = after["route_compute_ms"]
+    assert timings["count"] - before["route_compute_ms"]["count"] == 2
+    assert 0 < timings["p50"] <= timings["p99"]
+    assert 0 < timings["mean"] <= timings["max"]
@@ -0,0 +1,28 @@
+import asyncio
+
+import pytest
+
+platform_rust = pytest.importorskip("platform_rust")
+
+
+async def test_spawned_task_returns_result_and_can_be_cancelled():
+    async def add(a, b):
+        await asyncio.sleep(0.01)
+        return a + b
+
+    started = []
+
+    async def forever():
+        started.append(True)
+        await asyncio.sleep(3600)
+
+    assert await platform_rust.spawn_task(add(2, 3)) == 5
+
+    handle = platform_rust.spawn_task(forever())
+    while not started:
+        await asyncio.sleep(0.01)
+    assert handle.cancel()
+    with pytest.raises(asyncio.CancelledError):
+        handle.result(timeout=5)
+    assert handle.done()
+    assert not handle.cancel()
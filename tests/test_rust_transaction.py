@@ -0,0 +1,362 @@
+import asyncio
+import json
+import os
+import subprocess
+import sys
+
+import pytest
+
+platform_rust = pytest.importorskip("platform_rust")
+
+# Signed by 0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f
+LEGACY_TX = (
+    "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd9"
+    "39bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b29"
+    "7fb1966a3b6d83"
+)
+EIP1559_TX = (
+    "0x02f86d010284773594008509502f900082ea6094353535353535353535353535353535353535353582303980c080a0dc2f233f"
+    "c726c8efb5cf06927527dd45ac7845eacdd3da822033644bd677201ca04a55be67abceccf8b2b76054f5f18085f07948611361c4"
+    "c6d9a92b391b7e1518"
+)
+# Signed by 0x19e7e376e7c213b7e7e7e46cc70a5dd086daff2a
+SENDER2_NONCE0_TX = (
+    "0x02f86d0180843b9aca008502540be40082ea6094353535353535353535353535353535353535353582303980c080a0f73474f2"
+    "fa9958a02492d018313376691cb86421511ed72e2be3244d20f8f2d7a0329e8af62c513dc0e223b10b4f211bd8594a679270a9bb"
+    "9b57c0a534c8534e21"
+)
+SENDER2_NONCE1_TX = (
+    "0x02f86d0101843b9aca00850df847580082ea6094353535353535353535353535353535353535353582303980c001a0412c94cf"
+    "1c1ccabd5c79df097aee210d2e839cf2215355f3f54b612dcbeea392a0637f8b1efc4808e3f9ec7f8e491a3e2ccd6499e908223c"
+    "3749769cfd78ca3951"
+)
+# Same nonce as SENDER2_NONCE1_TX with a different fee
+SENDER2_NONCE1_CONFLICT_TX = (
+    "0x02f86d0101843b9aca0085104c533c0082ea6094353535353535353535353535353535353535353582303980c080a058d0521a"
+    "a9964461f2d73dcfad26f4bf7cb86ced03837462f247bb56ff58f600a03a991e89513f9ea512441154ef4606d60ee0f20da73a2c"
+    "89964408f4e7b6beca"
+)
+SENDER2_NONCE3_TX = (
+    "0x02f86d0103843b9aca008504a817c80082ea6094353535353535353535353535353535353535353582303980c001a06427e48e"
+    "9cfbe52fe9cdec7328d11962bfaa844ee2a2966e50fa92ac744537f2a040766d989f3713709dabfa55d712c9f13a58eb8282fa81"
+    "09fa73f40294f548be"
+)
+
+
+def test_batch_by_cost_respects_max_cost():
+    batcher = platform_rust.TransactionBatcher()
+    transactions = ["0x" + "ab" * n for n in (1, 5, 3, 8, 2, 2, 20)]
+
+    batches = batcher.batch_by_cost(transactions, len, 20)
+
+    assert [tx for batch in batches for tx in batch] == transactions
+    for batch in batches:
+        assert sum(len(tx) for tx in batch) <= 20 or len(batch) == 1
+
+
+def test_batch_by_cost_rejects_invalid_costs():
+    batcher = platform_rust.TransactionBatcher()
+    for cost in (-1.0, float("nan"), float("inf")):
+        with pytest.raises(ValueError):
+            batcher.batch_by_cost(["0xab"], lambda tx, cost=cost: cost, 20)
+    for max_cost in (-1.0, float("nan"), float("inf")):
+        with pytest.raises(ValueError):
+            batcher.batch_by_cost(["0xab"], len, max_cost)
+
+
+def test_batch_totals_sum_decoded_gas_and_fees():
+    transactions = [LEGACY_TX, EIP1559_TX, SENDER2_NONCE0_TX]
+    decoded = [platform_rust.decode_transaction(tx) for tx in transactions]
+
+    totals = platform_rust.batch_totals(transactions)
+
+    assert totals["count"] == 3
+    assert int(totals["total_gas"]) == sum(int(d["gas"]) for d in decoded)
+    assert int(totals["total_max_fee_wei"]) == sum(
+        int(d["gas"]) * int(d["gas_price"] or d["max_fee_per_gas"]) for d in decoded
+    )
+
+    per_batch = platform_rust.batch_totals([transactions[:1], transactions[1:]])
+    assert [b["count"] for b in per_batch] == [1, 2]
+    assert sum(int(b["total_gas"]) for b in per_batch) == int(totals["total_gas"])
+
+
+def test_partition_batches_balances_worker_load():
+    sizes = [7, 3, 5, 1, 8, 2, 4, 6]
+    batches = [[f"0x{i:02x}{j:02x}" for j in range(n)] for i, n in enumerate(sizes)]
+
+    workers = platform_rust.partition_batches(batches, 3)
+
+    assert len(workers) == 3
+    assert sorted(b[0] for w in workers for b in w) == sorted(b[0] for b in batches)
+    loads = [sum(len(b) for b in w) for w in workers]
+    assert max(loads) - min(loads) <= max(sizes)
+    assert max(loads) <= -(-sum(sizes) // 3) + max(sizes)
+
+
+def test_batch_merkle_root_is_stable_and_proofs_verify():
+    transactions = [LEGACY_TX, EIP1559_TX, SENDER2_NONCE0_TX]
+
+    tree = platform_rust.batch_merkle_root(transactions, include_proofs=True)
+
+    assert platform_rust.batch_merkle_root(transactions)["root"] == tree["root"]
+    assert tree["leaves"] == [platform_rust.decode_transaction(tx)["hash"] for tx in transactions]
+    for leaf, proof in zip(tree["leaves"], tree["proofs"]):
+        assert platform_rust.verify_merkle_proof(leaf, proof, tree["root"])
+    assert not platform_rust.verify_merkle_proof(tree["leaves"][0], tree["proofs"][1], tree["root"])
+    assert platform_rust.batch_merkle_root(transactions[:2])["root"] != tree["root"]
+
+
+def test_verify_merkle_proof_rejects_an_internal_node_as_leaf():
+    transactions = [LEGACY_TX, EIP1559_TX, SENDER2_NONCE0_TX, SENDER2_NONCE1_TX]
+    tree = platform_rust.batch_merkle_root(transactions, include_proofs=True)
+    # Root of the first pair, i.e. the internal node above leaves 0 and 1
+    internal = platform_rust.batch_merkle_root(transactions[:2])["root"]
+
+    assert not platform_rust.verify_merkle_proof(internal, tree["proofs"][0][1:], tree["root"])
+    assert platform_rust.verify_merkle_proof(tree["leaves"][0], tree["proofs"][0], tree["root"])
+    single = platform_rust.batch_merkle_root(transactions[:1])
+    assert single["root"] != single["leaves"][0]
+
+
+def test_filter_by_sender_rejects_unlisted_senders():
+    transactions = [LEGACY_TX, SENDER2_NONCE0_TX, EIP1559_TX]
+
+    result = platform_rust.filter_by_sender(transactions, ["0x9D8A62F656A8D1615C1294FD71E9CFB3E4855A4F"])
+
+    assert result["accepted"] == [LEGACY_TX, EIP1559_TX]
+    assert result["rejected"] == [SENDER2_NONCE0_TX]
+    assert platform_rust.filter_by_sender(transactions, [])["rejected"] == transactions
+
+
+def test_filter_by_sender_rejects_unrecoverable_and_validates_the_allowlist():
+    r = "dc2f233fc726c8efb5cf06927527dd45ac7845eacdd3da822033644bd677201c"
+    unrecoverable = EIP1559_TX.replace(r, "ff" * 32)
+
+    result = platform_rust.filter_by_sender([unrecoverable, LEGACY_TX], ["9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"])
+
+    assert result == {"accepted": [LEGACY_TX], "rejected": [unrecoverable]}
+    for bad in ("0x1234", "not an address", "0x" + "zz" * 20):
+        with pytest.raises(ValueError):
+            platform_rust.filter_by_sender([LEGACY_TX], [bad])
+
+
+def test_validate_bundle_flags_nonce_conflicts_and_gaps():
+    clean = platform_rust.validate_bundle([SENDER2_NONCE0_TX, LEGACY_TX, SENDER2_NONCE1_TX])
+    assert clean == {"valid": True, "issues": []}
+
+    report = platform_rust.validate_bundle(
+        [SENDER2_NONCE0_TX, SENDER2_NONCE1_TX, SENDER2_NONCE1_CONFLICT_TX, SENDER2_NONCE3_TX]
+    )
+    assert not report["valid"]
+    assert [(i["kind"], i["nonce"], i["index"]) for i in report["issues"]] == [
+        ("duplicate_nonce", 1, 2),
+        ("nonce_gap", 3, 3),
+    ]
+    assert report["issues"][0]["sender"] == "0x19E7E376E7C213B7E7e7e46cc70A5dD086DAff2A"
+
+
+def test_batch_transactions_splits_on_byte_limit():
+    batcher = platform_rust.TransactionBatcher(max_batch_size=3, max_batch_bytes=10)
+    # Raw sizes of 4, 4, 3, 1, 12 and 2 bytes
+    transactions = ["0x" + "ab" * n for n in (4, 4, 3, 1, 12, 2)]
+
+    batches = batcher.batch_transactions(transactions)
+
+    sizes = [[len(tx) // 2 - 1 for tx in batch] for batch in batches]
+    # The oversized transaction is kept, alone in its batch
+    assert sizes == [[4, 4], [3, 1], [12], [2]]
+    assert platform_rust.TransactionBatcher(max_batch_size=2).batch_transactions(transactions[:3]) == [
+        transactions[:2],
+        transactions[2:3],
+    ]
+
+
+def test_batcher_rejects_zero_max_batch_size():
+    with pytest.raises(ValueError):
+        platform_rust.TransactionBatcher(max_batch_size=0)
+
+
+def test_batcher_dedupes_repeated_transactions():
+    batcher = platform_rust.TransactionBatcher(max_batch_size=2, dedupe=True)
+    # The uppercase copy has the same raw bytes, so it is a duplicate too
+    transactions = [LEGACY_TX, EIP1559_TX, LEGACY_TX, SENDER2_NONCE0_TX, "0x" + EIP1559_TX[2:].upper()]
+
+    batches, dropped = batcher.batch_transactions(transactions)
+
+    assert batches == [[LEGACY_TX, EIP1559_TX], [SENDER2_NONCE0_TX]]
+    assert dropped == 2
+    # The count belongs to the call, not the batcher
+    assert batcher.batch_transactions(transactions[:2]) == ([[LEGACY_TX, EIP1559_TX]], 0)
+    assert batcher.batch_by_cost(transactions, len, 10**6) == (batches, 2)
+    plain = platform_rust.TransactionBatcher(max_batch_size=10)
+    assert len(plain.batch_transactions(transactions)[0]) == 5
+
+
+def test_batch_transactions_orders_by_gas_price():
+    batcher = platform_rust.TransactionBatcher(max_batch_size=4)
+    # Fee caps in gwei: 10, -, 20, 60, 40, 20
+    transactions = [
+        SENDER2_NONCE0_TX,
+        "0xdeadbeef",
+        LEGACY_TX,
+        SENDER2_NONCE1_TX,
+        EIP1559_TX,
+        SENDER2_NONCE3_TX,
+    ]
+
+    batches = batcher.batch_transactions(transactions, sort_by="gas_price_desc")
+
+    # The equal-fee pair keeps input order; the undecodable one sinks
+    assert batches == [
+        [SENDER2_NONCE1_TX, EIP1559_TX, LEGACY_TX, SENDER2_NONCE3_TX],
+        [SENDER2_NONCE0_TX, "0xdeadbeef"],
+    ]
+    ascending = batcher.batch_transactions(transactions, sort_by="gas_price_asc")
+    assert ascending[0][0] == SENDER2_NONCE0_TX and ascending[-1][-1] == "0xdeadbeef"
+    with pytest.raises(ValueError):
+        batcher.batch_transactions(transactions, sort_by="nonce")
+
+
+def test_batch_transactions_sorts_by_price_at_the_base_fee():
+    batcher = platform_rust.TransactionBatcher(max_batch_size=10)
+    transactions = [SENDER2_NONCE0_TX, LEGACY_TX, SENDER2_NONCE1_TX, EIP1559_TX, SENDER2_NONCE3_TX]
+
+    # At 15 gwei the dynamic-fee ones pay 10, 16, 17 and 16 gwei; legacy pays 20
+    batches = batcher.batch_transactions(transactions, sort_by="gas_price_desc", base_fee=15 * 10**9)
+
+    assert batches == [[LEGACY_TX, EIP1559_TX, SENDER2_NONCE1_TX, SENDER2_NONCE3_TX, SENDER2_NONCE0_TX]]
+    with pytest.raises(ValueError):
+        batcher.batch_transactions(transactions, base_fee=15 * 10**9)
+
+
+def test_assign_nonces_increments_from_start():
+    batcher = platform_rust.TransactionBatcher()
+    calls = [{"to": "0x" + "11" * 20}, {"to": "0x" + "22" * 20}, LEGACY_TX]
+
+    assigned = batcher.assign_nonces(calls, 5)
+
+    assert [entry["nonce"] for entry in assigned] == [5, 6, 7]
+    assert [entry["transaction"] for entry in assigned] == calls
+    with pytest.raises(ValueError):
+        batcher.assign_nonces(calls, 2**64 - 2)
+
+
+async def test_submit_batch_async_collects_per_transaction_results():
+    in_flight, peak = 0, 0
+
+    async def node(reader, writer):
+        nonlocal in_flight, peak
+        head = (await reader.readuntil(b"\r\n\r\n")).decode()
+        length = int(head.lower().split("content-length:")[1].split("\r\n")[0])
+        request = json.loads(await reader.readexactly(length))
+        assert request["method"] == "eth_sendRawTransaction"
+        in_flight += 1
+        peak = max(peak, in_flight)
+        await asyncio.sleep(0.05)
+        in_flight -= 1
+        if request["params"][0] == EIP1559_TX:
+            response = {"jsonrpc": "2.0", "id": request["id"], "error": {"code": -32000, "message": "nonce too low"}}
+        else:
+            response = {"jsonrpc": "2.0", "id": request["id"], "result": "0x" + "ab" * 32}
+        body = json.dumps(response).encode()
+        # Chunked, as many nodes answer behind proxies
+        writer.write(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n")
+        writer.write(b"%x\r\n%s\r\n0\r\n\r\n" % (len(body), body))
+        await writer.drain()
+        writer.close()
+
+    server = await asyncio.start_server(node, "127.0.0.1", 0)
+    port = server.sockets[0].getsockname()[1]
+    batcher = platform_rust.TransactionBatcher()
+    batch = [LEGACY_TX, EIP1559_TX, SENDER2_NONCE0_TX, "0xzz"]
+
+    results = await asyncio.wait_for(
+        batcher.submit_batch_async(f"http://127.0.0.1:{port}", batch, max_concurrency=2), 5
+    )
+    server.close()
+
+    assert [r["status"] for r in results] == ["submitted", "rejected", "submitted", "rejected"]
+    assert results[0] == {"hash": "0x" + "ab" * 32, "status": "submitted", "error": None}
+    # Rejected transactions still carry their locally computed hash
+    assert results[1]["hash"] == platform_rust.decode_transaction(EIP1559_TX)["hash"]
+    assert results[1]["error"] == "nonce too low"
+    assert results[3]["hash"] is None and "invalid hex" in results[3]["error"]
+    assert peak == 2
+
+
+async def test_submit_batch_async_reports_unreachable_endpoint():
+    server = await asyncio.start_server(lambda r, w: None, "127.0.0.1", 0)
+    port = server.sockets[0].getsockname()[1]
+    server.close()
+    await server.wait_closed()
+    batcher = platform_rust.TransactionBatcher()
+
+    results = await asyncio.wait_for(batcher.submit_batch_async(f"http://127.0.0.1:{port}", [LEGACY_TX]), 5)
+
+    assert results[0]["status"] == "failed" and results[0]["error"]
+    with pytest.raises(ValueError):
+        batcher.submit_batch_async("ftp://rpc.example", [LEGACY_TX])
+    with pytest.raises(ValueError):
+        batcher.submit_batch_async(f"http://127.0.0.1:{port}", [LEGACY_TX], max_concurrency=0)
+    with pytest.raises(ValueError):
+        batcher.submit_batch_async(f"http://127.0.0.1:{port}", [LEGACY_TX], timeout_secs=0.0)
+
+
+async def test_submit_batch_async_times_out_on_a_silent_node():
+    held = []
+
+    async def node(reader, writer):
+        # Accept the request but never answer it
+        held.append(writer)
+        await reader.readuntil(b"\r\n\r\n")
+
+    server = await asyncio.start_server(node, "127.0.0.1", 0)
+    port = server.sockets[0].getsockname()[1]
+    batcher = platform_rust.TransactionBatcher()
+
+    results = await asyncio.wait_for(
+        batcher.submit_batch_async(f"http://127.0.0.1:{port}", [LEGACY_TX], timeout_secs=0.2), 5
+    )
+    server.close()
+
+    assert results[0]["status"] == "failed"
+    assert "timed out" in results[0]["error"]
+
+
+async def test_submit_batch_async_speaks_tls_to_https_endpoints():
+    received = []
+
+    async def node(reader, writer):
+        # Record the first TLS record header, then drop the handshake
+        received.append(await reader.readexactly(3))
+        writer.close()
+
+    server = await asyncio.start_server(node, "127.0.0.1", 0)
+    port = server.sockets[0].getsockname()[1]
+    batcher = platform_rust.TransactionBatcher()
+
+    results = await asyncio.wait_for(batcher.submit_batch_async(f"https://127.0.0.1:{port}", [LEGACY_TX]), 5)
+    server.close()
+
+    # A ClientHello is a handshake record (0x16) on TLS 1.x, never plain HTTP
+    assert received[0][0] == 0x16 and received[0][1] == 0x03
+    assert results[0]["status"] == "failed"
+    assert "TLS handshake" in results[0]["error"]
+
+
+def test_submit_batch_async_exits_cleanly():
+    # Exiting right after the results resolve must not crash the interpreter
+    script = (
+        "import asyncio, socket, platform_rust\n"
+        "probe = socket.socket(); probe.bind(('127.0.0.1', 0)); port = probe.getsockname()[1]; probe.close()\n"
+        "async def main():\n"
+        "    batcher = platform_rust.TransactionBatcher()\n"
+        "    url = f'http://127.0.0.1:{port}'\n"
+        "    await asyncio.gather(*(batcher.submit_batch_async(url, ['0x00']) for _ in range(50)))\n"
+        "asyncio.run(main())\n"
+    )
+    env = dict(os.environ, PYTHONPATH=os.path.dirname(platform_rust.__file__))
+    for _ in range(5):
+        done = subprocess.run([sys.executable, "-c", script], env=env, capture_output=True, text=True, timeout=30)
+        assert done.returncode == 0, done.stderr